@@ -790,9 +790,14 @@ impl ComponentBuilder {
             match u.int_in_range::<u8>(0..=max_choice)? {
                 // Import.
                 0 => {
-                    let module = crate::limited_string(100, u)?;
+                    let module = crate::limited_string(100, self.config.string_alphabet, u)?;
                     let existing_module_imports = imports.entry(module.clone()).or_default();
-                    let field = crate::unique_string(100, existing_module_imports, u)?;
+                    let field = crate::unique_string(
+                        100,
+                        self.config.string_alphabet,
+                        existing_module_imports,
+                        u,
+                    )?;
                     let entity_type = match self.arbitrary_core_entity_type(
                         u,
                         &types,
@@ -811,7 +816,8 @@ impl ComponentBuilder {
 
                 // Export.
                 1 => {
-                    let name = crate::unique_string(100, &mut exports, u)?;
+                    let name =
+                        crate::unique_string(100, self.config.string_alphabet, &mut exports, u)?;
                     let entity_ty = match self.arbitrary_core_entity_type(
                         u,
                         &types,
@@ -1887,7 +1893,7 @@ struct CustomSection {
 
 impl<'a> Arbitrary<'a> for CustomSection {
     fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
-        let name = crate::limited_string(1_000, u)?;
+        let name = crate::limited_string(1_000, None, u)?;
         let data = u.arbitrary()?;
         Ok(CustomSection { name, data })
     }
@@ -1,6 +1,6 @@
 //! Configuring the shape of generated Wasm modules.
 
-use crate::InstructionKinds;
+use crate::{InstructionKinds, arbitrary_ratio};
 use anyhow::bail;
 use arbitrary::{Arbitrary, Result, Unstructured};
 
@@ -10,7 +10,7 @@ macro_rules! define_config {
         pub struct Config {
             $(
                 $(#[$field_attr:meta])*
-                pub $field:ident : $field_ty:ty = $default:expr,
+                pub $field:ident : $field_ty:ty = $default:expr $(; parse_with = $parser:path)?,
             )*
         }
     ) => {
@@ -103,6 +103,12 @@ macro_rules! define_config {
             /// with exactly the same names and types as those in the provided
             /// WebAssembly module.
             ///
+            /// This constrains the types, imports, and exports of the generated
+            /// module at once: all of the provided module's types are copied, in
+            /// the same rec groups, to the start of the generated module's type
+            /// section so that the imports and exports refer to the same type
+            /// indices as in the provided module.
+            ///
             /// Defaults to `None` which means arbitrary imports and exports will be
             /// generated.
             ///
@@ -219,6 +225,12 @@ macro_rules! define_config {
             /// with exactly the same names and types as those in the provided
             /// WebAssembly module.
             ///
+            /// This constrains the types, imports, and exports of the generated
+            /// module at once: all of the provided module's types are copied, in
+            /// the same rec groups, to the start of the generated module's type
+            /// section so that the imports and exports refer to the same type
+            /// indices as in the provided module.
+            ///
             /// Defaults to `None` which means arbitrary imports and exports will be
             /// generated.
             ///
@@ -242,7 +254,7 @@ macro_rules! define_config {
 
             $(
                 $(#[$field_attr])*
-                #[cfg_attr(feature = "clap", clap(long))]
+                #[cfg_attr(feature = "clap", clap(long $(, value_parser = $parser)?))]
                 pub $field: Option<$field_ty>,
             )*
         }
@@ -375,12 +387,57 @@ define_config! {
         /// Defaults to `true`.
         pub allow_floats: bool = true,
 
+        /// Determines whether we generate atomic instructions when the threads
+        /// or shared-everything-threads proposals are enabled.
+        ///
+        /// When `false` shared memories are still generated, but they're only
+        /// accessed with plain, non-atomic instructions.
+        ///
+        /// Defaults to `true`.
+        pub allow_atomics: bool = true,
+
+        /// Whether the first defined function should have a local of every
+        /// value type which is enabled, such as `i32`, `v128` and each
+        /// abstract reference type.
+        ///
+        /// Those locals count towards `max_locals` and come before any
+        /// arbitrary locals, but there is one of each type even if there are
+        /// more types than `max_locals`.
+        ///
+        /// Defaults to `false`.
+        pub all_valtypes_in_locals: bool = false,
+
+        /// Whether to panic if a generated atomic memory access doesn't use
+        /// its natural alignment.
+        ///
+        /// Atomic instructions are only valid with their natural alignment, so
+        /// this is a debugging aid to catch generation bugs as soon as they
+        /// happen rather than when the module is validated.
+        ///
+        /// Defaults to `false`.
+        pub assert_atomic_alignment: bool = false,
+
         /// Determines whether the bulk memory proposal is enabled for
         /// generating instructions.
         ///
         /// Defaults to `true`.
         pub bulk_memory_enabled: bool = true,
 
+        /// If set, adds this many functions to the module which call each
+        /// other in a linear chain.
+        ///
+        /// The functions all have type `[] -> []`. Each one's body is a single
+        /// `call` of the next, except for the last one whose body is empty.
+        /// The first function in the chain is exported, unless the exports are
+        /// fixed by [`Config::exports`] or [`Config::module_shape`]. The chain
+        /// is added on top of the module's other functions, regardless of
+        /// `max_funcs`, but isn't generated if a new type is needed and
+        /// `max_types` has been reached. The chain's `call` instructions
+        /// aren't counted against [`Config::max_total_instructions`].
+        ///
+        /// Defaults to `None`.
+        pub call_chain_depth: Option<usize> = None,
+
         /// Returns whether NaN values are canonicalized after all f32/f64
         /// operation. Defaults to false.
         ///
@@ -392,6 +449,121 @@ define_config! {
         /// instructions.
         pub canonicalize_nans: bool = false,
 
+        /// Whether to keep the static offsets of 64-bit memories within the
+        /// 32-bit range.
+        ///
+        /// When enabled, the `offset` immediates of memory instructions and
+        /// the offsets of active data segments on 64-bit memories never
+        /// exceed `u32::MAX`, and data segments on 64-bit memories don't use
+        /// a `global.get` offset. This is useful for engines which back 64-bit
+        /// memories with 32-bit allocations. Addresses computed at runtime
+        /// aren't affected.
+        ///
+        /// Defaults to `false`.
+        pub clamp_offsets_to_32bit: bool = false,
+
+        /// The probability, between `0.0` and `1.0`, that a table's element
+        /// type is a nullable reference to one of the module's function types.
+        ///
+        /// Such tables are otherwise only generated by chance. This only has an
+        /// effect when GC is enabled and the module has function types.
+        ///
+        /// Defaults to `0.0`.
+        pub concrete_func_table_element_ratio: f64 = 0.0,
+
+        /// The ratio, as a `(numerator, denominator)` pair, of arbitrary heap
+        /// types which are one of the module's concrete types rather than an
+        /// abstract heap type such as `any` or `func`.
+        ///
+        /// Raising this focuses generation on struct, array, and function
+        /// references. This only has an effect when GC is enabled, and
+        /// abstract heap types are always used when the module has no
+        /// concrete types to refer to. A numerator of zero never generates
+        /// concrete heap types, and the numerator must not be greater than
+        /// the denominator.
+        ///
+        /// Defaults to `None`, which picks concrete and abstract heap types
+        /// evenly.
+        pub concrete_heap_type_ratio: Option<(u32, u32)> = None; parse_with = parse_ratio,
+
+        /// The probability, between `0.0` and `1.0`, that a table's element
+        /// type is a reference to one of the module's struct or array types.
+        ///
+        /// Such tables are otherwise only generated by chance. Their element
+        /// type is sometimes non-nullable, in which case the table is given an
+        /// initialization expression. This only has an effect when GC is
+        /// enabled and the module has struct or array types.
+        ///
+        /// Defaults to `0.0`.
+        pub concrete_table_element_ratio: f64 = 0.0,
+
+        /// Whether to bias generated GC types toward subtyping types from
+        /// earlier rec groups.
+        ///
+        /// Types in one rec group may always declare supertypes in earlier rec
+        /// groups, but that otherwise only happens occasionally. When enabled,
+        /// about half of the types generated while such a supertype is
+        /// available will subtype a type from an earlier rec group.
+        ///
+        /// Defaults to `false`.
+        pub cross_rec_group_refs: bool = false,
+
+        /// The shape of the bytes used to initialize data segments.
+        ///
+        /// See the `DataContentKind` enum for details.
+        ///
+        /// Defaults to `DataContentKind::Random`.
+        pub data_content: DataContentKind = DataContentKind::Random,
+
+        /// The probability, between `0.0` and `1.0`, that a few dead
+        /// instructions are appended right after a `br`, `return`, or
+        /// `unreachable`.
+        ///
+        /// The dead code consumes operands from the polymorphic stack left
+        /// behind by the unconditional control transfer, exercising a
+        /// validator's handling of unreachable code. It never changes the
+        /// stack height, so the enclosing block still ends with the expected
+        /// results.
+        ///
+        /// Defaults to `0.0`.
+        pub dead_code_ratio: f64 = 0.0,
+
+        /// Whether to avoid generating structurally-identical types.
+        ///
+        /// When enabled, a newly generated rec group that is identical to an
+        /// existing rec group, taking into account references to types within
+        /// the same rec group, is discarded in favor of the existing one. This
+        /// means that no two rec groups in the type section canonicalize to
+        /// the same types, which makes for tidier output.
+        ///
+        /// Types copied from the `module_shape` or `available_imports` example
+        /// modules are kept as-is.
+        ///
+        /// Defaults to `false`.
+        pub deduplicate_types: bool = false,
+
+        /// The probability, between `0.0` and `1.0`, that a `br` or `br_if`
+        /// targets a label at a relative depth greater than one, when such a
+        /// label is a valid target.
+        ///
+        /// Branch targets are otherwise chosen uniformly among all valid
+        /// enclosing labels.
+        ///
+        /// Defaults to `0.0`.
+        pub deep_branch_ratio: f64 = 0.0,
+
+        /// Whether the module may define its own functions, tables, memories,
+        /// globals, tags, element segments, and data segments.
+        ///
+        /// When disabled, all of the `min_*` counts for definitions are
+        /// treated as zero and the generated module only imports and exports
+        /// entities, making it pure glue. Note that the exports required by
+        /// the `exports` module are still defined when necessary to satisfy
+        /// them, so that option takes precedence over this one.
+        ///
+        /// Defaults to `true`.
+        pub definitions_allowed: bool = true,
+
         /// Returns whether we should avoid generating code that will possibly
         /// trap.
         ///
@@ -401,7 +573,8 @@ define_config! {
         /// choose some arbitrary non-trapping behavior. For example, if we
         /// detect that a Load instruction would attempt to access out-of-bounds
         /// memory, we instead pretend the load succeeded and push 0 onto the
-        /// stack.
+        /// stack. Similarly, when `saturating_float_to_int_enabled` is also
+        /// set, only the saturating float-to-int conversions are generated.
         ///
         /// One type of trap that we can't currently avoid is
         /// StackOverflow. Even when `disallow_traps` is set to true, wasm-smith
@@ -411,17 +584,164 @@ define_config! {
         /// Defaults to `false`.
         pub disallow_traps: bool = false,
 
+        /// Controls how active element segments lay out their elements within
+        /// their table.
+        ///
+        /// See the `ElementDensity` enum for details.
+        ///
+        /// Defaults to `ElementDensity::Arbitrary`.
+        pub element_density: ElementDensity = ElementDensity::Arbitrary,
+
+        /// Forces the data count section to be present (`Some(true)`) or absent
+        /// (`Some(false)`) in generated modules.
+        ///
+        /// When `None` the section is emitted exactly when it's required, that
+        /// is when bulk memory is enabled and a function body refers to a data
+        /// segment with `memory.init`, `data.drop`, `array.new_data`, or
+        /// `array.init_data`.
+        ///
+        /// Note that this only affects encoding. Forcing the section to be
+        /// absent in a module which uses any of those instructions, or
+        /// forcing it to be present without bulk memory enabled, will produce
+        /// an intentionally invalid module. This is mostly useful for testing
+        /// decoders.
+        ///
+        /// Defaults to `None`.
+        pub emit_data_count_section: Option<bool> = None,
+
+        /// Whether to add a copy of one of the module's rec groups which is
+        /// canonically equal to the original, and then use types from the two
+        /// rec groups interchangeably.
+        ///
+        /// References between types within the copied rec group are rewritten
+        /// to refer to the copy, so the two rec groups define the same types
+        /// as far as validation is concerned. Two functions then use the
+        /// original and copied types in place of one another: one takes a
+        /// reference to an original type and returns it as the copied type,
+        /// and another passes a reference to the copied type to it. This
+        /// exercises the canonicalization of types in validators and engines.
+        ///
+        /// This only has an effect when GC is enabled and
+        /// [`Config::refs_in_signatures`] is `true`. The copy isn't removed by
+        /// [`Config::deduplicate_types`].
+        ///
+        /// Defaults to `false`.
+        pub emit_canonical_duplicate_rec_groups: bool = false,
+
         /// Determines whether the exception-handling proposal is enabled for
         /// generating instructions.
         ///
         /// Defaults to `true`.
         pub exceptions_enabled: bool = true,
 
+        /// Whether to make one function exported, placed in a table, and
+        /// called directly, all in the same module.
+        ///
+        /// When enabled, one function is exported if it isn't already, placed
+        /// at offset zero of a `funcref` table by an additional active element
+        /// segment, and called at the start of another function's body. A
+        /// defined table's minimum size may be raised to one, or a table added
+        /// if `max_tables` allows it, to make room for the function, regardless
+        /// of `max_element_segments`.
+        ///
+        /// This raises `min_funcs` to at least two. A function is shared
+        /// whenever possible, which isn't the case for modules generated with
+        /// `exports` or `module_shape`, or when:
+        ///
+        /// * no defined function has a generated body to call from,
+        /// * no other function has an unshared type whose parameters are all
+        ///   defaultable, or
+        /// * there's no `funcref` table that can hold the function, and
+        ///   `max_tables` has been reached or, without reference types, the
+        ///   module already has a table.
+        ///
+        /// Defaults to `false`.
+        pub exercise_func_sharing: bool = false,
+
+        /// Whether to exercise the full lifecycle of data segments in a single
+        /// memory.
+        ///
+        /// When enabled along with `bulk_memory_enabled`, the generated module
+        /// contains an active and a passive data segment, and the body of one
+        /// of its functions starts with a `memory.init` from the passive
+        /// segment, a `data.drop` of it, and a `memory.copy`, all on the memory
+        /// that the active segment initializes. These instructions operate on
+        /// zero bytes and therefore never trap.
+        ///
+        /// This raises `min_memories` and `min_funcs` to at least one, and may
+        /// add data segments beyond `max_data_segments`. Modules that
+        /// still end up without a defined function, for example because there
+        /// are no function types, won't contain the instructions.
+        ///
+        /// Defaults to `false`.
+        pub exercise_memory_lifecycle: bool = false,
+
+        /// Whether to export at least one function, table, memory, global,
+        /// and tag, for each of those kinds of which the module has any.
+        ///
+        /// These exports are chosen before, and count towards, the arbitrary
+        /// exports bounded by [`Config::min_exports`] and
+        /// [`Config::max_exports`], but are generated even if there are more
+        /// kinds than `max_exports`. This has no effect when the module's
+        /// exports are given by [`Config::exports`] or
+        /// [`Config::module_shape`].
+        ///
+        /// Defaults to `false`.
+        pub export_all_kinds: bool = false,
+
         /// Export all WebAssembly objects in the module. Defaults to false.
         ///
         /// This overrides [`Config::min_exports`] and [`Config::max_exports`].
         pub export_everything: bool = false,
 
+        /// The order in which entries are written to the export section.
+        ///
+        /// See the `ExportOrdering` enum for details.
+        ///
+        /// Defaults to `ExportOrdering::Generation`.
+        pub export_ordering: ExportOrdering = ExportOrdering::Generation,
+
+        /// Whether types that are alone in their recursion group should still
+        /// be encoded with an explicit `(rec ...)` wrapper.
+        ///
+        /// By default a single-type rec group uses the shorter implicit
+        /// encoding. The explicit form is only valid with the GC proposal, so
+        /// this has no effect unless [`Config::gc_enabled`] is also set.
+        ///
+        /// Defaults to `false`.
+        pub force_explicit_rec_groups: bool = false,
+
+        /// Whether every generated module must have a start function.
+        ///
+        /// If no function with an empty signature was otherwise generated, a
+        /// defined function of type `[] -> []` is added to serve as the start
+        /// function, as long as `max_funcs` and `max_types` allow it. This has
+        /// no effect unless `allow_start_export` is enabled.
+        ///
+        /// Defaults to `false`.
+        pub force_start_function: bool = false,
+
+        /// Whether every defined table with a nonzero minimum size should be
+        /// fully populated by an active element segment covering
+        /// `[0, minimum)`.
+        ///
+        /// Entries are non-null wherever a non-null constant of the table's
+        /// element type is available, i.e. a matching function for `ref.func`
+        /// or a matching immutable global. Otherwise, for example for an
+        /// `externref` table with no suitable globals, entries may still be
+        /// explicit `ref.null`s. Without reference types, which rules out
+        /// `ref.null` entries, a table is only populated if the module has any
+        /// functions. Tables other than a `funcref` table 0 are only populated
+        /// when bulk memory is enabled, since their segments need an explicit
+        /// table index.
+        ///
+        /// These segments count towards [`Config::max_element_segments`] but,
+        /// since they must cover the whole table, aren't limited by
+        /// [`Config::max_elements`].
+        ///
+        /// Defaults to `false`.
+        pub fully_initialize_tables: bool = false,
+
         /// Determines whether the GC proposal is enabled when generating a Wasm
         /// module.
         ///
@@ -434,10 +754,84 @@ define_config! {
         /// Defaults to `false`.
         pub custom_page_sizes_enabled: bool = false,
 
+        /// Whether to add a working `cabi_realloc` function to the module and
+        /// export it under that name.
+        ///
+        /// The function has the canonical ABI's `(func (param i32 i32 i32 i32)
+        /// (result i32))` signature and is a bump allocator over a 32-bit
+        /// memory, which is added if the module doesn't have one. It grows the
+        /// memory as needed and copies the old allocation's contents when
+        /// reallocating, if bulk memory is enabled. When an allocation can't be
+        /// satisfied it traps, or returns `0` if [`Config::disallow_traps`] is
+        /// enabled. The allocator's instructions aren't counted against
+        /// [`Config::max_total_instructions`], and the function and its heap
+        /// pointer global are added regardless of [`Config::max_funcs`] and
+        /// [`Config::max_globals`].
+        ///
+        /// Defaults to `false`.
+        pub generate_cabi_realloc: bool = false,
+
         /// Returns whether we should generate custom sections or not. Defaults
         /// to false.
         pub generate_custom_sections: bool = false,
 
+        /// Whether to emit a `target_features` custom section, like the one
+        /// LLVM produces, listing the proposals the module uses.
+        ///
+        /// Each listed feature has the `+` ("used") prefix. Only proposals
+        /// that the module actually exercises are listed, as reported by
+        /// [`Module::feature_coverage`](crate::Module::feature_coverage), so the
+        /// listed features are always enabled in this `Config`.
+        ///
+        /// Defaults to `false`.
+        pub generate_target_features_section: bool = false,
+
+        /// A dictionary of field names to give generated imports, such as
+        /// WASI function names like `fd_write` and `proc_exit`.
+        ///
+        /// When provided and non-empty, each arbitrary import's field name is
+        /// chosen from this dictionary rather than being an arbitrary string,
+        /// which makes generated modules look more like real programs. A
+        /// numeric suffix is appended if needed to keep import names unique.
+        /// Imports copied from `available_imports` or `module_shape` are
+        /// unaffected.
+        ///
+        /// Defaults to `None`.
+        pub import_field_dictionary: Option<Vec<String>> = None; parse_with = parse_comma_separated,
+
+        /// Whether generated modules must instantiate without trapping.
+        ///
+        /// This implies `disallow_traps`, which already keeps active data and
+        /// element segments within the minimum size of their memory or table,
+        /// and additionally forbids a start function since it could run
+        /// arbitrary code during instantiation. Imports must be provided with
+        /// at least the minimum size of their declared types.
+        ///
+        /// Defaults to `false`.
+        pub instantiable: bool = false,
+
+        /// Whether export names must be valid component-model kebab-case
+        /// identifiers, i.e. lowercase words separated by hyphens such as
+        /// `foo-bar2`.
+        ///
+        /// This makes generated core modules more likely to be usable with
+        /// component tooling. Export names copied from the `exports` example
+        /// module are kept as-is.
+        ///
+        /// Defaults to `false`.
+        pub kebab_case_export_names: bool = false,
+
+        /// Whether to generate the legacy exception-handling instructions
+        /// (`try`, `catch`, `catch_all`, `delegate`, and `rethrow`) instead of
+        /// `try_table` and `throw_ref`.
+        ///
+        /// Only one form is used within a module, and with this enabled no
+        /// `exnref` values are generated either. This has no effect unless
+        /// `exceptions_enabled` is also enabled.
+        ///
+        /// Defaults to `false`.
+        pub legacy_exceptions: bool = false,
+
         /// Returns the maximal size of the `alias` section. Defaults to 1000.
         pub max_aliases: usize = 1000,
 
@@ -486,6 +880,31 @@ define_config! {
         /// appended to the function body to finish block scopes.
         pub max_instructions: usize = 100,
 
+        /// The maximum number of instructions to generate across all function
+        /// bodies in the module, or `None` for no module-wide limit. Defaults
+        /// to `None`.
+        ///
+        /// Once the limit is reached, the remaining function bodies are given
+        /// the smallest body that terminates: empty if the function has no
+        /// results, and otherwise a single `unreachable`, or a constant for
+        /// each result when `disallow_traps` is enabled. The limit therefore
+        /// can't be honored if it is smaller than the total size of those
+        /// smallest bodies, or when `disallow_traps` is enabled and a result
+        /// is a non-nullable reference that can't be allocated without
+        /// operands, in which case that function's generated body is kept.
+        /// Bodies of raw bytes from `allow_invalid_funcs`, the functions
+        /// added by `call_chain_depth` and `generate_cabi_realloc`, and
+        /// instructions added by `exercise_memory_lifecycle`,
+        /// `exercise_func_sharing`, or `meaningful_start` aren't counted.
+        pub max_total_instructions: Option<usize> = None,
+
+        /// The maximum number of locals, not including parameters, to generate
+        /// in a function body. Defaults to 100.
+        ///
+        /// Note that validators impose their own limit on the total number of
+        /// locals, which is 50,000 for `wasmparser`.
+        pub max_locals: usize = 100,
+
         /// The maximum number of memories to use. Defaults to 1.
         ///
         /// This includes imported memories.
@@ -544,8 +963,11 @@ define_config! {
         /// parameters and 2 results would take 11 types (one for the type, 10
         /// for params/results). A module type with 2 imports and 3 exports
         /// would take 6 (module + imports + exports) plus the size of each
-        /// import/export type. This is a somewhat rough measurement that is not
-        /// intended to be very precise.
+        /// import/export type. Reference types to concrete GC types
+        /// additionally count the size of the types they refer to, so a
+        /// parameter of type `(ref $s)` where `$s` is a struct with 3 fields
+        /// takes 5 (the parameter, the struct, and its fields). This is a
+        /// somewhat rough measurement that is not intended to be very precise.
         ///
         /// Defaults to 1000.
         pub max_type_size: u32 = 1000,
@@ -560,6 +982,20 @@ define_config! {
         /// Note that this is irrelevant unless value model support is enabled.
         pub max_values: usize = 10,
 
+        /// Whether a generated start function must have an observable effect
+        /// when it runs during instantiation.
+        ///
+        /// When a defined function is chosen as the start function, its body
+        /// begins with a `global.set` of a mutable global, a `memory.grow`, or
+        /// a zero-length `table.fill`, chosen among the module's globals,
+        /// memories, and tables. If the module has none of these then a
+        /// mutable global is added for the start function to set. The rest of
+        /// the body is generated as usual. This has no effect unless
+        /// `allow_start_export` is enabled.
+        ///
+        /// Defaults to `false`.
+        pub meaningful_start: bool = false,
+
         /// Returns whether 64-bit memories are allowed. Defaults to true.
         ///
         /// Note that this is the gate for the memory64 proposal to WebAssembly.
@@ -575,6 +1011,20 @@ define_config! {
         /// See the `MemoryOffsetChoices` struct for details.
         pub memory_offset_choices: MemoryOffsetChoices = MemoryOffsetChoices::default(),
 
+        /// The minimum total number of bytes across all data segments.
+        ///
+        /// When the generated data segments are smaller than this in total,
+        /// existing segments are made larger, and if they don't have enough
+        /// room a single segment is added within `max_data_segments`. Added
+        /// bytes repeat a segment's existing contents. Under `disallow_traps`
+        /// active segments only grow up to the minimum size of their memory,
+        /// so the floor may only be reached with passive segments, which
+        /// require bulk memory. If the segments can't hold the bytes then the
+        /// floor isn't reached.
+        ///
+        /// Defaults to `None`.
+        pub min_data_bytes: Option<usize> = None,
+
         /// The minimum number of data segments to generate. Defaults to 0.
         pub min_data_segments: usize = 0,
 
@@ -616,7 +1066,8 @@ define_config! {
 
         /// The minimum number of memories to use. Defaults to 0.
         ///
-        /// This includes imported memories.
+        /// This includes imported memories. If this is larger than
+        /// `max_memories` then `max_memories` is raised to match.
         pub min_memories: u32 = 0,
 
         /// The minimum number of tables to use. Defaults to 0.
@@ -639,23 +1090,127 @@ define_config! {
         /// the size would otherwise be smaller than number returned here.
         pub min_uleb_size: u8 = 1,
 
+        /// The probability, between `0.0` and `1.0`, that a generated memory
+        /// is forced to have a minimum size of zero pages.
+        ///
+        /// This exercises the handling of empty memories, which are otherwise
+        /// somewhat rare. The ratio is ignored when `disallow_traps` is
+        /// enabled since memories must then have at least one page.
+        ///
+        /// Defaults to `0.0`.
+        pub min_zero_memory_ratio: f64 = 0.0,
+
         /// Determines whether the multi-value results are enabled.
         ///
         /// Defaults to `true`.
         pub multi_value_enabled: bool = true,
 
+        /// The probability, between `0.0` and `1.0`, that a generated
+        /// `memory.copy` copies between provably overlapping source and
+        /// destination ranges of the same memory.
+        ///
+        /// Overlapping copies must behave like `memmove`, which is an easy
+        /// thing for a backend to get wrong. An overlapping copy pushes its
+        /// own constant operands, which stay within the memory's minimum size,
+        /// so it is also generated when no suitable operands are on the stack
+        /// and when `disallow_traps` is enabled.
+        ///
+        /// Defaults to `0.0`.
+        pub overlapping_copy_ratio: f64 = 0.0,
+
+        /// The probability, between `0.0` and `1.0`, that a generated `loop`
+        /// takes parameters when multi-value is enabled.
+        ///
+        /// A loop with parameters consumes values from the stack on entry, and
+        /// every branch back to the loop must supply new values for those
+        /// parameters, so values are carried across iterations. Such loops
+        /// are only generated when a function type whose parameters are on
+        /// the stack exists in the module.
+        ///
+        /// Defaults to `0.0`.
+        pub parameterized_loop_ratio: f64 = 0.0,
+
+        /// The probability, between `0.0` and `1.0`, that a generated
+        /// `memory.init` pushes its own constant operands which copy either a
+        /// full or a partial range of a passive data segment into bounds of
+        /// the memory's minimum size.
+        ///
+        /// Partial ranges start past the beginning of the segment or end
+        /// before its end, which exercises the bounds arithmetic of
+        /// `memory.init`. Such inits are also generated when no suitable
+        /// operands are on the stack and when `disallow_traps` is enabled. In
+        /// the latter case `data.drop` is never generated, since initializing
+        /// from a dropped segment traps.
+        ///
+        /// Defaults to `0.0`.
+        pub partial_memory_init_ratio: f64 = 0.0,
+
+        /// An adversarial, but still valid, shape to give generated modules
+        /// for stress testing consumers such as validators.
+        ///
+        /// See the `PathologyKind` enum for details.
+        ///
+        /// Defaults to `None`.
+        pub pathological: Option<PathologyKind> = None,
+
+        /// Whether `call` instructions should prefer imported functions over
+        /// defined functions as their callee.
+        ///
+        /// When enabled, and an imported function is a valid callee, calls
+        /// will only target imported functions. This is useful for exercising
+        /// the overhead of calls into the host.
+        ///
+        /// Defaults to `false`.
+        pub prefer_calling_imports: bool = false,
+
         /// Determines whether the reference types proposal is enabled for
         /// generating instructions.
         ///
         /// Defaults to `true`.
         pub reference_types_enabled: bool = true,
 
+        /// Whether reference types may be used as the parameters or results
+        /// of generated function types.
+        ///
+        /// When `false` references are still used elsewhere, such as in tables,
+        /// globals and locals, but never in a function signature. Function
+        /// types supplied through [`Config::available_imports`] or
+        /// [`Config::module_shape`] are used as-is.
+        ///
+        /// Defaults to `true`.
+        pub refs_in_signatures: bool = true,
+
         /// Determines whether the Relaxed SIMD proposal is enabled for
         /// generating instructions.
         ///
         /// Defaults to `true`.
         pub relaxed_simd_enabled: bool = true,
 
+        /// The probability, between `0.0` and `1.0`, that a struct or array
+        /// field refers to a type in the rec group being generated.
+        ///
+        /// Such fields are nullable references to the type itself, to an
+        /// earlier type in the rec group, or to a later one through a forward
+        /// reference, which forms self-referential and mutually-recursive
+        /// types like linked list nodes. Fields of shared types only refer to
+        /// earlier shared types in the rec group since the sharedness of later
+        /// types isn't known yet. This only has an effect when GC is enabled.
+        ///
+        /// Defaults to `0.0`.
+        pub recursive_type_bias: f64 = 0.0,
+
+        /// The probability, between `0.0` and `1.0`, that an arbitrary export
+        /// re-exports an imported function, table, memory, global, or tag
+        /// rather than picking from the whole index space of its kind.
+        ///
+        /// Imported entities are always candidates for export; this only
+        /// biases the selection toward them. Re-exports are given fresh
+        /// export names like any other export. This has no effect when
+        /// `export_everything` is enabled.
+        ///
+        /// Defaults to `0.0`.
+        pub reexport_imports_ratio: f64 = 0.0,
+
         /// Determines whether the non-trapping float-to-int conversions
         /// proposal is enabled.
         ///
@@ -686,6 +1241,27 @@ define_config! {
         /// Defaults to `true`.
         pub simd_enabled: bool = true,
 
+        /// The characters that generated import and export names are made of.
+        ///
+        /// This applies to names generated for core modules and components.
+        /// Names taken from [`Config::import_field_dictionary`] are used
+        /// as-is. `None` behaves the same as `Some(StringAlphabet::Utf8)`.
+        ///
+        /// Defaults to `None`.
+        pub string_alphabet: Option<StringAlphabet> = None,
+
+        /// The probability, between `0.0` and `1.0`, that a constant
+        /// expression is a `global.get` of a global whose type is a strict
+        /// subtype of the expression's type, when such a global exists.
+        ///
+        /// With GC enabled this exercises subtyping within constant
+        /// expressions, such as a defined `anyref` global initialized from an
+        /// imported `(ref $struct)` global. Such globals are otherwise chosen
+        /// just as often as any other initializer.
+        ///
+        /// Defaults to `0.0`.
+        pub subtype_global_get_ratio: f64 = 0.0,
+
         /// Determines whether the tail calls proposal is enabled for generating
         /// instructions.
         ///
@@ -706,6 +1282,44 @@ define_config! {
         /// Defaults to `true`.
         pub threads_enabled: bool = true,
 
+        /// The probability, between `0.0` and `1.0`, that a generated
+        /// `call_indirect` or `return_call_indirect` is given a table index
+        /// which is guaranteed to trap at runtime.
+        ///
+        /// The index is either beyond the table's maximum size or, when
+        /// reference types are enabled, the index of a null slot that was just
+        /// added with `table.grow`. Indirect calls are never generated when
+        /// [`Config::disallow_traps`] is enabled, so this has no effect then.
+        ///
+        /// Defaults to `0.0`.
+        pub trapping_indirect_call_ratio: f64 = 0.0,
+
+        /// Forces every generated memory to have the same index type:
+        /// `Some(true)` makes all memories 64-bit and `Some(false)` makes all
+        /// memories 32-bit.
+        ///
+        /// When `None` each memory's index type is chosen independently. Note
+        /// that 64-bit memories additionally require
+        /// [`Config::memory64_enabled`]; without it all memories are 32-bit
+        /// regardless of this setting. Memories imported from
+        /// [`Config::available_imports`] or [`Config::module_shape`] keep
+        /// their own index types.
+        ///
+        /// Defaults to `None`.
+        pub uniform_memory_index_type: Option<bool> = None,
+
+        /// Whether every parameter and local of a generated function should be
+        /// both read and written at least once in its body.
+        ///
+        /// Functions otherwise frequently leave some of their locals untouched.
+        /// When this is enabled any local that wasn't already read or written by
+        /// the time the body is finished gets a `local.get` / `local.set` pair
+        /// appended before the function's control frames are closed. This is
+        /// useful for stressing register allocation.
+        ///
+        /// Defaults to `false`.
+        pub use_all_locals: bool = false,
+
         /// Indicates whether wasm-smith is allowed to generate invalid function
         /// bodies.
         ///
@@ -725,6 +1339,20 @@ define_config! {
         /// Defaults to `false`.
         pub wide_arithmetic_enabled: bool = false,
 
+        /// The probability, between `0.0` and `1.0`, that the offset of an
+        /// active data segment is an extended constant expression, using the
+        /// arithmetic instructions of the [extended-const proposal].
+        ///
+        /// This only has an effect when `extended_const_enabled` is `true`.
+        /// Under `disallow_traps` such offsets evaluate to the same in-bounds
+        /// offset that would otherwise be used, and otherwise they may also be
+        /// arbitrary expressions over constants and globals.
+        ///
+        /// [extended-const proposal]: https://github.com/WebAssembly/extended-const
+        ///
+        /// Defaults to `0.0`.
+        pub extended_const_data_offset_ratio: f64 = 0.0,
+
         /// Determines whether the [extended-const proposal] is enabled.
         ///
         /// [extended-const proposal]: https://github.com/WebAssembly/extended-const
@@ -792,8 +1420,254 @@ impl std::str::FromStr for MemoryOffsetChoices {
     }
 }
 
+/// How active element segments are laid out within their table.
+///
+/// Element segments whose offset is a global are only generated with
+/// `ElementDensity::Arbitrary`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Deserialize, serde_derive::Serialize)
+)]
+pub enum ElementDensity {
+    /// Offsets and sizes are chosen arbitrarily, and may be out of bounds
+    /// unless `disallow_traps` is enabled.
+    #[default]
+    Arbitrary,
+    /// Each active segment starts at offset zero and has as many elements as
+    /// the table's minimum size, filling it without gaps.
+    ///
+    /// The number of elements is still capped by `max_elements`.
+    Dense,
+    /// Each active segment has a handful of elements at an arbitrary
+    /// in-bounds offset, scattering entries across the table.
+    Sparse,
+}
+
+impl std::str::FromStr for ElementDensity {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "arbitrary" => Ok(ElementDensity::Arbitrary),
+            "dense" => Ok(ElementDensity::Dense),
+            "sparse" => Ok(ElementDensity::Sparse),
+            _ => Err(format!("unknown element density: {s}")),
+        }
+    }
+}
+
+/// The contents of generated data segments.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Deserialize, serde_derive::Serialize)
+)]
+pub enum DataContentKind {
+    /// Arbitrary bytes.
+    #[default]
+    Random,
+    /// Only zero bytes.
+    Zeros,
+    /// Printable ASCII characters, in the range `0x20..=0x7e`.
+    Ascii,
+    /// A short arbitrary pattern of between one and eight bytes, repeated
+    /// for the length of the segment.
+    RepeatedPattern,
+}
+
+impl std::str::FromStr for DataContentKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(DataContentKind::Random),
+            "zeros" => Ok(DataContentKind::Zeros),
+            "ascii" => Ok(DataContentKind::Ascii),
+            "repeated-pattern" => Ok(DataContentKind::RepeatedPattern),
+            _ => Err(format!("unknown data content kind: {s}")),
+        }
+    }
+}
+
+/// The characters used in generated names, selected with
+/// [`Config::string_alphabet`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Deserialize, serde_derive::Serialize)
+)]
+pub enum StringAlphabet {
+    /// Printable ASCII characters, in the range `0x20..=0x7e`.
+    Ascii,
+    /// ASCII letters and digits.
+    Alphanumeric,
+    /// Arbitrary UTF-8.
+    #[default]
+    Utf8,
+}
+
+impl std::str::FromStr for StringAlphabet {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(StringAlphabet::Ascii),
+            "alphanumeric" => Ok(StringAlphabet::Alphanumeric),
+            "utf8" => Ok(StringAlphabet::Utf8),
+            _ => Err(format!("unknown string alphabet: {s}")),
+        }
+    }
+}
+
+/// The order of entries in a generated module's export section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Deserialize, serde_derive::Serialize)
+)]
+pub enum ExportOrdering {
+    /// Exports appear in the order in which they were generated.
+    #[default]
+    Generation,
+    /// Exports are sorted by name.
+    ByName,
+    /// Exports are grouped by kind (functions, tables, memories, globals and
+    /// then tags), keeping generation order within each group.
+    ByKind,
+}
+
+impl std::str::FromStr for ExportOrdering {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "generation" => Ok(ExportOrdering::Generation),
+            "by-name" => Ok(ExportOrdering::ByName),
+            "by-kind" => Ok(ExportOrdering::ByKind),
+            _ => Err(format!("unknown export ordering: {s}")),
+        }
+    }
+}
+
+/// Adversarial module shapes which can be requested with
+/// [`Config::pathological`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_derive::Deserialize, serde_derive::Serialize)
+)]
+pub enum PathologyKind {
+    /// Every function body starts with this many nested `block`s, all of
+    /// which are open at once.
+    DeepBlockNesting(usize),
+    /// All of the module's types are generated in a single rec group of
+    /// `max_types` types.
+    ///
+    /// This requires GC to be enabled, since otherwise every rec group holds
+    /// exactly one type.
+    LongRecGroup,
+}
+
+impl std::str::FromStr for PathologyKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some(("deep-block-nesting", depth)) => depth
+                .parse()
+                .map(PathologyKind::DeepBlockNesting)
+                .map_err(|e| format!("invalid block nesting depth: {e}")),
+            None if s == "long-rec-group" => Ok(PathologyKind::LongRecGroup),
+            _ => Err(format!("unknown pathology: {s}")),
+        }
+    }
+}
+
+/// Parses a comma-separated list of strings, such as the import field names
+/// in `--import-field-dictionary fd_write,proc_exit`.
+#[cfg(feature = "clap")]
+fn parse_comma_separated(s: &str) -> Result<Vec<String>, String> {
+    Ok(s.split(',').map(|s| s.to_string()).collect())
+}
+
+/// Parses a ratio written as `numerator/denominator`, such as the
+/// `3/4` in `--concrete-heap-type-ratio 3/4`.
+#[cfg(feature = "clap")]
+fn parse_ratio(s: &str) -> Result<(u32, u32), String> {
+    let (numerator, denominator) = s
+        .split_once('/')
+        .ok_or_else(|| format!("expected a ratio such as `1/2`, found `{s}`"))?;
+    let numerator = numerator.parse::<u32>().map_err(|e| e.to_string())?;
+    let denominator = denominator.parse::<u32>().map_err(|e| e.to_string())?;
+    if denominator == 0 || numerator > denominator {
+        return Err(format!("`{s}` is not a ratio between 0 and 1"));
+    }
+    Ok((numerator, denominator))
+}
+
+/// Probabilities with which [`Config::arbitrary_with_weights`] enables each
+/// proposal.
+///
+/// Each weight is a probability between `0.0` and `1.0`. A weight of `None`,
+/// the default, enables the proposal with the same coin flip as the
+/// [`Arbitrary`] implementation for [`Config`]. Note that proposals may still
+/// be disabled afterwards when a proposal they depend on is disabled, for
+/// example GC requires reference types.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[allow(missing_docs)]
+pub struct SwarmWeights {
+    pub bulk_memory: Option<f64>,
+    pub exceptions: Option<f64>,
+    pub extended_const: Option<f64>,
+    pub gc: Option<f64>,
+    pub memory64: Option<f64>,
+    pub multi_value: Option<f64>,
+    pub reference_types: Option<f64>,
+    pub relaxed_simd: Option<f64>,
+    pub saturating_float_to_int: Option<f64>,
+    pub sign_extension: Option<f64>,
+    pub simd: Option<f64>,
+    pub tail_call: Option<f64>,
+    pub threads: Option<f64>,
+}
+
+/// Decide whether to enable a proposal with the given weight.
+fn enable_with_weight(u: &mut Unstructured<'_>, weight: Option<f64>) -> Result<bool> {
+    match weight {
+        None => u.arbitrary(),
+        Some(weight) => arbitrary_ratio(u, weight),
+    }
+}
+
 impl<'a> Arbitrary<'a> for Config {
     fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Config::arbitrary_with_weights(u, &SwarmWeights::default())
+    }
+}
+
+impl Config {
+    /// Generate an arbitrary `Config` for swarm testing, like its
+    /// [`Arbitrary`] implementation, but enabling each proposal according to
+    /// `weights`.
+    ///
+    /// This can be used to focus fuzzing campaigns on particular combinations
+    /// of proposals, for example by enabling GC less often or never.
+    ///
+    /// ```
+    /// use arbitrary::Unstructured;
+    /// use wasm_smith::{Config, SwarmWeights};
+    ///
+    /// # let bytes = [0; 64];
+    /// let mut u = Unstructured::new(&bytes);
+    /// let weights = SwarmWeights {
+    ///     gc: Some(0.3),
+    ///     threads: Some(0.0),
+    ///     ..SwarmWeights::default()
+    /// };
+    /// let config = Config::arbitrary_with_weights(&mut u, &weights)?;
+    /// assert!(!config.threads_enabled);
+    /// # Ok::<(), arbitrary::Error>(())
+    /// ```
+    pub fn arbitrary_with_weights(
+        u: &mut Unstructured<'_>,
+        weights: &SwarmWeights,
+    ) -> Result<Config> {
         const MAX_MAXIMUM: usize = 1000;
 
         let mut config = Config {
@@ -812,20 +1686,23 @@ impl<'a> Arbitrary<'a> for Config {
             max_memory32_bytes: u.int_in_range(0..=u32::MAX as u64 + 1)?,
             max_memory64_bytes: u.int_in_range(0..=u64::MAX as u128 + 1)?,
             min_uleb_size: u.int_in_range(0..=5)?,
-            bulk_memory_enabled: u.arbitrary()?,
-            reference_types_enabled: u.arbitrary()?,
-            simd_enabled: u.arbitrary()?,
-            multi_value_enabled: u.arbitrary()?,
+            bulk_memory_enabled: enable_with_weight(u, weights.bulk_memory)?,
+            reference_types_enabled: enable_with_weight(u, weights.reference_types)?,
+            simd_enabled: enable_with_weight(u, weights.simd)?,
+            multi_value_enabled: enable_with_weight(u, weights.multi_value)?,
             max_aliases: u.int_in_range(0..=MAX_MAXIMUM)?,
             max_nesting_depth: u.int_in_range(0..=10)?,
-            saturating_float_to_int_enabled: u.arbitrary()?,
-            sign_extension_ops_enabled: u.arbitrary()?,
-            relaxed_simd_enabled: u.arbitrary()?,
-            exceptions_enabled: u.arbitrary()?,
-            threads_enabled: u.arbitrary()?,
-            tail_call_enabled: u.arbitrary()?,
-            gc_enabled: u.arbitrary()?,
-            memory64_enabled: u.arbitrary()?,
+            saturating_float_to_int_enabled: enable_with_weight(
+                u,
+                weights.saturating_float_to_int,
+            )?,
+            sign_extension_ops_enabled: enable_with_weight(u, weights.sign_extension)?,
+            relaxed_simd_enabled: enable_with_weight(u, weights.relaxed_simd)?,
+            exceptions_enabled: enable_with_weight(u, weights.exceptions)?,
+            threads_enabled: enable_with_weight(u, weights.threads)?,
+            tail_call_enabled: enable_with_weight(u, weights.tail_call)?,
+            gc_enabled: enable_with_weight(u, weights.gc)?,
+            memory64_enabled: enable_with_weight(u, weights.memory64)?,
             allowed_instructions: {
                 use flagset::Flags;
                 let mut allowed = Vec::new();
@@ -840,7 +1717,10 @@ impl<'a> Arbitrary<'a> for Config {
             max_table_elements: u.int_in_range(0..=1_000_000)?,
             disallow_traps: u.arbitrary()?,
             allow_floats: u.arbitrary()?,
-            extended_const_enabled: u.arbitrary()?,
+            allow_atomics: true,
+            all_valtypes_in_locals: false,
+            assert_atomic_alignment: false,
+            extended_const_enabled: enable_with_weight(u, weights.extended_const)?,
 
             // These fields, unlike the ones above, are less useful to set.
             // They either make weird inputs or are for features not widely
@@ -853,6 +1733,7 @@ impl<'a> Arbitrary<'a> for Config {
             min_exports: 0,
             min_element_segments: 0,
             min_elements: 0,
+            min_data_bytes: None,
             min_data_segments: 0,
             min_memories: 0,
             min_tables: 0,
@@ -863,14 +1744,59 @@ impl<'a> Arbitrary<'a> for Config {
             max_values: 0,
             memory_offset_choices: MemoryOffsetChoices::default(),
             allow_start_export: true,
+            force_start_function: false,
+            call_chain_depth: None,
+            generate_cabi_realloc: false,
             max_type_size: 1000,
             canonicalize_nans: false,
             available_imports: None,
             exports: None,
             module_shape: None,
+            export_all_kinds: false,
             export_everything: false,
             generate_custom_sections: false,
+            generate_target_features_section: false,
             allow_invalid_funcs: false,
+            prefer_calling_imports: false,
+            reexport_imports_ratio: 0.0,
+            refs_in_signatures: true,
+            dead_code_ratio: 0.0,
+            deep_branch_ratio: 0.0,
+            definitions_allowed: true,
+            instantiable: false,
+            deduplicate_types: false,
+            kebab_case_export_names: false,
+            exercise_memory_lifecycle: false,
+            exercise_func_sharing: false,
+            min_zero_memory_ratio: 0.0,
+            overlapping_copy_ratio: 0.0,
+            parameterized_loop_ratio: 0.0,
+            partial_memory_init_ratio: 0.0,
+            meaningful_start: false,
+            import_field_dictionary: None,
+            cross_rec_group_refs: false,
+            data_content: DataContentKind::Random,
+            element_density: ElementDensity::Arbitrary,
+            extended_const_data_offset_ratio: 0.0,
+            emit_canonical_duplicate_rec_groups: false,
+            export_ordering: ExportOrdering::Generation,
+            legacy_exceptions: false,
+            string_alphabet: None,
+            subtype_global_get_ratio: 0.0,
+            trapping_indirect_call_ratio: 0.0,
+            concrete_func_table_element_ratio: 0.0,
+            concrete_heap_type_ratio: None,
+            concrete_table_element_ratio: 0.0,
+            recursive_type_bias: 0.0,
+            force_explicit_rec_groups: false,
+            fully_initialize_tables: false,
+            emit_data_count_section: None,
+            use_all_locals: false,
+            uniform_memory_index_type: None,
+            max_total_instructions: None,
+            max_locals: 100,
+            pathological: None,
+            clamp_offsets_to_32bit: false,
 
             // Proposals that are not stage4+ are disabled by default.
             custom_page_sizes_enabled: false,
@@ -880,9 +1806,7 @@ impl<'a> Arbitrary<'a> for Config {
         config.sanitize();
         Ok(config)
     }
-}
 
-impl Config {
     /// "Shrink" this `Config` where appropriate to ensure its configuration is
     /// valid for wasm-smith.
     ///
@@ -894,7 +1818,8 @@ impl Config {
     ///
     /// This method will not enable anything that isn't already enabled or
     /// increase any limit of an item, but it may turn features off or shrink
-    /// limits from what they're previously specified as.
+    /// limits from what they're previously specified as. The one exception is
+    /// `instantiable`, which turns on `disallow_traps`.
     pub(crate) fn sanitize(&mut self) {
         // If reference types are disabled then automatically flag tables as
         // capped at 1 and disable gc as well.
@@ -922,6 +1847,45 @@ impl Config {
             self.shared_everything_threads_enabled = false;
         }
 
+        // Instantiable modules are a stronger form of disallowing traps, and
+        // the start function could trap through stack overflow.
+        if self.instantiable {
+            self.disallow_traps = true;
+            self.allow_start_export = false;
+        }
+
+        // Modules without definitions can't exercise data segments, and
+        // shouldn't be required to define anything.
+        if !self.definitions_allowed {
+            self.exercise_memory_lifecycle = false;
+            self.exercise_func_sharing = false;
+            self.min_funcs = 0;
+            self.min_tables = 0;
+            self.min_memories = 0;
+            self.min_globals = 0;
+            self.min_tags = 0;
+            self.min_element_segments = 0;
+            self.min_data_segments = 0;
+        }
+
+        // Exercising the data segment lifecycle needs a memory to initialize
+        // and a function to do it in.
+        if self.exercise_memory_lifecycle && self.bulk_memory_enabled {
+            self.min_memories = self.min_memories.max(1);
+            self.max_memories = self.max_memories.max(1);
+            self.min_funcs = self.min_funcs.max(1);
+            self.max_funcs = self.max_funcs.max(1);
+        }
+
+        // Sharing a function needs another function to call it from.
+        if self.exercise_func_sharing {
+            self.min_funcs = self.min_funcs.max(2);
+            self.max_funcs = self.max_funcs.max(2);
+        }
+
+        // Make sure that the required number of memories can be generated.
+        self.max_memories = self.max_memories.max(self.min_memories as usize);
+
         // If module_shape is present then disable available_imports and exports.
         if self.module_shape.is_some() {
             self.available_imports = None;
@@ -929,6 +1893,33 @@ impl Config {
         }
     }
 
+    /// Returns the value types that modules generated with this `Config` draw
+    /// from for locals, globals, function signatures, and so on.
+    ///
+    /// Concrete reference types are not included since they depend on the
+    /// types defined in each generated module.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasm_smith::Config;
+    /// use wasm_encoder::{RefType, ValType};
+    ///
+    /// let config = Config {
+    ///     gc_enabled: true,
+    ///     reference_types_enabled: true,
+    ///     ..Config::default()
+    /// };
+    /// let types = config.value_types();
+    /// assert!(types.contains(&ValType::Ref(RefType::ANYREF)));
+    /// assert!(types.contains(&ValType::Ref(RefType::I31REF)));
+    /// ```
+    pub fn value_types(&self) -> Vec<wasm_encoder::ValType> {
+        let mut config = self.clone();
+        config.sanitize();
+        crate::core::configured_valtypes(&config)
+    }
+
     /// Returns the set of features that are necessary for validating against
     /// this `Config`.
     #[cfg(feature = "wasmparser")]
@@ -957,6 +1948,10 @@ impl Config {
         features.set(WasmFeatures::RELAXED_SIMD, self.relaxed_simd_enabled);
         features.set(WasmFeatures::MULTI_MEMORY, self.max_memories > 1);
         features.set(WasmFeatures::EXCEPTIONS, self.exceptions_enabled);
+        features.set(
+            WasmFeatures::LEGACY_EXCEPTIONS,
+            self.exceptions_enabled && self.legacy_exceptions,
+        );
         features.set(WasmFeatures::MEMORY64, self.memory64_enabled);
         features.set(WasmFeatures::TAIL_CALL, self.tail_call_enabled);
         features.set(WasmFeatures::FUNCTION_REFERENCES, self.gc_enabled);
@@ -975,6 +1970,143 @@ impl Config {
 
         features
     }
+
+    /// Creates a default `Config` whose feature flags are set to match
+    /// `features`.
+    ///
+    /// This is the inverse of [`Config::features`]: every proposal that
+    /// wasm-smith can generate is enabled if and only if it's enabled in
+    /// `features`, so that generated modules validate under `features`.
+    /// All other fields have their default values.
+    ///
+    /// Some feature sets can't be targeted exactly:
+    ///
+    /// * Disabling `MUTABLE_GLOBAL` isn't supported; mutable globals may still
+    ///   be imported or exported.
+    /// * `LEGACY_EXCEPTIONS` is only ever used on top of `EXCEPTIONS`, so
+    ///   [`Config::legacy_exceptions`] is left off and exceptions are disabled
+    ///   entirely without `EXCEPTIONS`.
+    /// * Reference types, and GC in turn, additionally require `GC_TYPES`
+    ///   since wasm-smith generates `externref`. GC also requires
+    ///   `FUNCTION_REFERENCES`, which can't be enabled on its own.
+    /// * Proposals which wasm-smith doesn't generate at all, such as the
+    ///   component model or stack switching, are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasm_smith::Config;
+    /// use wasmparser::WasmFeatures;
+    ///
+    /// let config = Config::for_feature_set(WasmFeatures::WASM1 | WasmFeatures::SIMD);
+    /// assert!(config.simd_enabled);
+    /// assert!(!config.reference_types_enabled);
+    /// ```
+    #[cfg(feature = "wasmparser")]
+    pub fn for_feature_set(features: wasmparser::WasmFeatures) -> Config {
+        use wasmparser::WasmFeatures;
+
+        let reference_types =
+            features.contains(WasmFeatures::REFERENCE_TYPES | WasmFeatures::GC_TYPES);
+        let default = Config::default();
+        Config {
+            allow_floats: features.contains(WasmFeatures::FLOATS),
+            saturating_float_to_int_enabled: features
+                .contains(WasmFeatures::SATURATING_FLOAT_TO_INT),
+            sign_extension_ops_enabled: features.contains(WasmFeatures::SIGN_EXTENSION),
+            reference_types_enabled: reference_types,
+            multi_value_enabled: features.contains(WasmFeatures::MULTI_VALUE),
+            bulk_memory_enabled: features.contains(WasmFeatures::BULK_MEMORY),
+            simd_enabled: features.contains(WasmFeatures::SIMD),
+            relaxed_simd_enabled: features.contains(WasmFeatures::RELAXED_SIMD),
+            max_memories: if features.contains(WasmFeatures::MULTI_MEMORY) {
+                default.max_memories
+            } else {
+                default.max_memories.min(1)
+            },
+            exceptions_enabled: features.contains(WasmFeatures::EXCEPTIONS),
+            legacy_exceptions: false,
+            memory64_enabled: features.contains(WasmFeatures::MEMORY64),
+            tail_call_enabled: features.contains(WasmFeatures::TAIL_CALL),
+            gc_enabled: reference_types
+                && features.contains(WasmFeatures::FUNCTION_REFERENCES | WasmFeatures::GC),
+            threads_enabled: features.contains(WasmFeatures::THREADS),
+            shared_everything_threads_enabled: features
+                .contains(WasmFeatures::SHARED_EVERYTHING_THREADS),
+            custom_page_sizes_enabled: features.contains(WasmFeatures::CUSTOM_PAGE_SIZES),
+            extended_const_enabled: features.contains(WasmFeatures::EXTENDED_CONST),
+            wide_arithmetic_enabled: features.contains(WasmFeatures::WIDE_ARITHMETIC),
+            ..default
+        }
+    }
+
+    /// Creates a default `Config` for modules with exactly one function whose
+    /// body is as large as the input allows.
+    ///
+    /// This is useful for stress-testing function-level compilation. Instead of
+    /// spreading the input across many small functions, `min_funcs` and
+    /// `max_funcs` are both one and the per-function `max_instructions` and
+    /// `max_locals` caps are raised so that the generation budget goes into a
+    /// single body. Imports are disabled so that the one function is defined
+    /// rather than imported. All other fields have their default values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasm_smith::Config;
+    /// let config = Config::single_large_function();
+    /// assert_eq!(config.min_funcs, 1);
+    /// assert_eq!(config.max_funcs, 1);
+    /// ```
+    pub fn single_large_function() -> Config {
+        Config {
+            min_funcs: 1,
+            max_funcs: 1,
+            max_imports: 0,
+            max_instructions: 100_000,
+            max_locals: 10_000,
+            ..Config::default()
+        }
+    }
+
+    /// Creates a default `Config` for modules which have at least one entry
+    /// in every section.
+    ///
+    /// Generated modules have a type, import, function, table, memory,
+    /// global, export, start, element, data, and code section. The `min_*`
+    /// fields for each of these are raised to require an entry, and
+    /// `force_start_function` is enabled. There is a single import, so that
+    /// the tables, memories, globals, and functions the module defines aren't
+    /// all imported instead. Because imported memories count towards
+    /// `min_memories`, two memories are required, which uses multi-memory.
+    /// All other fields have their default values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasm_smith::Config;
+    /// let config = Config::all_sections_present();
+    /// assert!(config.force_start_function);
+    /// assert_eq!(config.min_imports, 1);
+    /// ```
+    pub fn all_sections_present() -> Config {
+        Config {
+            min_types: 1,
+            min_imports: 1,
+            max_imports: 1,
+            min_funcs: 1,
+            min_tables: 1,
+            max_tables: 2,
+            min_memories: 2,
+            max_memories: 2,
+            min_globals: 1,
+            min_exports: 1,
+            min_element_segments: 1,
+            min_data_segments: 1,
+            force_start_function: true,
+            ..Config::default()
+        }
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -986,7 +2118,12 @@ impl<'de> serde::Deserialize<'de> for Config {
         use serde::de::Error;
 
         match Config::try_from(InternalOptionalConfig::deserialize(deserializer)?) {
-            Ok(config) => Ok(config),
+            Ok(mut config) => {
+                // Loaded configurations are sanitized so that they're always
+                // valid, just like those from `Config::arbitrary`.
+                config.sanitize();
+                Ok(config)
+            }
             Err(e) => Err(D::Error::custom(e)),
         }
     }
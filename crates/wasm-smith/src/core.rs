@@ -1,12 +1,17 @@
 //! Generating arbitrary core Wasm modules.
 
 mod code_builder;
+mod coverage;
 pub(crate) mod encode;
 mod terminate;
 
-use crate::{Config, arbitrary_loop, limited_string, unique_string};
+use crate::{
+    Config, DataContentKind, ElementDensity, PathologyKind, StringAlphabet, arbitrary_loop,
+    arbitrary_ratio, limited_string, unique_kebab_string, unique_string,
+};
 use arbitrary::{Arbitrary, Result, Unstructured};
-use code_builder::CodeBuilderAllocations;
+use code_builder::{CodeBuilderAllocations, const_results};
+pub use coverage::FeatureCoverage;
 use flagset::{FlagSet, flags};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
@@ -196,12 +201,170 @@ impl Module {
         &self.config
     }
 
+    /// Returns the number of imported functions.
+    ///
+    /// Imported functions come first in the function index space, followed
+    /// by the module's defined functions.
+    pub fn num_imported_functions(&self) -> usize {
+        self.funcs.len() - self.num_defined_funcs
+    }
+
+    /// Returns the number of functions defined in this module.
+    pub fn num_defined_functions(&self) -> usize {
+        self.num_defined_funcs
+    }
+
+    /// Returns the number of imported globals.
+    pub fn num_imported_globals(&self) -> usize {
+        self.globals.len() - self.defined_globals.len()
+    }
+
+    /// Returns the number of globals defined in this module.
+    pub fn num_defined_globals(&self) -> usize {
+        self.defined_globals.len()
+    }
+
+    /// Returns the number of imported tables.
+    pub fn num_imported_tables(&self) -> usize {
+        self.tables.len() - self.defined_tables.len()
+    }
+
+    /// Returns the number of tables defined in this module.
+    pub fn num_defined_tables(&self) -> usize {
+        self.defined_tables.len()
+    }
+
+    /// Returns the number of imported memories.
+    pub fn num_imported_memories(&self) -> usize {
+        self.memories.len() - self.num_defined_memories
+    }
+
+    /// Returns the number of memories defined in this module.
+    pub fn num_defined_memories(&self) -> usize {
+        self.num_defined_memories
+    }
+
+    /// Returns the number of imported tags.
+    pub fn num_imported_tags(&self) -> usize {
+        self.tags.len() - self.num_defined_tags
+    }
+
+    /// Returns the number of tags defined in this module.
+    pub fn num_defined_tags(&self) -> usize {
+        self.num_defined_tags
+    }
+
+    /// Returns the number of struct types defined in this module.
+    pub fn num_struct_types(&self) -> usize {
+        self.struct_types.len()
+    }
+
+    /// Returns the indices of the function types that are actually used by
+    /// this module.
+    ///
+    /// A function type is used if it is the type of an imported or defined
+    /// function or of a tag, or if a generated function body refers to it from
+    /// a block type, `call_indirect`, or `call_ref` (including their tail-call
+    /// variants). Function types which are only referenced from other types are
+    /// not included.
+    pub fn used_func_types(&self) -> HashSet<u32> {
+        let mut used = HashSet::new();
+        used.extend(self.funcs.iter().map(|(ty, _)| *ty));
+        used.extend(self.tags.iter().map(|tag| tag.func_type_idx));
+        for code in &self.code {
+            let Instructions::Generated(instrs) = &code.instructions else {
+                continue;
+            };
+            for instr in instrs {
+                let ty = match instr {
+                    Instruction::Block(BlockType::FunctionType(ty))
+                    | Instruction::Loop(BlockType::FunctionType(ty))
+                    | Instruction::If(BlockType::FunctionType(ty))
+                    | Instruction::Try(BlockType::FunctionType(ty))
+                    | Instruction::TryTable(BlockType::FunctionType(ty), _)
+                    | Instruction::CallIndirect { type_index: ty, .. }
+                    | Instruction::ReturnCallIndirect { type_index: ty, .. }
+                    | Instruction::CallRef(ty)
+                    | Instruction::ReturnCallRef(ty) => *ty,
+                    _ => continue,
+                };
+                used.insert(ty);
+            }
+        }
+        used
+    }
+
+    /// Asserts that this module doesn't require any WebAssembly features
+    /// beyond those enabled by its configuration, as reported by
+    /// [`Config::features`].
+    ///
+    /// This is intended for tests, to catch generator bugs such as emitting a
+    /// GC instruction when GC is disabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the module fails to validate with only the configured
+    /// features enabled.
+    #[cfg(feature = "wasmparser")]
+    pub fn assert_features_subset(&self) {
+        let features = self.config.features();
+        let wasm = self.to_bytes();
+        if let Err(e) = wasmparser::Validator::new_with_features(features).validate_all(&wasm) {
+            panic!("module requires features beyond {features:?}: {e}");
+        }
+    }
+
     /// Creates a new `Module` with the specified `config` for
     /// configuration and `Unstructured` for the DNA of this module.
     pub fn new(config: Config, u: &mut Unstructured<'_>) -> Result<Self> {
         Self::new_internal(config, u, DuplicateImportsBehavior::Allowed)
     }
 
+    /// Creates a new `Module` like [`Module::new`], also returning the number
+    /// of bytes of `u` that were consumed to generate it.
+    ///
+    /// This is useful for understanding which part of a larger input affected
+    /// the module's generation. Note that some decisions, such as the lengths
+    /// of names, depend on how much input remains, so generating a module from
+    /// just the consumed bytes won't necessarily reproduce it.
+    pub fn new_tracked(config: Config, u: &mut Unstructured<'_>) -> Result<(Self, usize)> {
+        let original_len = u.len();
+        let module = Self::new(config, u)?;
+        Ok((module, original_len - u.len()))
+    }
+
+    /// Creates a new `Module` like [`Module::new`], regenerating it from
+    /// fresh bytes of `u` until `predicate` holds for the generated module.
+    ///
+    /// This is useful for targeting rare module structures without having to
+    /// discard unwanted modules after the fact.
+    ///
+    /// # Errors
+    ///
+    /// Attempts where generating a module fails count towards `max_attempts`
+    /// like attempts whose module doesn't satisfy `predicate`. Once all
+    /// `max_attempts` are used up, [`arbitrary::Error::IncorrectFormat`] is
+    /// returned, which is likely if the predicate is rarely satisfied by
+    /// modules generated with `config`. If `u` runs out of data,
+    /// [`arbitrary::Error::NotEnoughData`] is returned instead.
+    pub fn new_matching(
+        config: Config,
+        u: &mut Unstructured<'_>,
+        mut predicate: impl FnMut(&Module) -> bool,
+        max_attempts: usize,
+    ) -> Result<Self> {
+        for _ in 0..max_attempts {
+            match Self::new(config.clone(), u) {
+                Ok(module) if predicate(&module) => return Ok(module),
+                Err(arbitrary::Error::NotEnoughData) => {
+                    return Err(arbitrary::Error::NotEnoughData);
+                }
+                Ok(_) | Err(_) => {}
+            }
+        }
+        Err(arbitrary::Error::IncorrectFormat)
+    }
+
     pub(crate) fn new_internal(
         config: Config,
         u: &mut Unstructured<'_>,
@@ -277,6 +440,49 @@ impl SubType {
     fn unwrap_array(&self) -> &ArrayType {
         self.composite_type.unwrap_array()
     }
+
+    /// Returns a copy of this type with every type index it refers to,
+    /// including its supertype, replaced by `f(index)`.
+    fn map_type_indices(&self, f: impl Fn(u32) -> u32) -> SubType {
+        let map_val_type = |ty: ValType| match ty {
+            ValType::Ref(RefType {
+                nullable,
+                heap_type: HeapType::Concrete(i),
+            }) => ValType::Ref(RefType {
+                nullable,
+                heap_type: HeapType::Concrete(f(i)),
+            }),
+            ty => ty,
+        };
+        let map_field_type = |ty: FieldType| FieldType {
+            element_type: match ty.element_type {
+                StorageType::Val(ty) => StorageType::Val(map_val_type(ty)),
+                ty => ty,
+            },
+            mutable: ty.mutable,
+        };
+        let inner = match &self.composite_type.inner {
+            CompositeInnerType::Array(a) => {
+                CompositeInnerType::Array(ArrayType(map_field_type(a.0)))
+            }
+            CompositeInnerType::Func(func) => CompositeInnerType::Func(Rc::new(FuncType {
+                params: func.params.iter().copied().map(map_val_type).collect(),
+                results: func.results.iter().copied().map(map_val_type).collect(),
+            })),
+            CompositeInnerType::Struct(s) => CompositeInnerType::Struct(StructType {
+                fields: s.fields.iter().copied().map(map_field_type).collect(),
+            }),
+        };
+        SubType {
+            is_final: self.is_final,
+            supertype: self.supertype.map(&f),
+            composite_type: CompositeType {
+                inner,
+                shared: self.composite_type.shared,
+            },
+            depth: self.depth,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -435,6 +641,7 @@ pub(crate) enum Offset {
     Const32(i32),
     Const64(i64),
     Global(u32),
+    Expr(ConstExpr),
 }
 
 impl Module {
@@ -462,11 +669,13 @@ impl Module {
 
         self.should_encode_imports = !self.imports.is_empty() || u.arbitrary()?;
 
-        self.arbitrary_tags(u)?;
-        self.arbitrary_funcs(u)?;
-        self.arbitrary_tables(u)?;
-        self.arbitrary_memories(u)?;
-        self.arbitrary_globals(u)?;
+        if self.config.definitions_allowed {
+            self.arbitrary_tags(u)?;
+            self.arbitrary_funcs(u)?;
+            self.arbitrary_tables(u)?;
+            self.arbitrary_memories(u)?;
+            self.arbitrary_globals(u)?;
+        }
         if self.required_exports(u)? {
             generate_arbitrary_exports = false;
         }
@@ -475,9 +684,26 @@ impl Module {
         }
         self.should_encode_types = !self.types.is_empty() || u.arbitrary()?;
         self.arbitrary_start(u)?;
-        self.arbitrary_elems(u)?;
-        self.arbitrary_data(u)?;
+        if self.config.definitions_allowed {
+            self.arbitrary_elems(u)?;
+            self.arbitrary_data(u)?;
+        }
         self.arbitrary_code(u)?;
+        if let Some(depth) = self.config.call_chain_depth {
+            self.add_call_chain(u, depth)?;
+        }
+        if self.config.emit_canonical_duplicate_rec_groups && self.config.refs_in_signatures {
+            self.add_canonical_duplicate_rec_group(u)?;
+        }
+        if self.config.generate_cabi_realloc {
+            self.add_cabi_realloc()?;
+        }
+        if self.config.exercise_func_sharing {
+            self.add_func_sharing(u)?;
+        }
+        if self.config.meaningful_start {
+            self.add_start_side_effect(u)?;
+        }
         Ok(())
     }
 
@@ -503,6 +729,61 @@ impl Module {
         self.heap_type_is_sub_type(a.heap_type, b.heap_type)
     }
 
+    /// The least upper bound of `a` and `b`: their most specific common
+    /// supertype, if they have one.
+    fn ref_type_lub(&self, a: RefType, b: RefType) -> Option<RefType> {
+        Some(RefType {
+            nullable: a.nullable || b.nullable,
+            heap_type: self.heap_type_lub(a.heap_type, b.heap_type)?,
+        })
+    }
+
+    fn heap_type_lub(&self, a: HeapType, b: HeapType) -> Option<HeapType> {
+        use AbstractHeapType::*;
+        use CompositeInnerType as CT;
+
+        // This also covers the bottom types, which are the only heap types
+        // with more than one direct supertype.
+        if self.heap_type_is_sub_type(a, b) {
+            return Some(b);
+        }
+
+        // Otherwise `a` and its supertypes form a chain, and the first of
+        // them which is also a supertype of `b` is the least upper bound.
+        let mut supertypes = vec![a];
+        let (shared, abstract_supertypes): (_, &[_]) = match a {
+            HeapType::Concrete(mut i) => {
+                while let Some(supertype) = self.ty(i).supertype {
+                    supertypes.push(HeapType::Concrete(supertype));
+                    i = supertype;
+                }
+                let ty = &self.ty(i).composite_type;
+                let abstract_supertypes: &[_] = match ty.inner {
+                    CT::Struct(_) => &[Struct, Eq, Any],
+                    CT::Array(_) => &[Array, Eq, Any],
+                    CT::Func(_) => &[Func],
+                };
+                (ty.shared, abstract_supertypes)
+            }
+            HeapType::Abstract { shared, ty } => (
+                shared,
+                match ty {
+                    I31 | Struct | Array => &[Eq, Any],
+                    Eq => &[Any],
+                    _ => &[],
+                },
+            ),
+        };
+        supertypes.extend(
+            abstract_supertypes
+                .iter()
+                .map(|&ty| HeapType::Abstract { shared, ty }),
+        );
+        supertypes
+            .into_iter()
+            .find(|&supertype| self.heap_type_is_sub_type(b, supertype))
+    }
+
     fn heap_type_is_sub_type(&self, a: HeapType, b: HeapType) -> bool {
         use AbstractHeapType::*;
         use CompositeInnerType as CT;
@@ -573,8 +854,17 @@ impl Module {
 
     fn arbitrary_types(&mut self, u: &mut Unstructured) -> Result<()> {
         assert!(self.config.min_types <= self.config.max_types);
+        if self.config.pathological == Some(PathologyKind::LongRecGroup) && self.config.gc_enabled {
+            return self.arbitrary_long_rec_group(u);
+        }
         while self.types.len() < self.config.min_types {
+            let len = self.types.len();
             self.arbitrary_rec_group(u, AllowEmptyRecGroup::No)?;
+            // Deduplication can discard the new rec group, and once the input
+            // is exhausted the same rec group would be generated forever.
+            if self.types.len() == len && u.is_empty() {
+                return Err(arbitrary::Error::NotEnoughData);
+            }
         }
         while self.types.len() < self.config.max_types {
             let keep_going = u.arbitrary().unwrap_or(false);
@@ -586,6 +876,23 @@ impl Module {
         Ok(())
     }
 
+    /// Generates a single rec group holding as many types as allowed, for
+    /// `PathologyKind::LongRecGroup`.
+    fn arbitrary_long_rec_group(&mut self, u: &mut Unstructured) -> Result<()> {
+        let rec_group_start = self.types.len();
+        let type_ref_limit = u32::try_from(self.config.max_types).unwrap();
+        self.max_type_limit = MaxTypeLimit::Num(type_ref_limit);
+        for _ in rec_group_start..self.config.max_types {
+            let ty = self.arbitrary_sub_type(u)?;
+            self.add_type(ty);
+        }
+        self.max_type_limit = MaxTypeLimit::ModuleTypes;
+        if self.types.len() > rec_group_start {
+            self.rec_groups.push(rec_group_start..self.types.len());
+        }
+        Ok(())
+    }
+
     fn add_type(&mut self, ty: SubType) -> u32 {
         let index = u32::try_from(self.types.len()).unwrap();
 
@@ -631,7 +938,7 @@ impl Module {
 
         if self.config.gc_enabled {
             // With small probability, clone an existing rec group.
-            if self.rec_groups.len() > 0 && u.ratio(1, u8::MAX)? {
+            if self.rec_groups.len() > 0 && !self.config.deduplicate_types && u.ratio(1, u8::MAX)? {
                 return self.clone_rec_group(u, kind);
             }
 
@@ -658,9 +965,65 @@ impl Module {
         self.max_type_limit = MaxTypeLimit::ModuleTypes;
 
         self.rec_groups.push(rec_group_start..self.types.len());
+        self.deduplicate_last_rec_group();
         Ok(())
     }
 
+    /// If `deduplicate_types` is enabled and the most recently added rec group
+    /// is structurally identical to an earlier one, remove it.
+    ///
+    /// Returns the start index of the earlier rec group if the last one was
+    /// removed.
+    fn deduplicate_last_rec_group(&mut self) -> Option<u32> {
+        if !self.config.deduplicate_types {
+            return None;
+        }
+        let (last, earlier) = self.rec_groups.split_last()?;
+        let canonical = self.canonical_rec_group(last.clone());
+        let existing = earlier
+            .iter()
+            .find(|group| self.canonical_rec_group((*group).clone()) == canonical)?
+            .start;
+
+        let len = self.rec_groups.pop().unwrap().start;
+        self.types.truncate(len);
+        for list in [
+            &mut self.array_types,
+            &mut self.func_types,
+            &mut self.struct_types,
+            &mut self.can_subtype,
+        ] {
+            list.retain(|i| (*i as usize) < len);
+        }
+        self.super_to_sub_types.retain(|_, subtypes| {
+            subtypes.retain(|i| (*i as usize) < len);
+            !subtypes.is_empty()
+        });
+        Some(u32::try_from(existing).unwrap())
+    }
+
+    /// Returns the types of the given rec group with references to types within
+    /// the rec group replaced by indices relative to the rec group, so that
+    /// structurally-identical rec groups compare equal.
+    ///
+    /// Rec group relative indices are counted down from `u32::MAX` to keep them
+    /// distinct from references to types outside of the rec group.
+    fn canonical_rec_group(&self, group: Range<usize>) -> Vec<SubType> {
+        let start = u32::try_from(group.start).unwrap();
+        let end = u32::try_from(group.end).unwrap();
+        let canonical_index = |i: u32| {
+            if (start..end).contains(&i) {
+                u32::MAX - (i - start)
+            } else {
+                i
+            }
+        };
+        self.types[group]
+            .iter()
+            .map(|ty| ty.map_type_indices(canonical_index))
+            .collect()
+    }
+
     fn clone_rec_group(&mut self, u: &mut Unstructured, kind: AllowEmptyRecGroup) -> Result<()> {
         // Choose an arbitrary rec group to clone, but bail out if the selected
         // rec group isn't valid to clone. For example if empty groups aren't
@@ -691,6 +1054,29 @@ impl Module {
         Ok(())
     }
 
+    /// Append a copy of the rec group `group` whose references to types within
+    /// `group` are rewritten to refer to the copy, so that the two rec groups
+    /// canonicalize to the same types. Returns the start of the copy.
+    fn add_canonical_rec_group_clone(&mut self, group: Range<usize>) -> usize {
+        let start = u32::try_from(group.start).unwrap();
+        let end = u32::try_from(group.end).unwrap();
+        let new_start = u32::try_from(self.types.len()).unwrap();
+        let rewrite_index = |i: u32| {
+            if (start..end).contains(&i) {
+                new_start + (i - start)
+            } else {
+                i
+            }
+        };
+        for index in group {
+            let ty = self.types[index].map_type_indices(rewrite_index);
+            self.add_type(ty);
+        }
+        let new_start = new_start as usize;
+        self.rec_groups.push(new_start..self.types.len());
+        new_start
+    }
+
     fn arbitrary_sub_type(&mut self, u: &mut Unstructured) -> Result<SubType> {
         if !self.config.gc_enabled {
             let shared = self.arbitrary_shared(u)?;
@@ -707,8 +1093,25 @@ impl Module {
             });
         }
 
+        if self.config.cross_rec_group_refs {
+            // Rec groups are contiguous, so the group being generated starts
+            // where the last complete one ended.
+            let rec_group_start = self.rec_groups.last().map_or(0, |g| g.end);
+            let earlier = self
+                .can_subtype
+                .iter()
+                .copied()
+                .filter(|i| (*i as usize) < rec_group_start)
+                .collect::<Vec<_>>();
+            if !earlier.is_empty() && u.arbitrary()? {
+                let supertype = *u.choose(&earlier)?;
+                return self.arbitrary_sub_type_of_super_type(u, supertype);
+            }
+        }
+
         if !self.can_subtype.is_empty() && u.ratio(1, 32_u8)? {
-            self.arbitrary_sub_type_of_super_type(u)
+            let supertype = *u.choose(&self.can_subtype)?;
+            self.arbitrary_sub_type_of_super_type(u, supertype)
         } else {
             Ok(SubType {
                 is_final: u.arbitrary()?,
@@ -719,8 +1122,11 @@ impl Module {
         }
     }
 
-    fn arbitrary_sub_type_of_super_type(&mut self, u: &mut Unstructured) -> Result<SubType> {
-        let supertype = *u.choose(&self.can_subtype)?;
+    fn arbitrary_sub_type_of_super_type(
+        &mut self,
+        u: &mut Unstructured,
+        supertype: u32,
+    ) -> Result<SubType> {
         let mut composite_type = self.types[usize::try_from(supertype).unwrap()]
             .composite_type
             .clone();
@@ -1089,6 +1495,9 @@ impl Module {
     }
 
     fn arbitrary_storage_type(&mut self, u: &mut Unstructured) -> Result<StorageType> {
+        if let Some(ty) = self.arbitrary_recursive_ref_type(u)? {
+            return Ok(StorageType::Val(ValType::Ref(ty)));
+        }
         match u.int_in_range(0..=2)? {
             0 => Ok(StorageType::I8),
             1 => Ok(StorageType::I16),
@@ -1097,6 +1506,37 @@ impl Module {
         }
     }
 
+    /// With probability `recursive_type_bias`, generates a nullable reference
+    /// to a type in the rec group currently being generated, including the
+    /// type being generated itself and types later in the rec group.
+    fn arbitrary_recursive_ref_type(&self, u: &mut Unstructured) -> Result<Option<RefType>> {
+        let MaxTypeLimit::Num(type_ref_limit) = self.max_type_limit else {
+            return Ok(None);
+        };
+        if !self.config.gc_enabled || !arbitrary_ratio(u, self.config.recursive_type_bias)? {
+            return Ok(None);
+        }
+
+        // Rec groups are contiguous, so the group being generated starts
+        // where the last complete one ended.
+        let rec_group_start = self.rec_groups.last().map_or(0, |g| g.end) as u32;
+        let candidates = (rec_group_start..type_ref_limit)
+            .filter(|i| match self.types.get(*i as usize) {
+                Some(ty) => !self.must_share || ty.composite_type.shared,
+                // The sharedness of types that haven't been generated yet isn't
+                // known, so they can only be referenced from unshared types.
+                None => !self.must_share,
+            })
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(RefType {
+            nullable: true,
+            heap_type: HeapType::Concrete(*u.choose(&candidates)?),
+        }))
+    }
+
     fn arbitrary_ref_type(&self, u: &mut Unstructured) -> Result<RefType> {
         if !self.config.reference_types_enabled {
             return Ok(RefType::FUNCREF);
@@ -1107,6 +1547,66 @@ impl Module {
         })
     }
 
+    /// Generates the element type of a table, which is sometimes a reference
+    /// to a concrete type when GC is enabled so that such tables aren't only
+    /// generated by chance.
+    ///
+    /// References to struct and array types are generated as configured by
+    /// `concrete_table_element_ratio`, and nullable references to function
+    /// types as configured by `concrete_func_table_element_ratio`.
+    fn arbitrary_table_element_type(&self, u: &mut Unstructured) -> Result<RefType> {
+        if self.config.gc_enabled {
+            let concrete_type_limit = match self.max_type_limit {
+                MaxTypeLimit::Num(n) => n,
+                MaxTypeLimit::ModuleTypes => u32::try_from(self.types.len()).unwrap(),
+            };
+            if self.config.concrete_table_element_ratio > 0.0 {
+                let aggregate_types = self
+                    .struct_types
+                    .iter()
+                    .chain(&self.array_types)
+                    .copied()
+                    .filter(|i| *i < concrete_type_limit)
+                    .collect::<Vec<_>>();
+                if !aggregate_types.is_empty()
+                    && arbitrary_ratio(u, self.config.concrete_table_element_ratio)?
+                {
+                    let ty = *u.choose(&aggregate_types)?;
+                    // A non-nullable table needs an initialization expression,
+                    // which can always be made for arrays and for structs
+                    // whose fields are all defaultable.
+                    let has_const_expr = match &self.ty(ty).composite_type.inner {
+                        CompositeInnerType::Struct(s) => {
+                            s.fields.iter().all(|f| f.element_type.is_defaultable())
+                        }
+                        _ => true,
+                    };
+                    return Ok(RefType {
+                        nullable: !has_const_expr || u.arbitrary()?,
+                        heap_type: HeapType::Concrete(ty),
+                    });
+                }
+            }
+            if self.config.concrete_func_table_element_ratio > 0.0 {
+                let func_types = self
+                    .func_types
+                    .iter()
+                    .copied()
+                    .filter(|i| *i < concrete_type_limit)
+                    .collect::<Vec<_>>();
+                if !func_types.is_empty()
+                    && arbitrary_ratio(u, self.config.concrete_func_table_element_ratio)?
+                {
+                    return Ok(RefType {
+                        nullable: true,
+                        heap_type: HeapType::Concrete(*u.choose(&func_types)?),
+                    });
+                }
+            }
+        }
+        self.arbitrary_ref_type(u)
+    }
+
     fn arbitrary_heap_type(&self, u: &mut Unstructured) -> Result<HeapType> {
         assert!(self.config.reference_types_enabled);
 
@@ -1115,7 +1615,10 @@ impl Module {
             MaxTypeLimit::ModuleTypes => u32::try_from(self.types.len()).unwrap(),
         };
 
-        if self.config.gc_enabled && concrete_type_limit > 0 && u.arbitrary()? {
+        if self.config.gc_enabled
+            && concrete_type_limit > 0
+            && self.arbitrary_concrete_heap_type(u)?
+        {
             let idx = u.int_in_range(0..=concrete_type_limit - 1)?;
             // If the caller is demanding a shared heap type but the concrete
             // type we found is not in fact shared, we skip down below to use an
@@ -1133,7 +1636,7 @@ impl Module {
 
         use AbstractHeapType::*;
         let mut choices = vec![Func, Extern];
-        if self.config.exceptions_enabled {
+        if self.config.exceptions_enabled && !self.config.legacy_exceptions {
             choices.push(Exn);
         }
         if self.config.gc_enabled {
@@ -1150,12 +1653,23 @@ impl Module {
         })
     }
 
+    /// Decide whether an arbitrary heap type should be concrete, as
+    /// configured by `concrete_heap_type_ratio`.
+    fn arbitrary_concrete_heap_type(&self, u: &mut Unstructured) -> Result<bool> {
+        match self.config.concrete_heap_type_ratio {
+            None => u.arbitrary(),
+            Some((0, _)) => Ok(false),
+            Some((numerator, denominator)) => u.ratio(numerator, denominator),
+        }
+    }
+
     fn arbitrary_func_type(&mut self, u: &mut Unstructured) -> Result<Rc<FuncType>> {
         let mut params = vec![];
         let mut results = vec![];
         let max_params = 20;
+        let allow_refs = self.config.refs_in_signatures;
         arbitrary_loop(u, 0, max_params, |u| {
-            params.push(self.arbitrary_valtype(u)?);
+            params.push(self.arbitrary_valtype_of(u, allow_refs)?);
             Ok(true)
         })?;
         let max_results = if self.config.multi_value_enabled {
@@ -1164,7 +1678,7 @@ impl Module {
             1
         };
         arbitrary_loop(u, 0, max_results, |u| {
-            results.push(self.arbitrary_valtype(u)?);
+            results.push(self.arbitrary_valtype_of(u, allow_refs)?);
             Ok(true)
         })?;
         Ok(Rc::new(FuncType { params, results }))
@@ -1220,92 +1734,22 @@ impl Module {
         // First, we validate the module-by-example and extract the required types, imports
         // and exports. Besides, we also extract the functions, tags, tables, memories and
         // globals that are necessary for generating the exports.
-        let mut available_funcs: Vec<u32> = Vec::new();
-        let mut available_tags: Vec<wasmparser::TagType> = Vec::new();
-        let mut available_tables: Vec<wasmparser::TableType> = Vec::new();
-        let mut available_globals: Vec<wasmparser::GlobalType> = Vec::new();
-        let mut available_memories: Vec<wasmparser::MemoryType> = Vec::new();
-
-        let mut required_types: Vec<SubType> = Vec::new();
-        let mut required_recgrps: Vec<usize> = Vec::new();
-        let mut required_imports: Vec<wasmparser::Import> = Vec::new();
-        let mut required_exports: Vec<wasmparser::Export> = Vec::new();
-        let mut validator = wasmparser::Validator::new();
-        validator
-            .validate_all(example_module)
-            .expect("Failed to validate `module_shape` module");
-        for payload in wasmparser::Parser::new(0).parse_all(&example_module) {
-            match payload.expect("could not parse the `module_shape` module") {
-                wasmparser::Payload::TypeSection(type_reader) => {
-                    for recgrp in type_reader {
-                        let recgrp = recgrp.expect("could not read recursive group");
-                        required_recgrps.push(recgrp.types().len());
-                        for subtype in recgrp.into_types() {
-                            let mut subtype: SubType = subtype.try_into().unwrap();
-                            if let Some(supertype_idx) = subtype.supertype {
-                                subtype.depth = required_types[supertype_idx as usize].depth + 1;
-                            }
-                            required_types.push(subtype);
-                        }
-                    }
-                }
-                wasmparser::Payload::ImportSection(import_reader) => {
-                    for im in import_reader {
-                        let im = im.expect("could not read import");
-                        required_imports.push(im);
-                    }
-                }
-                wasmparser::Payload::ExportSection(export_reader) => {
-                    for ex in export_reader {
-                        let ex = ex.expect("could not read export");
-                        required_exports.push(ex);
-                    }
-                }
-                wasmparser::Payload::FunctionSection(function_reader) => {
-                    for func in function_reader {
-                        let func = func.expect("could not read function");
-                        available_funcs.push(func);
-                    }
-                }
-                wasmparser::Payload::TagSection(tag_reader) => {
-                    for tag in tag_reader {
-                        let tag = tag.expect("could not read tag");
-                        available_tags.push(tag);
-                    }
-                }
-                wasmparser::Payload::TableSection(table_reader) => {
-                    for table in table_reader {
-                        let table = table.expect("could not read table");
-                        available_tables.push(table.ty);
-                    }
-                }
-                wasmparser::Payload::MemorySection(memory_reader) => {
-                    for memory in memory_reader {
-                        let memory = memory.expect("could not read memory");
-                        available_memories.push(memory);
-                    }
-                }
-                wasmparser::Payload::GlobalSection(global_reader) => {
-                    for global in global_reader {
-                        let global = global.expect("could not read global");
-                        available_globals.push(global.ty);
-                    }
-                }
-                _ => {}
-            }
-        }
+        let ExampleModule {
+            rec_groups: required_recgrps,
+            types: required_types,
+            imports: required_imports,
+            exports: required_exports,
+            funcs: mut available_funcs,
+            tags: mut available_tags,
+            tables: mut available_tables,
+            memories: mut available_memories,
+            globals: mut available_globals,
+            ..
+        } = ExampleModule::parse(example_module, "module_shape");
 
         // Next, we copy all the types from the module-by-example into current module. This is necessary
         // to ensure that the current module has all the types it needs to type-check correctly.
-        let mut recgrp_start_idx = self.types.len();
-        for size in required_recgrps {
-            self.rec_groups
-                .push(recgrp_start_idx..recgrp_start_idx + size);
-            recgrp_start_idx += size;
-        }
-        for ty in &required_types {
-            self.add_type(ty.clone());
-        }
+        self.add_example_types(&required_recgrps, &required_types);
 
         // We then generate import entries which refer to the imported types. Additionally, we add the
         // imported items to their corresponding vectors here, ensuring that exports reference the
@@ -1475,6 +1919,20 @@ impl Module {
         Ok(())
     }
 
+    /// Copy the rec groups and types of an example module into this module.
+    #[cfg(feature = "wasmparser")]
+    fn add_example_types(&mut self, rec_groups: &[usize], types: &[SubType]) {
+        let mut recgrp_start_idx = self.types.len();
+        for size in rec_groups {
+            self.rec_groups
+                .push(recgrp_start_idx..recgrp_start_idx + size);
+            recgrp_start_idx += size;
+        }
+        for ty in types {
+            self.add_type(ty.clone());
+        }
+    }
+
     fn arbitrary_imports(&mut self, u: &mut Unstructured) -> Result<()> {
         if self.config.max_type_size < self.type_size {
             return Ok(());
@@ -1532,13 +1990,19 @@ impl Module {
             let f = u.choose(&choices)?;
             let entity_type = f(u, self)?;
             let budget = self.config.max_type_size - self.type_size;
-            if entity_type.size() + 1 > budget {
+            let size = entity_type.size(self);
+            if size + 1 > budget {
                 return Ok(false);
             }
-            self.type_size += entity_type.size() + 1;
+            self.type_size += size + 1;
 
             // Generate an arbitrary module/name pair to name this import.
-            let mut import_pair = unique_import_strings(1_000, u)?;
+            let mut import_pair = unique_import_strings(
+                1_000,
+                self.config.string_alphabet,
+                self.config.import_field_dictionary.as_deref(),
+                u,
+            )?;
             if self.duplicate_imports_behavior == DuplicateImportsBehavior::Disallowed {
                 while import_strings.contains(&import_pair) {
                     use std::fmt::Write;
@@ -1606,48 +2070,29 @@ impl Module {
         // this vector) as it appears in the parsed code, to the type itself. We copy all the types
         // from module-by-example into the module being constructed for the sake of simplicity
         // and for this reason, [`Self::config::max_types`] may be surpassed.
-        let mut new_recgrps = Vec::<usize>::new();
-        let mut available_types = Vec::<SubType>::new();
-        let mut available_imports = Vec::<wasmparser::Import>::new();
-        let mut validator = wasmparser::Validator::new();
-        validator
-            .validate_all(example_module)
-            .expect("Failed to validate `module_shape` module");
-        for payload in wasmparser::Parser::new(0).parse_all(&example_module) {
-            match payload.expect("could not parse the available import payload") {
-                wasmparser::Payload::TypeSection(type_reader) => {
-                    for recgrp in type_reader {
-                        let recgrp = recgrp.expect("could not read recursive group");
-                        new_recgrps.push(recgrp.types().len());
-                        for subtype in recgrp.into_types() {
-                            let mut subtype: SubType = subtype.try_into().unwrap();
-                            if let Some(supertype_idx) = subtype.supertype {
-                                subtype.depth = available_types[supertype_idx as usize].depth + 1;
-                            }
-                            available_types.push(subtype);
-                        }
-                    }
-                }
-                wasmparser::Payload::ImportSection(import_reader) => {
-                    for im in import_reader {
-                        let im = im.expect("could not read import");
-                        // We can immediately filter whether this is an import we want to
-                        // use.
-                        let use_import = u.arbitrary().unwrap_or(false);
-                        if !use_import {
-                            continue;
-                        }
-                        available_imports.push(im);
-                    }
-                }
-                _ => {}
+        let ExampleModule {
+            rec_groups: new_recgrps,
+            types: available_types,
+            imports,
+            ..
+        } = ExampleModule::parse(example_module, "available_imports");
+        let mut available_imports = Vec::with_capacity(imports.len());
+        for im in imports {
+            // We can immediately filter whether this is an import we want to
+            // use.
+            let use_import = u.arbitrary().unwrap_or(false);
+            if !use_import {
+                continue;
             }
+            available_imports.push(im);
         }
 
         // We then generate import entries which refer to the imported types. Since this function
         // is called at the very beginning of the module generation process and all types from the
         // module-by-example are copied into the current module, no further adjustments are needed
-        // for type indices.
+        // for type indices. The types are added up front so that the type size of each import can
+        // take into account the concrete types it refers to.
+        self.add_example_types(&new_recgrps, &available_types);
         let mut new_imports = Vec::with_capacity(available_imports.len());
         for import in available_imports {
             let type_size_budget = self.config.max_type_size - self.type_size;
@@ -1661,7 +2106,7 @@ impl Module {
                             Some(ty) => match &ty.composite_type.inner {
                                 CompositeInnerType::Func(func_type) => {
                                     let entity = EntityType::Func(*sig_idx, Rc::clone(func_type));
-                                    if type_size_budget < entity.size() {
+                                    if type_size_budget < entity.size(self) {
                                         continue;
                                     }
                                     self.funcs.push((*sig_idx, Rc::clone(func_type)));
@@ -1689,7 +2134,7 @@ impl Module {
                                         func_type: Rc::clone(func_type),
                                     };
                                     let entity = EntityType::Tag(tag_type.clone());
-                                    if type_size_budget < entity.size() {
+                                    if type_size_budget < entity.size(self) {
                                         continue;
                                     }
                                     self.tags.push(tag_type);
@@ -1704,7 +2149,7 @@ impl Module {
                 wasmparser::TypeRef::Table(table_ty) => {
                     let table_ty = TableType::try_from(*table_ty).unwrap();
                     let entity = EntityType::Table(table_ty);
-                    let type_size = entity.size();
+                    let type_size = entity.size(self);
                     if type_size_budget < type_size || !self.can_add_local_or_import_table() {
                         continue;
                     }
@@ -1716,7 +2161,7 @@ impl Module {
                 wasmparser::TypeRef::Memory(memory_ty) => {
                     let memory_ty = MemoryType::from(*memory_ty);
                     let entity = EntityType::Memory(memory_ty);
-                    let type_size = entity.size();
+                    let type_size = entity.size(self);
                     if type_size_budget < type_size || !self.can_add_local_or_import_memory() {
                         continue;
                     }
@@ -1728,7 +2173,7 @@ impl Module {
                 wasmparser::TypeRef::Global(global_ty) => {
                     let global_ty = GlobalType::try_from(*global_ty).unwrap();
                     let entity = EntityType::Global(global_ty);
-                    let type_size = entity.size();
+                    let type_size = entity.size(self);
                     if type_size_budget < type_size || !self.can_add_local_or_import_global() {
                         continue;
                     }
@@ -1746,15 +2191,6 @@ impl Module {
         }
 
         // Finally, add the entities we just generated.
-        let mut recgrp_start_idx = self.types.len();
-        for size in new_recgrps {
-            self.rec_groups
-                .push(recgrp_start_idx..recgrp_start_idx + size);
-            recgrp_start_idx += size;
-        }
-        for ty in available_types {
-            self.add_type(ty);
-        }
         self.imports.extend(new_imports);
 
         Ok(())
@@ -1766,8 +2202,8 @@ impl Module {
             ExportKind::Memory => EntityType::Memory(self.memories[index as usize]),
             ExportKind::Table => EntityType::Table(self.tables[index as usize]),
             ExportKind::Func => {
-                let (_idx, ty) = &self.funcs[index as usize];
-                EntityType::Func(u32::max_value(), ty.clone())
+                let (idx, ty) = &self.funcs[index as usize];
+                EntityType::Func(*idx, ty.clone())
             }
             ExportKind::Tag => EntityType::Tag(self.tags[index as usize].clone()),
         }
@@ -1777,15 +2213,48 @@ impl Module {
         &self.types[idx as usize]
     }
 
-    fn func_types(&self) -> impl Iterator<Item = (u32, &FuncType)> + '_ {
-        self.func_types
-            .iter()
-            .copied()
-            .map(move |type_i| (type_i, &**self.func_type(type_i)))
-    }
-
-    fn func_type(&self, idx: u32) -> &Rc<FuncType> {
-        match &self.ty(idx).composite_type.inner {
+    /// The size of the concrete type that `ty` refers to, if any, for the
+    /// purposes of `EntityType::size`.
+    ///
+    /// A concrete type counts as one plus one for each of its fields, params,
+    /// or results, plus the nested size of those in turn. Types already in
+    /// `seen` are not counted again.
+    fn nested_type_size(&self, ty: ValType, seen: &mut HashSet<u32>) -> u32 {
+        let ValType::Ref(RefType {
+            heap_type: HeapType::Concrete(idx),
+            ..
+        }) = ty
+        else {
+            return 0;
+        };
+        if !seen.insert(idx) {
+            return 0;
+        }
+        let tys: Vec<ValType> = match &self.ty(idx).composite_type.inner {
+            CompositeInnerType::Func(f) => {
+                f.params.iter().chain(f.results.iter()).copied().collect()
+            }
+            CompositeInnerType::Struct(s) => {
+                s.fields.iter().map(|f| f.element_type.unpack()).collect()
+            }
+            CompositeInnerType::Array(a) => vec![a.0.element_type.unpack()],
+        };
+        let mut size = 1 + tys.len() as u32;
+        for ty in tys {
+            size += self.nested_type_size(ty, seen);
+        }
+        size
+    }
+
+    fn func_types(&self) -> impl Iterator<Item = (u32, &FuncType)> + '_ {
+        self.func_types
+            .iter()
+            .copied()
+            .map(move |type_i| (type_i, &**self.func_type(type_i)))
+    }
+
+    fn func_type(&self, idx: u32) -> &Rc<FuncType> {
+        match &self.ty(idx).composite_type.inner {
             CompositeInnerType::Func(f) => f,
             _ => panic!("types[{idx}] is not a func type"),
         }
@@ -1817,6 +2286,12 @@ impl Module {
     }
 
     fn arbitrary_valtype(&self, u: &mut Unstructured) -> Result<ValType> {
+        self.arbitrary_valtype_of(u, true)
+    }
+
+    /// Generates an arbitrary value type, excluding reference types when
+    /// `allow_refs` is `false`.
+    fn arbitrary_valtype_of(&self, u: &mut Unstructured, allow_refs: bool) -> Result<ValType> {
         #[derive(PartialEq, Eq, PartialOrd, Ord)]
         enum ValTypeClass {
             I32,
@@ -1838,6 +2313,7 @@ impl Module {
                 ValType::V128 => ValTypeClass::V128,
                 ValType::Ref(_) => ValTypeClass::Ref,
             })
+            .filter(|class| allow_refs || *class != ValTypeClass::Ref)
             .collect();
         val_classes.sort_unstable();
         val_classes.dedup();
@@ -1891,6 +2367,27 @@ impl Module {
     }
 
     fn arbitrary_funcs(&mut self, u: &mut Unstructured) -> Result<()> {
+        // Functions can't be defined without a function type to give them, so
+        // if a minimum number of functions is required then make sure there's
+        // at least one unshared function type.
+        if self.funcs.len() < self.config.min_funcs
+            && self.types.len() < self.config.max_types
+            && self.func_types.iter().all(|&i| self.is_shared_type(i))
+        {
+            self.rec_groups.push(self.types.len()..self.types.len() + 1);
+            let func_type = self.arbitrary_func_type(u)?;
+            self.add_type(SubType {
+                is_final: true,
+                supertype: None,
+                composite_type: CompositeType {
+                    inner: CompositeInnerType::Func(func_type),
+                    shared: false,
+                },
+                depth: 1,
+            });
+            self.deduplicate_last_rec_group();
+        }
+
         if self.func_types.is_empty() {
             return Ok(());
         }
@@ -1963,19 +2460,16 @@ impl Module {
     }
 
     fn arbitrary_memories(&mut self, u: &mut Unstructured) -> Result<()> {
-        arbitrary_loop(
-            u,
-            self.config.min_memories as usize,
-            self.config.max_memories,
-            |u| {
-                if !self.can_add_local_or_import_memory() {
-                    return Ok(false);
-                }
-                let ty = arbitrary_memtype(u, self.config())?;
-                self.add_arbitrary_memory_of_type(ty)?;
-                Ok(true)
-            },
-        )
+        // Imported memories count towards the minimum.
+        let min = (self.config.min_memories as usize).saturating_sub(self.memories.len());
+        arbitrary_loop(u, min, self.config.max_memories, |u| {
+            if !self.can_add_local_or_import_memory() {
+                return Ok(false);
+            }
+            let ty = arbitrary_memtype(u, self.config())?;
+            self.add_arbitrary_memory_of_type(ty)?;
+            Ok(true)
+        })
     }
 
     /// Add a new global of the given type and return its global index.
@@ -2015,6 +2509,18 @@ impl Module {
         u: &mut Unstructured,
         allow_defined_globals: bool,
     ) -> Result<ConstExpr> {
+        if self.config.subtype_global_get_ratio > 0.0 {
+            let subtype_globals = self
+                .globals_for_const_expr(ty, allow_defined_globals)
+                .filter(|i| self.globals[*i as usize].val_type != ty)
+                .collect::<Vec<_>>();
+            if !subtype_globals.is_empty()
+                && arbitrary_ratio(u, self.config.subtype_global_get_ratio)?
+            {
+                return Ok(ConstExpr::global_get(*u.choose(&subtype_globals)?));
+            }
+        }
+
         let mut choices = mem::take(&mut self.const_expr_choices);
         choices.clear();
 
@@ -2033,13 +2539,23 @@ impl Module {
             ValType::I32 => {
                 choices.push(Box::new(|u, _| Ok(ConstExpr::i32_const(u.arbitrary()?))));
                 if self.config.extended_const_enabled {
-                    choices.push(Box::new(arbitrary_extended_const));
+                    let globals = self
+                        .globals_for_const_expr(ty, allow_defined_globals)
+                        .collect::<Vec<_>>();
+                    choices.push(Box::new(move |u, ty| {
+                        arbitrary_extended_const(u, ty, &globals)
+                    }));
                 }
             }
             ValType::I64 => {
                 choices.push(Box::new(|u, _| Ok(ConstExpr::i64_const(u.arbitrary()?))));
                 if self.config.extended_const_enabled {
-                    choices.push(Box::new(arbitrary_extended_const));
+                    let globals = self
+                        .globals_for_const_expr(ty, allow_defined_globals)
+                        .collect::<Vec<_>>();
+                    choices.push(Box::new(move |u, ty| {
+                        arbitrary_extended_const(u, ty, &globals)
+                    }));
                 }
             }
             ValType::F32 => choices.push(Box::new(|u, _| {
@@ -2088,10 +2604,11 @@ impl Module {
                             }
                             choices.push(Box::new(move |_, _| Ok(ConstExpr::ref_func(i as u32))));
                         }
+                        if self.config.gc_enabled {
+                            self.push_gc_const_expr_choices(ty, &mut choices);
+                        }
                     }
 
-                    // TODO: fill out more GC types e.g `array.new` and
-                    // `struct.new`
                     _ => {}
                 }
             }
@@ -2100,67 +2617,106 @@ impl Module {
         let f = u.choose(&choices)?;
         let ret = f(u, ty);
         self.const_expr_choices = choices;
-        return ret;
-
-        /// Implementation of generation of expressions from the
-        /// `extended-const` proposal to WebAssembly. This proposal enabled
-        /// using `i{32,64}.{add,sub,mul}` in constant expressions in addition
-        /// to the previous `i{32,64}.const` instructions. Note that at this
-        /// time this doesn't use the full expression generator in
-        /// `code_builder.rs` but instead inlines just what's necessary for
-        /// constant expressions here.
-        fn arbitrary_extended_const(u: &mut Unstructured<'_>, ty: ValType) -> Result<ConstExpr> {
-            use wasm_encoder::Instruction::*;
-
-            // This only works for i32/i64, would need refactoring for different
-            // types.
-            assert!(ty == ValType::I32 || ty == ValType::I64);
-            let add = if ty == ValType::I32 { I32Add } else { I64Add };
-            let sub = if ty == ValType::I32 { I32Sub } else { I64Sub };
-            let mul = if ty == ValType::I32 { I32Mul } else { I64Mul };
-            let const_: fn(&mut Unstructured<'_>) -> Result<wasm_encoder::Instruction<'static>> =
-                if ty == ValType::I32 {
-                    |u| u.arbitrary().map(I32Const)
-                } else {
-                    |u| u.arbitrary().map(I64Const)
-                };
+        ret
+    }
 
-            // Here `instrs` is the list of instructions, in reverse order, that
-            // are going to be emitted. The `needed` value keeps track of how
-            // many values are needed to complete this expression. New
-            // instructions must be generated while some more items are needed.
-            let mut instrs = Vec::new();
-            let mut needed = 1;
-            while needed > 0 {
-                // If fuzz data has been exhausted or if this is a "large
-                // enough" constant expression then force generation of
-                // constants to finish out the expression.
-                let choice = if u.is_empty() || instrs.len() > 10 {
-                    0
-                } else {
-                    u.int_in_range(0..=3)?
-                };
-                match choice {
-                    0 => {
-                        instrs.push(const_(u)?);
-                        needed -= 1;
-                    }
-                    1 => {
-                        instrs.push(add.clone());
-                        needed += 1;
-                    }
-                    2 => {
-                        instrs.push(sub.clone());
-                        needed += 1;
-                    }
-                    3 => {
-                        instrs.push(mul.clone());
-                        needed += 1;
+    /// Pushes choices for constant expressions allocating a new instance of
+    /// the struct or array type `ty`, as allowed by the GC proposal.
+    ///
+    /// The fields or elements of the new instance are always plain constants
+    /// or `ref.null`, so types with non-nullable reference fields can only be
+    /// created here if they're empty.
+    fn push_gc_const_expr_choices(
+        &self,
+        ty: u32,
+        choices: &mut Vec<Box<dyn Fn(&mut Unstructured, ValType) -> Result<ConstExpr>>>,
+    ) {
+        use wasm_encoder::Instruction;
+
+        // Arrays created in constant expressions are kept small.
+        const MAX_CONST_ARRAY_LEN: u32 = 4;
+
+        fn arbitrary_field_const(
+            u: &mut Unstructured,
+            ty: StorageType,
+        ) -> Result<Instruction<'static>> {
+            Ok(match ty {
+                StorageType::I8 | StorageType::I16 | StorageType::Val(ValType::I32) => {
+                    Instruction::I32Const(u.arbitrary()?)
+                }
+                StorageType::Val(ValType::I64) => Instruction::I64Const(u.arbitrary()?),
+                StorageType::Val(ValType::F32) => {
+                    Instruction::F32Const(u.arbitrary::<f32>()?.into())
+                }
+                StorageType::Val(ValType::F64) => {
+                    Instruction::F64Const(u.arbitrary::<f64>()?.into())
+                }
+                StorageType::Val(ValType::V128) => Instruction::V128Const(u.arbitrary()?),
+                StorageType::Val(ValType::Ref(r)) => {
+                    debug_assert!(r.nullable);
+                    Instruction::RefNull(r.heap_type)
+                }
+            })
+        }
+
+        match &self.ty(ty).composite_type.inner {
+            CompositeInnerType::Func(_) => {}
+            CompositeInnerType::Struct(s) => {
+                let fields = s.fields.iter().map(|f| f.element_type).collect::<Vec<_>>();
+                if !fields.iter().all(|f| f.is_defaultable()) {
+                    return;
+                }
+                choices.push(Box::new(move |_, _| {
+                    Ok(ConstExpr::extended([Instruction::StructNewDefault(ty)]))
+                }));
+                choices.push(Box::new(move |u, _| {
+                    let mut instrs = Vec::with_capacity(fields.len() + 1);
+                    for field in fields.iter() {
+                        instrs.push(arbitrary_field_const(u, *field)?);
                     }
-                    _ => unreachable!(),
+                    instrs.push(Instruction::StructNew(ty));
+                    Ok(ConstExpr::extended(instrs))
+                }));
+            }
+            CompositeInnerType::Array(a) => {
+                let elem = a.0.element_type;
+                if !elem.is_defaultable() {
+                    choices.push(Box::new(move |_, _| {
+                        Ok(ConstExpr::extended([Instruction::ArrayNewFixed {
+                            array_type_index: ty,
+                            array_size: 0,
+                        }]))
+                    }));
+                    return;
                 }
+                choices.push(Box::new(move |u, _| {
+                    let len = u.int_in_range(0..=MAX_CONST_ARRAY_LEN)?;
+                    Ok(ConstExpr::extended([
+                        Instruction::I32Const(len as i32),
+                        Instruction::ArrayNewDefault(ty),
+                    ]))
+                }));
+                choices.push(Box::new(move |u, _| {
+                    let len = u.int_in_range(0..=MAX_CONST_ARRAY_LEN)?;
+                    Ok(ConstExpr::extended([
+                        arbitrary_field_const(u, elem)?,
+                        Instruction::I32Const(len as i32),
+                        Instruction::ArrayNew(ty),
+                    ]))
+                }));
+                choices.push(Box::new(move |u, _| {
+                    let len = u.int_in_range(0..=MAX_CONST_ARRAY_LEN)?;
+                    let mut instrs = Vec::with_capacity(len as usize + 1);
+                    for _ in 0..len {
+                        instrs.push(arbitrary_field_const(u, elem)?);
+                    }
+                    instrs.push(Instruction::ArrayNewFixed {
+                        array_type_index: ty,
+                        array_size: len,
+                    });
+                    Ok(ConstExpr::extended(instrs))
+                }));
             }
-            Ok(ConstExpr::extended(instrs.into_iter().rev()))
         }
     }
 
@@ -2198,22 +2754,11 @@ impl Module {
 
     #[cfg(feature = "wasmparser")]
     fn _required_exports(&mut self, u: &mut Unstructured, example_module: &[u8]) -> Result<()> {
-        let mut required_exports: Vec<wasmparser::Export> = vec![];
-        let mut validator = wasmparser::Validator::new();
-        let exports_types = validator
-            .validate_all(&example_module)
-            .expect("Failed to validate `exports` Wasm");
-        for payload in wasmparser::Parser::new(0).parse_all(&example_module) {
-            match payload.expect("Failed to read `exports` Wasm") {
-                wasmparser::Payload::ExportSection(export_reader) => {
-                    required_exports = export_reader
-                        .into_iter()
-                        .collect::<Result<_, _>>()
-                        .expect("Failed to read `exports` export section");
-                }
-                _ => {}
-            }
-        }
+        let ExampleModule {
+            validated_types: exports_types,
+            exports: required_exports,
+            ..
+        } = ExampleModule::parse(example_module, "exports");
 
         // For each export, add necessary prerequisites to the module.
         let exports_types = exports_types.as_ref();
@@ -2274,6 +2819,7 @@ impl Module {
                     let (func_type, subtype) = check_and_get_func_type(id);
                     self.rec_groups.push(self.types.len()..self.types.len() + 1);
                     let type_index = self.add_type(subtype);
+                    let type_index = self.deduplicate_last_rec_group().unwrap_or(type_index);
                     let func_index = self.funcs.len() as u32;
                     self.funcs.push((type_index, func_type));
                     self.num_defined_funcs += 1;
@@ -2296,6 +2842,7 @@ impl Module {
                     let (func_type, subtype) = check_and_get_func_type(id);
                     self.rec_groups.push(self.types.len()..self.types.len() + 1);
                     let type_index = self.add_type(subtype);
+                    let type_index = self.deduplicate_last_rec_group().unwrap_or(type_index);
                     let tag_index = self.tags.len() as u32;
                     self.tags.push(TagType {
                         func_type_idx: type_index,
@@ -2314,7 +2861,10 @@ impl Module {
     }
 
     fn arbitrary_exports(&mut self, u: &mut Unstructured) -> Result<()> {
-        if self.config.max_type_size < self.type_size && !self.config.export_everything {
+        if self.config.max_type_size < self.type_size
+            && !self.config.export_everything
+            && !self.config.export_all_kinds
+        {
             return Ok(());
         }
 
@@ -2340,28 +2890,46 @@ impl Module {
                 .map(|i| (ExportKind::Global, i as u32))
                 .collect(),
         );
+        choices.push(
+            (0..self.tags.len())
+                .map(|i| (ExportKind::Tag, i as u32))
+                .collect(),
+        );
 
         // If the configuration demands exporting everything, we do so here and
         // early-return.
         if self.config.export_everything {
             for choices_by_kind in choices {
                 for (kind, idx) in choices_by_kind {
-                    let name = unique_string(1_000, &mut self.export_names, u)?;
+                    let name = self.arbitrary_export_name(u)?;
                     self.add_arbitrary_export(name, kind, idx)?;
                 }
             }
             return Ok(());
         }
 
-        arbitrary_loop(u, self.config.min_exports, self.config.max_exports, |u| {
+        // Export one item of each kind first if required.
+        let mut exported = 0;
+        if self.config.export_all_kinds {
+            for list in choices.iter().filter(|list| !list.is_empty()) {
+                let name = self.arbitrary_export_name(u)?;
+                let (kind, idx) = *u.choose(list)?;
+                self.add_arbitrary_export(name, kind, idx)?;
+                exported += 1;
+            }
+        }
+
+        let min_exports = self.config.min_exports.saturating_sub(exported);
+        let max_exports = self.config.max_exports.saturating_sub(exported);
+        arbitrary_loop(u, min_exports, max_exports, |u| {
             // Remove all candidates for export whose type size exceeds our
             // remaining budget for type size. Then also remove any classes
             // of exports which no longer have any candidates.
             //
             // If there's nothing remaining after this, then we're done.
-            let max_size = self.config.max_type_size - self.type_size;
+            let max_size = self.config.max_type_size.saturating_sub(self.type_size);
             for list in choices.iter_mut() {
-                list.retain(|(kind, idx)| self.type_of(*kind, *idx).size() + 1 < max_size);
+                list.retain(|(kind, idx)| self.type_of(*kind, *idx).size(self) + 1 < max_size);
             }
             choices.retain(|list| !list.is_empty());
             if choices.is_empty() {
@@ -2370,17 +2938,64 @@ impl Module {
 
             // Pick a name, then pick the export, and then we can record
             // information about the chosen export.
-            let name = unique_string(1_000, &mut self.export_names, u)?;
+            let name = self.arbitrary_export_name(u)?;
             let list = u.choose(&choices)?;
-            let (kind, idx) = *u.choose(list)?;
+            let (kind, idx) = match self.arbitrary_reexport(u, list)? {
+                Some(export) => export,
+                None => *u.choose(list)?,
+            };
             self.add_arbitrary_export(name, kind, idx)?;
             Ok(true)
         })
     }
 
+    fn arbitrary_export_name(&mut self, u: &mut Unstructured) -> Result<String> {
+        if self.config.kebab_case_export_names {
+            unique_kebab_string(1_000, &mut self.export_names, u)
+        } else {
+            unique_string(
+                1_000,
+                self.config.string_alphabet,
+                &mut self.export_names,
+                u,
+            )
+        }
+    }
+
+    /// Possibly choose an imported entity from `candidates` to re-export, as
+    /// configured by `reexport_imports_ratio`.
+    fn arbitrary_reexport(
+        &self,
+        u: &mut Unstructured,
+        candidates: &[(ExportKind, u32)],
+    ) -> Result<Option<(ExportKind, u32)>> {
+        if self.config.reexport_imports_ratio <= 0.0 {
+            return Ok(None);
+        }
+        let imported = candidates
+            .iter()
+            .copied()
+            .filter(|(kind, idx)| (*idx as usize) < self.num_imported(*kind))
+            .collect::<Vec<_>>();
+        if imported.is_empty() || !arbitrary_ratio(u, self.config.reexport_imports_ratio)? {
+            return Ok(None);
+        }
+        Ok(Some(*u.choose(&imported)?))
+    }
+
+    fn num_imported(&self, kind: ExportKind) -> usize {
+        match kind {
+            ExportKind::Func => self.num_imported_functions(),
+            ExportKind::Table => self.num_imported_tables(),
+            ExportKind::Memory => self.num_imported_memories(),
+            ExportKind::Global => self.num_imported_globals(),
+            ExportKind::Tag => self.num_imported_tags(),
+        }
+    }
+
     fn add_arbitrary_export(&mut self, name: String, kind: ExportKind, idx: u32) -> Result<()> {
         let ty = self.type_of(kind, idx);
-        self.type_size += 1 + ty.size();
+        self.type_size += 1 + ty.size(self);
         if self.type_size <= self.config.max_type_size {
             self.exports.push((name, kind, idx));
             Ok(())
@@ -2405,7 +3020,13 @@ impl Module {
             }
         }
 
-        if !choices.is_empty() && u.arbitrary().unwrap_or(false) {
+        if self.config.force_start_function && choices.is_empty() {
+            choices.extend(self.add_nullary_func());
+        }
+
+        if !choices.is_empty()
+            && (self.config.force_start_function || u.arbitrary().unwrap_or(false))
+        {
             let f = *u.choose(&choices)?;
             self.start = Some(f);
         }
@@ -2413,7 +3034,62 @@ impl Module {
         Ok(())
     }
 
+    /// Adds a defined function of type `[] -> []` for `force_start_function`,
+    /// adding the type too if there isn't one, and returns its index.
+    ///
+    /// Returns `None` if there's no room for the function or its type.
+    fn add_nullary_func(&mut self) -> Option<u32> {
+        if !self.config.definitions_allowed || self.funcs.len() >= self.config.max_funcs {
+            return None;
+        }
+        let ty = self.nullary_func_type()?;
+        self.funcs.push((ty, self.func_type(ty).clone()));
+        self.num_defined_funcs += 1;
+        Some(self.funcs.len() as u32 - 1)
+    }
+
+    /// Returns the index of an unshared `[] -> []` function type, adding one
+    /// if there isn't one already and `max_types` allows it.
+    fn nullary_func_type(&mut self) -> Option<u32> {
+        self.find_or_add_func_type(&[], &[])
+    }
+
+    /// Returns the index of an unshared function type with the given params
+    /// and results, adding one if there isn't one already and `max_types`
+    /// allows it.
+    fn find_or_add_func_type(&mut self, params: &[ValType], results: &[ValType]) -> Option<u32> {
+        let existing = self.func_types.iter().copied().find(|&i| {
+            let ty = self.func_type(i);
+            !self.is_shared_type(i) && ty.params == params && ty.results == results
+        });
+        if existing.is_some() {
+            return existing;
+        }
+        if self.types.len() >= self.config.max_types {
+            return None;
+        }
+        self.rec_groups.push(self.types.len()..self.types.len() + 1);
+        self.add_type(SubType {
+            is_final: true,
+            supertype: None,
+            composite_type: CompositeType {
+                inner: CompositeInnerType::Func(Rc::new(FuncType {
+                    params: params.to_vec(),
+                    results: results.to_vec(),
+                })),
+                shared: false,
+            },
+            depth: 1,
+        });
+        self.should_encode_types = true;
+        self.func_types.last().copied()
+    }
+
     fn arbitrary_elems(&mut self, u: &mut Unstructured) -> Result<()> {
+        if self.config.fully_initialize_tables {
+            self.arbitrary_full_table_elems(u)?;
+        }
+
         // Create a helper closure to choose an arbitrary offset.
         let mut global_i32 = vec![];
         let mut global_i64 = vec![];
@@ -2426,8 +3102,35 @@ impl Module {
             }
         }
         let disallow_traps = self.config.disallow_traps;
+        let density = self.config.element_density;
         let arbitrary_active_elem =
             |u: &mut Unstructured, min_mem_size: u64, table: Option<u32>, table_ty: &TableType| {
+                let const_offset = |offset: u64| {
+                    if table_ty.table64 {
+                        Offset::Const64(offset as i64)
+                    } else {
+                        Offset::Const32(offset as i32)
+                    }
+                };
+                match density {
+                    ElementDensity::Arbitrary => {}
+                    ElementDensity::Dense => {
+                        let offset = const_offset(0);
+                        return Ok((ElementKind::Active { table, offset }, Some(min_mem_size)));
+                    }
+                    ElementDensity::Sparse => {
+                        const MAX_SPARSE_ELEMENTS: u64 = 4;
+                        let offset = if min_mem_size == 0 {
+                            0
+                        } else {
+                            u.int_in_range(0..=min_mem_size - 1)?
+                        };
+                        let max_size = (min_mem_size - offset).min(MAX_SPARSE_ELEMENTS);
+                        let offset = const_offset(offset);
+                        return Ok((ElementKind::Active { table, offset }, Some(max_size)));
+                    }
+                }
+
                 let global_choices = if table_ty.table64 {
                     &global_i64
                 } else {
@@ -2454,12 +3157,7 @@ impl Module {
                         None
                     };
 
-                    let offset = if table_ty.table64 {
-                        Offset::Const64(offset as i64)
-                    } else {
-                        Offset::Const32(offset as i32)
-                    };
-                    (offset, max_size_hint)
+                    (const_offset(offset), max_size_hint)
                 };
                 Ok((ElementKind::Active { table, offset }, max_size_hint))
             };
@@ -2508,10 +3206,15 @@ impl Module {
             return Ok(());
         }
 
+        // Segments which fully initialize tables count towards the limits.
         arbitrary_loop(
             u,
-            self.config.min_element_segments,
-            self.config.max_element_segments,
+            self.config
+                .min_element_segments
+                .saturating_sub(self.elems.len()),
+            self.config
+                .max_element_segments
+                .saturating_sub(self.elems.len()),
             |u| {
                 // Pick a kind of element segment to generate which will also
                 // give us a hint of the maximum size, if any.
@@ -2520,6 +3223,15 @@ impl Module {
                     .map(|i| usize::try_from(i).unwrap())
                     .unwrap_or_else(|| self.config.max_elements);
 
+                // Dense segments must fill their table, so they have an exact
+                // size.
+                let min = match (density, &kind) {
+                    (ElementDensity::Dense, ElementKind::Active { .. }) => {
+                        max.min(self.config.max_elements)
+                    }
+                    _ => self.config.min_elements,
+                };
+
                 // Infer, from the kind of segment, the type of the element
                 // segment. Passive/declared segments can be declared with any
                 // reference type, but active segments must match their table.
@@ -2570,7 +3282,7 @@ impl Module {
                 {
                     let mut init = vec![];
                     if func_candidates.len() > 0 {
-                        arbitrary_loop(u, self.config.min_elements, max, |u| {
+                        arbitrary_loop(u, min, max, |u| {
                             let func_idx = *u.choose(&func_candidates)?;
                             init.push(func_idx);
                             Ok(true)
@@ -2579,7 +3291,7 @@ impl Module {
                     Elements::Functions(init)
                 } else {
                     let mut init = vec![];
-                    arbitrary_loop(u, self.config.min_elements, max, |u| {
+                    arbitrary_loop(u, min, max, |u| {
                         init.push(self.arbitrary_const_expr(ValType::Ref(ty), u, true)?);
                         Ok(true)
                     })?;
@@ -2592,6 +3304,95 @@ impl Module {
         )
     }
 
+    /// Generates an active element segment for each defined table which
+    /// covers the whole of its minimum size, as configured by
+    /// `fully_initialize_tables`.
+    fn arbitrary_full_table_elems(&mut self, u: &mut Unstructured) -> Result<()> {
+        let num_imported_tables = self.num_imported_tables();
+        for i in num_imported_tables..self.tables.len() {
+            let ty = self.tables[i];
+            if ty.minimum == 0 {
+                continue;
+            }
+            let table = if i == 0 && ty.element_type == RefType::FUNCREF {
+                None
+            } else if self.config.bulk_memory_enabled {
+                Some(i as u32)
+            } else {
+                continue;
+            };
+            let offset = if ty.table64 {
+                Offset::Const64(0)
+            } else {
+                Offset::Const32(0)
+            };
+            let len = usize::try_from(ty.minimum).unwrap();
+
+            // Collect all the non-null constants that can fill this table.
+            let non_null = ValType::Ref(RefType {
+                nullable: false,
+                ..ty.element_type
+            });
+            let mut funcs = Vec::new();
+            match ty.element_type.heap_type {
+                HeapType::Abstract {
+                    ty: AbstractHeapType::Func,
+                    shared,
+                } => {
+                    for (f, (t, _)) in self.funcs.iter().enumerate() {
+                        if shared == self.is_shared_type(*t) {
+                            funcs.push(f as u32);
+                        }
+                    }
+                }
+                HeapType::Concrete(ty) => {
+                    for (f, (t, _)) in self.funcs.iter().enumerate() {
+                        if *t == ty {
+                            funcs.push(f as u32);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            let globals = self
+                .globals_for_const_expr(non_null, true)
+                .collect::<Vec<_>>();
+
+            let items = if !self.config.reference_types_enabled {
+                // Without reference types the only way to fill the table is
+                // with a list of function indices.
+                if funcs.is_empty() {
+                    continue;
+                }
+                let mut init = Vec::with_capacity(len);
+                for _ in 0..len {
+                    init.push(*u.choose(&funcs)?);
+                }
+                Elements::Functions(init)
+            } else {
+                let mut init = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let n = funcs.len() + globals.len();
+                    init.push(if n == 0 {
+                        self.arbitrary_const_expr(ValType::Ref(ty.element_type), u, true)?
+                    } else {
+                        match u.int_in_range(0..=n - 1)? {
+                            j if j < funcs.len() => ConstExpr::ref_func(funcs[j]),
+                            j => ConstExpr::global_get(globals[j - funcs.len()]),
+                        }
+                    });
+                }
+                Elements::Expressions(init)
+            };
+            self.elems.push(ElementSegment {
+                kind: ElementKind::Active { table, offset },
+                ty: ty.element_type,
+                items,
+            });
+        }
+        Ok(())
+    }
+
     fn arbitrary_code(&mut self, u: &mut Unstructured) -> Result<()> {
         self.compute_interesting_values();
 
@@ -2600,12 +3401,32 @@ impl Module {
             self,
             self.config.exports.is_some() || self.config.module_shape.is_some(),
         );
-        for (idx, ty) in self.funcs[self.funcs.len() - self.num_defined_funcs..].iter() {
+        let defined_funcs = &self.funcs[self.num_imported_functions()..];
+
+        // When the total number of instructions is capped, every function
+        // needs room for its smallest terminating body, so reserve that many
+        // instructions for the functions that haven't been generated yet.
+        let mut remaining = self.config.max_total_instructions;
+        let mut reserved = defined_funcs
+            .iter()
+            .map(|(_, ty)| self.min_func_body_len(ty))
+            .sum::<usize>();
+        for (idx, ty) in defined_funcs.iter() {
+            reserved -= self.min_func_body_len(ty);
+            let budget = remaining.map(|r| r.saturating_sub(reserved));
             let shared = self.is_shared_type(*idx);
-            let body = self.arbitrary_func_body(u, ty, &mut allocs, shared)?;
+            let body = self.arbitrary_func_body(u, ty, &mut allocs, shared, budget)?;
+            if let (Some(remaining), Instructions::Generated(instructions)) =
+                (&mut remaining, &body.instructions)
+            {
+                *remaining = remaining.saturating_sub(instructions.len());
+            }
             self.code.push(body);
         }
         allocs.finish(u, self)?;
+        if self.exercise_memory_lifecycle() {
+            self.add_memory_lifecycle_instructions();
+        }
         Ok(())
     }
 
@@ -2615,13 +3436,29 @@ impl Module {
         ty: &FuncType,
         allocs: &mut CodeBuilderAllocations,
         shared: bool,
+        budget: Option<usize>,
     ) -> Result<Code> {
         let mut locals = self.arbitrary_locals(u)?;
         let builder = allocs.builder(ty, &mut locals, shared);
         let instructions = if self.config.allow_invalid_funcs && u.arbitrary().unwrap_or(false) {
             Instructions::Arbitrary(arbitrary_vec_u8(u)?)
         } else {
-            Instructions::Generated(builder.arbitrary(u, self)?)
+            let max_instructions = match budget {
+                Some(budget) => budget.min(self.config.max_instructions),
+                None => self.config.max_instructions,
+            };
+            let mut instructions = builder.arbitrary(u, self, max_instructions)?;
+
+            // Finishing the body's control frames may have pushed it over the
+            // module-wide budget, in which case fall back to the smallest body
+            // that still terminates. If there isn't one, the generated body is
+            // kept as-is.
+            if budget.is_some_and(|b| instructions.len() > b) {
+                if let Some(body) = self.min_func_body(ty) {
+                    instructions = body;
+                }
+            }
+            Instructions::Generated(instructions)
         };
 
         Ok(Code {
@@ -2630,9 +3467,33 @@ impl Module {
         })
     }
 
+    /// The smallest body for a function of type `ty` that terminates: empty
+    /// if it has no results, and otherwise `unreachable`, or a constant value
+    /// for each result when traps are disallowed.
+    ///
+    /// Returns `None` if traps are disallowed and one of the results is a
+    /// non-nullable reference for which no value can be allocated.
+    fn min_func_body(&self, ty: &FuncType) -> Option<Vec<Instruction>> {
+        if self.config.disallow_traps {
+            const_results(self, &ty.results)
+        } else if ty.results.is_empty() {
+            Some(Vec::new())
+        } else {
+            Some(vec![Instruction::Unreachable])
+        }
+    }
+
+    fn min_func_body_len(&self, ty: &FuncType) -> usize {
+        self.min_func_body(ty).map_or(0, |body| body.len())
+    }
+
     fn arbitrary_locals(&self, u: &mut Unstructured) -> Result<Vec<ValType>> {
         let mut ret = Vec::new();
-        arbitrary_loop(u, 0, 100, |u| {
+        if self.config.all_valtypes_in_locals && self.code.is_empty() {
+            ret.extend(self.valtypes.iter().copied());
+        }
+        let max_locals = self.config.max_locals.saturating_sub(ret.len());
+        arbitrary_loop(u, 0, max_locals, |u| {
             ret.push(self.arbitrary_valtype(u)?);
             Ok(true)
         })?;
@@ -2649,10 +3510,8 @@ impl Module {
         let disallow_traps = self.config.disallow_traps;
         let mut choices32: Vec<Box<dyn Fn(&mut Unstructured, u64, usize) -> Result<Offset>>> =
             vec![];
-        choices32.push(Box::new(|u, min_size, data_len| {
-            let min = u32::try_from(min_size.saturating_mul(64 * 1024))
-                .unwrap_or(u32::MAX)
-                .into();
+        choices32.push(Box::new(|u, min_bytes, data_len| {
+            let min = u32::try_from(min_bytes).unwrap_or(u32::MAX).into();
             let max = if disallow_traps { min } else { u32::MAX.into() };
             Ok(Offset::Const32(
                 arbitrary_offset(u, min, max, data_len)? as i32
@@ -2660,9 +3519,14 @@ impl Module {
         }));
         let mut choices64: Vec<Box<dyn Fn(&mut Unstructured, u64, usize) -> Result<Offset>>> =
             vec![];
-        choices64.push(Box::new(|u, min_size, data_len| {
-            let min = min_size.saturating_mul(64 * 1024);
-            let max = if disallow_traps { min } else { u64::MAX };
+        let offset64_limit = if self.config.clamp_offsets_to_32bit {
+            u64::from(u32::MAX)
+        } else {
+            u64::MAX
+        };
+        choices64.push(Box::new(move |u, min_bytes, data_len| {
+            let min = min_bytes.min(offset64_limit);
+            let max = if disallow_traps { min } else { offset64_limit };
             Ok(Offset::Const64(
                 arbitrary_offset(u, min, max, data_len)? as i64
             ))
@@ -2671,8 +3535,10 @@ impl Module {
             for i in self.globals_for_const_expr(ValType::I32, true) {
                 choices32.push(Box::new(move |_, _, _| Ok(Offset::Global(i))));
             }
-            for i in self.globals_for_const_expr(ValType::I64, true) {
-                choices64.push(Box::new(move |_, _, _| Ok(Offset::Global(i))));
+            if !self.config.clamp_offsets_to_32bit {
+                for i in self.globals_for_const_expr(ValType::I64, true) {
+                    choices64.push(Box::new(move |_, _, _| Ok(Offset::Global(i))));
+                }
             }
         }
 
@@ -2700,52 +3566,813 @@ impl Module {
             self.config.min_data_segments,
             self.config.max_data_segments,
             |u| {
-                let mut init: Vec<u8> = u.arbitrary()?;
+                let mut init = self.arbitrary_data_init(u)?;
 
                 // Passive data can only be generated if bulk memory is enabled.
                 // Otherwise if there are no memories we *only* generate passive
                 // data. Finally if all conditions are met we use an input byte to
                 // determine if it should be passive or active.
-                let kind =
-                    if self.config.bulk_memory_enabled && (memories.is_empty() || u.arbitrary()?) {
-                        DataSegmentKind::Passive
+                let kind = if self.config.bulk_memory_enabled
+                    && (memories.is_empty() || u.arbitrary()?)
+                {
+                    DataSegmentKind::Passive
+                } else {
+                    let memory_index = *u.choose(&memories)?;
+                    let mem = &self.memories[memory_index as usize];
+                    let f = if mem.memory64 {
+                        u.choose(&choices64)?
                     } else {
-                        let memory_index = *u.choose(&memories)?;
-                        let mem = &self.memories[memory_index as usize];
-                        let f = if mem.memory64 {
-                            u.choose(&choices64)?
-                        } else {
-                            u.choose(&choices32)?
-                        };
-                        let mut offset = f(u, mem.minimum, init.len())?;
-
-                        // If traps are disallowed then truncate the size of the
-                        // data segment to the minimum size of memory to guarantee
-                        // it will fit. Afterwards ensure that the offset of the
-                        // data segment is in-bounds by clamping it to the
-                        if self.config.disallow_traps {
-                            let max_size = (u64::MAX / 64 / 1024).min(mem.minimum) * 64 * 1024;
-                            init.truncate(max_size as usize);
-                            let max_offset = max_size - init.len() as u64;
-                            match &mut offset {
-                                Offset::Const32(x) => {
-                                    *x = (*x as u64).min(max_offset) as i32;
-                                }
-                                Offset::Const64(x) => {
-                                    *x = (*x as u64).min(max_offset) as i64;
-                                }
-                                Offset::Global(_) => unreachable!(),
+                        u.choose(&choices32)?
+                    };
+                    let min_bytes = mem.minimum.saturating_mul(u64::from(crate::page_size(mem)));
+                    let mut offset = f(u, min_bytes, init.len())?;
+
+                    // If traps are disallowed then truncate the size of the
+                    // data segment to the minimum size of memory to guarantee
+                    // it will fit. Afterwards ensure that the offset of the
+                    // data segment is in-bounds by clamping it to the
+                    // remaining space.
+                    if self.config.disallow_traps {
+                        init.truncate(usize::try_from(min_bytes).unwrap_or(usize::MAX));
+                        let max_offset = min_bytes - init.len() as u64;
+                        match &mut offset {
+                            Offset::Const32(x) => {
+                                *x = (*x as u64).min(max_offset) as i32;
                             }
+                            Offset::Const64(x) => {
+                                *x = (*x as u64).min(max_offset) as i64;
+                            }
+                            Offset::Global(_) | Offset::Expr(_) => unreachable!(),
                         }
-                        DataSegmentKind::Active {
-                            offset,
-                            memory_index,
-                        }
-                    };
+                    }
+                    DataSegmentKind::Active {
+                        offset,
+                        memory_index,
+                    }
+                };
                 self.data.push(DataSegment { kind, init });
                 Ok(true)
             },
-        )
+        )?;
+
+        if let Some(min_bytes) = self.config.min_data_bytes {
+            self.grow_data_to(min_bytes, &memories);
+        }
+        self.arbitrary_extended_data_offsets(u)?;
+        if self.exercise_memory_lifecycle() {
+            self.add_memory_lifecycle_segments(u)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the offsets of active data segments into extended constant
+    /// expressions as configured by `extended_const_data_offset_ratio`.
+    ///
+    /// Offsets are rewritten into an equivalent sum or difference of constants
+    /// so they stay in-bounds, or when traps are allowed they're sometimes
+    /// replaced entirely by an arbitrary expression over constants and
+    /// globals.
+    fn arbitrary_extended_data_offsets(&mut self, u: &mut Unstructured) -> Result<()> {
+        let ratio = self.config.extended_const_data_offset_ratio;
+        if ratio <= 0.0 || !self.config.extended_const_enabled {
+            return Ok(());
+        }
+        for i in 0..self.data.len() {
+            let DataSegmentKind::Active {
+                memory_index,
+                offset,
+            } = &self.data[i].kind
+            else {
+                continue;
+            };
+            if !arbitrary_ratio(u, ratio)? {
+                continue;
+            }
+            let memory64 = self.memories[*memory_index as usize].memory64;
+            let value = match offset {
+                Offset::Const32(x) => Some(i64::from(*x)),
+                Offset::Const64(x) => Some(*x),
+                Offset::Global(_) | Offset::Expr(_) => None,
+            };
+            let arbitrary = !self.config.disallow_traps
+                && !(memory64 && self.config.clamp_offsets_to_32bit)
+                && (value.is_none() || u.arbitrary()?);
+            let expr = match value {
+                Some(value) if !arbitrary => {
+                    let a = u.arbitrary::<i64>()?;
+                    match (memory64, u.arbitrary()?) {
+                        (false, false) => ConstExpr::i32_const(a as i32)
+                            .with_i32_const((value as i32).wrapping_sub(a as i32))
+                            .with_i32_add(),
+                        (false, true) => {
+                            ConstExpr::i32_const((value as i32).wrapping_add(a as i32))
+                                .with_i32_const(a as i32)
+                                .with_i32_sub()
+                        }
+                        (true, false) => ConstExpr::i64_const(a)
+                            .with_i64_const(value.wrapping_sub(a))
+                            .with_i64_add(),
+                        (true, true) => ConstExpr::i64_const(value.wrapping_add(a))
+                            .with_i64_const(a)
+                            .with_i64_sub(),
+                    }
+                }
+                _ => {
+                    let ty = if memory64 { ValType::I64 } else { ValType::I32 };
+                    let globals = self.globals_for_const_expr(ty, true).collect::<Vec<_>>();
+                    arbitrary_extended_const(u, ty, &globals)?
+                }
+            };
+            if let DataSegmentKind::Active { offset, .. } = &mut self.data[i].kind {
+                *offset = Offset::Expr(expr);
+            }
+        }
+        Ok(())
+    }
+
+    /// Grow data segments until they hold `min_bytes` in total for
+    /// `min_data_bytes`, adding a segment to grow if the existing ones don't
+    /// have room.
+    fn grow_data_to(&mut self, min_bytes: usize, memories: &[u32]) {
+        let total = self.data.iter().map(|d| d.init.len()).sum::<usize>();
+        let mut needed = min_bytes.saturating_sub(total);
+        if needed == 0 {
+            return;
+        }
+
+        // The number of bytes an active segment can hold without trapping
+        // instantiation, if traps are disallowed.
+        let capacity = |m: &Module, kind: &DataSegmentKind| -> usize {
+            match kind {
+                DataSegmentKind::Active {
+                    offset,
+                    memory_index,
+                } if m.config.disallow_traps => {
+                    let mem = &m.memories[*memory_index as usize];
+                    let min_bytes = mem.minimum.saturating_mul(u64::from(crate::page_size(mem)));
+                    let offset = match offset {
+                        Offset::Const32(x) => u64::from(*x as u32),
+                        Offset::Const64(x) => *x as u64,
+                        Offset::Global(_) | Offset::Expr(_) => unreachable!(),
+                    };
+                    usize::try_from(min_bytes.saturating_sub(offset)).unwrap_or(usize::MAX)
+                }
+                _ => usize::MAX,
+            }
+        };
+
+        let room = self
+            .data
+            .iter()
+            .map(|d| capacity(self, &d.kind).saturating_sub(d.init.len()))
+            .fold(0, usize::saturating_add);
+        if room < needed && self.data.len() < self.config.max_data_segments {
+            let kind = if self.config.bulk_memory_enabled {
+                DataSegmentKind::Passive
+            } else if let Some(&memory_index) = memories
+                .iter()
+                .max_by_key(|i| self.memories[**i as usize].minimum)
+            {
+                DataSegmentKind::Active {
+                    offset: if self.memories[memory_index as usize].memory64 {
+                        Offset::Const64(0)
+                    } else {
+                        Offset::Const32(0)
+                    },
+                    memory_index,
+                }
+            } else {
+                return;
+            };
+            self.data.push(DataSegment {
+                kind,
+                init: Vec::new(),
+            });
+        }
+
+        for i in 0..self.data.len() {
+            let room = capacity(self, &self.data[i].kind).saturating_sub(self.data[i].init.len());
+            let len = room.min(needed);
+            if len == 0 {
+                continue;
+            }
+            let filler = match self.config.data_content {
+                DataContentKind::Ascii => b' ',
+                _ => 0,
+            };
+            let init = &mut self.data[i].init;
+            let new_len = init.len() + len;
+            if init.is_empty() {
+                init.resize(new_len, filler);
+            } else {
+                let extra = init.iter().copied().cycle().take(len).collect::<Vec<_>>();
+                init.extend(extra);
+            }
+            needed -= len;
+            if needed == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Generate the bytes of a data segment according to
+    /// `self.config.data_content`.
+    fn arbitrary_data_init(&self, u: &mut Unstructured) -> Result<Vec<u8>> {
+        match self.config.data_content {
+            DataContentKind::Random => u.arbitrary(),
+            DataContentKind::Zeros => Ok(vec![0; u.arbitrary_len::<u8>()?]),
+            DataContentKind::Ascii => {
+                let len = u.arbitrary_len::<u8>()?;
+                (0..len).map(|_| u.int_in_range(0x20..=0x7e)).collect()
+            }
+            DataContentKind::RepeatedPattern => {
+                let pattern_len = u.int_in_range(1..=8)?;
+                let pattern = (0..pattern_len)
+                    .map(|_| u.arbitrary())
+                    .collect::<Result<Vec<u8>>>()?;
+                let len = u.arbitrary_len::<u8>()?;
+                Ok(pattern.iter().copied().cycle().take(len).collect())
+            }
+        }
+    }
+
+    /// Add `depth` functions for `call_chain_depth`, each of which calls the
+    /// next, and export the first.
+    fn add_call_chain(&mut self, u: &mut Unstructured, depth: usize) -> Result<()> {
+        if depth == 0 || !self.config.definitions_allowed {
+            return Ok(());
+        }
+        let Some(ty) = self.nullary_func_type() else {
+            return Ok(());
+        };
+        let head = u32::try_from(self.funcs.len()).unwrap();
+        for i in 0..depth {
+            let next = head + u32::try_from(i + 1).unwrap();
+            self.funcs.push((ty, self.func_type(ty).clone()));
+            self.num_defined_funcs += 1;
+            self.code.push(Code {
+                locals: Vec::new(),
+                instructions: Instructions::Generated(if i + 1 < depth {
+                    vec![Instruction::Call(next)]
+                } else {
+                    Vec::new()
+                }),
+            });
+        }
+        if self.config.exports.is_none() && self.config.module_shape.is_none() {
+            let name = self.arbitrary_export_name(u)?;
+            self.exports.push((name, ExportKind::Func, head));
+        }
+        Ok(())
+    }
+
+    /// Duplicate a rec group for `emit_canonical_duplicate_rec_groups`, and add
+    /// functions which use a type from the original rec group and its copy
+    /// interchangeably.
+    ///
+    /// The first function has type `[(ref null $a)] -> [(ref null $b)]` and
+    /// returns its argument, where `$a` is the original type and `$b` its
+    /// copy. The second passes a `(ref null $b)` to the first.
+    fn add_canonical_duplicate_rec_group(&mut self, u: &mut Unstructured) -> Result<()> {
+        if !self.config.gc_enabled || !self.config.definitions_allowed {
+            return Ok(());
+        }
+        let room = self.config.max_types.saturating_sub(self.types.len());
+        let candidates = self
+            .rec_groups
+            .iter()
+            .filter(|g| !g.is_empty() && g.len() <= room)
+            .cloned()
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        let group = u.choose(&candidates)?.clone();
+        let offset = u.int_in_range(0..=group.len() - 1)?;
+        let original = u32::try_from(group.start + offset).unwrap();
+        let copy = u32::try_from(self.add_canonical_rec_group_clone(group) + offset).unwrap();
+        self.should_encode_types = true;
+
+        let nullable_ref = |i| {
+            ValType::Ref(RefType {
+                nullable: true,
+                heap_type: HeapType::Concrete(i),
+            })
+        };
+        let Some(identity_ty) =
+            self.find_or_add_func_type(&[nullable_ref(original)], &[nullable_ref(copy)])
+        else {
+            return Ok(());
+        };
+        let Some(caller_ty) = self.nullary_func_type() else {
+            return Ok(());
+        };
+
+        let identity = u32::try_from(self.funcs.len()).unwrap();
+        for (ty, instructions) in [
+            (identity_ty, vec![Instruction::LocalGet(0)]),
+            (
+                caller_ty,
+                vec![
+                    Instruction::RefNull(HeapType::Concrete(copy)),
+                    Instruction::Call(identity),
+                    Instruction::Drop,
+                ],
+            ),
+        ] {
+            self.funcs.push((ty, self.func_type(ty).clone()));
+            self.num_defined_funcs += 1;
+            self.code.push(Code {
+                locals: Vec::new(),
+                instructions: Instructions::Generated(instructions),
+            });
+        }
+        Ok(())
+    }
+
+    /// Add a bump allocator implementing the canonical ABI's `cabi_realloc`
+    /// for `generate_cabi_realloc`, and export it.
+    ///
+    /// The allocator hands out memory from a mutable `i32` global which starts
+    /// at the end of the memory's initial size, or at 8 if that's empty since
+    /// `0` is the failure value, growing the memory as needed. When an
+    /// allocation can't be satisfied it returns `0` if `disallow_traps` is
+    /// enabled and traps otherwise.
+    ///
+    /// The global and function are added even if that exceeds `max_globals`
+    /// or `max_funcs`, while a memory is only added if `max_memories` allows
+    /// it.
+    fn add_cabi_realloc(&mut self) -> Result<()> {
+        if !self.config.definitions_allowed {
+            return Ok(());
+        }
+
+        let Some(ty) = self.find_or_add_func_type(&[ValType::I32; 4], &[ValType::I32]) else {
+            return Ok(());
+        };
+
+        // The allocator needs a 32-bit memory with the default page size,
+        // which is added if there isn't one already.
+        let existing = self
+            .memories
+            .iter()
+            .position(|m| !m.memory64 && m.page_size_log2.is_none());
+        let memory = match existing {
+            Some(i) => u32::try_from(i).unwrap(),
+            None if self.memories.len() < self.config.max_memories => {
+                let maximum = self.config.memory_max_size_required.then_some(1);
+                self.add_arbitrary_memory_of_type(MemoryType {
+                    minimum: 0,
+                    maximum,
+                    memory64: false,
+                    shared: false,
+                    page_size_log2: None,
+                })?
+            }
+            None => return Ok(()),
+        };
+
+        let initial_heap = self.memories[memory as usize]
+            .minimum
+            .saturating_mul(65536)
+            .max(8)
+            .min(u32::MAX.into());
+        let heap = u32::try_from(self.globals.len()).unwrap();
+        self.globals.push(GlobalType {
+            val_type: ValType::I32,
+            mutable: true,
+            shared: false,
+        });
+        self.defined_globals
+            .push((heap, ConstExpr::i32_const(initial_heap as u32 as i32)));
+
+        let func_idx = u32::try_from(self.funcs.len()).unwrap();
+        self.funcs.push((ty, self.func_type(ty).clone()));
+        self.num_defined_funcs += 1;
+        self.code.push(Code {
+            locals: vec![ValType::I64, ValType::I64],
+            instructions: Instructions::Generated(self.cabi_realloc_body(memory, heap)),
+        });
+
+        let name = "cabi_realloc";
+        if self.config.exports.is_none()
+            && self.config.module_shape.is_none()
+            && self.export_names.insert(name.to_string())
+        {
+            self.exports
+                .push((name.to_string(), ExportKind::Func, func_idx));
+        }
+        Ok(())
+    }
+
+    /// The body of the `cabi_realloc` bump allocator with signature
+    /// `(old_ptr, old_size, align, new_size) -> ptr`.
+    ///
+    /// Locals 4 and 5 are `i64`s holding the new pointer and the end of the
+    /// new allocation, which are computed with 64-bit arithmetic so they
+    /// can't overflow.
+    fn cabi_realloc_body(&self, memory: u32, heap: u32) -> Vec<Instruction> {
+        let (old_ptr, old_size, align, new_size, ptr, end) = (0, 1, 2, 3, 4, 5);
+        let fail = if self.config.disallow_traps {
+            vec![Instruction::I32Const(0), Instruction::Return]
+        } else {
+            vec![Instruction::Unreachable]
+        };
+        let memory_bytes = [
+            Instruction::MemorySize(memory),
+            Instruction::I64ExtendI32U,
+            Instruction::I64Const(16),
+            Instruction::I64Shl,
+        ];
+
+        let mut body = vec![
+            // An alignment of zero is treated as one.
+            Instruction::LocalGet(align),
+            Instruction::LocalGet(align),
+            Instruction::I32Eqz,
+            Instruction::I32Or,
+            Instruction::I64ExtendI32U,
+            Instruction::LocalSet(end),
+            // ptr = (heap + align - 1) & -align
+            Instruction::GlobalGet(heap),
+            Instruction::I64ExtendI32U,
+            Instruction::LocalGet(end),
+            Instruction::I64Add,
+            Instruction::I64Const(1),
+            Instruction::I64Sub,
+            Instruction::I64Const(0),
+            Instruction::LocalGet(end),
+            Instruction::I64Sub,
+            Instruction::I64And,
+            Instruction::LocalSet(ptr),
+            // end = ptr + new_size
+            Instruction::LocalGet(ptr),
+            Instruction::LocalGet(new_size),
+            Instruction::I64ExtendI32U,
+            Instruction::I64Add,
+            Instruction::LocalSet(end),
+            // Fail if the allocation doesn't fit in a 32-bit address space.
+            Instruction::LocalGet(end),
+            Instruction::I64Const(u32::MAX.into()),
+            Instruction::I64GtU,
+            Instruction::If(BlockType::Empty),
+        ];
+        body.extend(fail.iter().cloned());
+        body.push(Instruction::End);
+
+        // Grow the memory by enough pages to fit the allocation if needed,
+        // failing if it can't grow.
+        body.push(Instruction::LocalGet(end));
+        body.extend(memory_bytes.iter().cloned());
+        body.extend([Instruction::I64GtU, Instruction::If(BlockType::Empty)]);
+        body.push(Instruction::LocalGet(end));
+        body.extend(memory_bytes.iter().cloned());
+        body.extend([
+            Instruction::I64Sub,
+            Instruction::I64Const(65535),
+            Instruction::I64Add,
+            Instruction::I64Const(16),
+            Instruction::I64ShrU,
+            Instruction::I32WrapI64,
+            Instruction::MemoryGrow(memory),
+            Instruction::I32Const(-1),
+            Instruction::I32Eq,
+            Instruction::If(BlockType::Empty),
+        ]);
+        body.extend(fail.iter().cloned());
+        body.extend([Instruction::End, Instruction::End]);
+
+        // Copy `min(old_size, new_size)` bytes of the old allocation over,
+        // as long as they're in bounds.
+        if self.config.bulk_memory_enabled {
+            body.extend([
+                Instruction::LocalGet(old_ptr),
+                Instruction::I64ExtendI32U,
+                Instruction::LocalGet(old_size),
+                Instruction::LocalGet(new_size),
+                Instruction::LocalGet(old_size),
+                Instruction::LocalGet(new_size),
+                Instruction::I32LtU,
+                Instruction::Select,
+                Instruction::LocalTee(old_size),
+                Instruction::I64ExtendI32U,
+                Instruction::I64Add,
+            ]);
+            body.extend(memory_bytes.iter().cloned());
+            body.extend([
+                Instruction::I64LeU,
+                Instruction::If(BlockType::Empty),
+                Instruction::LocalGet(ptr),
+                Instruction::I32WrapI64,
+                Instruction::LocalGet(old_ptr),
+                Instruction::LocalGet(old_size),
+                Instruction::MemoryCopy {
+                    src_mem: memory,
+                    dst_mem: memory,
+                },
+                Instruction::End,
+            ]);
+        }
+
+        body.extend([
+            Instruction::LocalGet(end),
+            Instruction::I32WrapI64,
+            Instruction::GlobalSet(heap),
+            Instruction::LocalGet(ptr),
+            Instruction::I32WrapI64,
+        ]);
+        body
+    }
+
+    /// Make one function exported, placed in a table, and called directly
+    /// from another function's body for `exercise_func_sharing`.
+    fn add_func_sharing(&mut self, u: &mut Unstructured) -> Result<()> {
+        if self.config.exports.is_some() || self.config.module_shape.is_some() {
+            return Ok(());
+        }
+        let num_imported_funcs = self.num_imported_functions();
+        let Some((caller, _)) = self
+            .code
+            .iter()
+            .enumerate()
+            .find(|(_, c)| matches!(c.instructions, Instructions::Generated(_)))
+        else {
+            return Ok(());
+        };
+        let caller = u32::try_from(num_imported_funcs + caller).unwrap();
+
+        // The callee must not be the caller, to avoid unbounded recursion, and
+        // all of its parameters need a default value to call it with.
+        let defaultable = |ty: &ValType| match ty {
+            ValType::Ref(r) => r.nullable,
+            _ => true,
+        };
+        let candidates = self
+            .funcs
+            .iter()
+            .enumerate()
+            .filter(|(i, (ty, func))| {
+                *i as u32 != caller
+                    && !self.is_shared_type(*ty)
+                    && func.params.iter().all(defaultable)
+            })
+            .map(|(i, _)| i as u32)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        let func = *u.choose(&candidates)?;
+
+        // Find a `funcref` table with room for the function at offset zero,
+        // growing the minimum size of a defined table or adding a new table if
+        // necessary and `max_tables` allows it.
+        let num_imported_tables = self.num_imported_tables();
+        let table = self.tables.iter().enumerate().position(|(i, t)| {
+            t.element_type == RefType::FUNCREF
+                && (i == 0 || self.config.reference_types_enabled)
+                && (t.minimum > 0 || (i >= num_imported_tables && t.maximum != Some(0)))
+        });
+        let table = match table {
+            Some(i) => i,
+            None if self.can_add_local_or_import_table()
+                && (self.tables.is_empty() || self.config.reference_types_enabled) =>
+            {
+                self.tables.push(TableType {
+                    element_type: RefType::FUNCREF,
+                    minimum: 1,
+                    maximum: None,
+                    table64: false,
+                    shared: false,
+                });
+                self.defined_tables.push(None);
+                self.tables.len() - 1
+            }
+            None => return Ok(()),
+        };
+        let table_ty = &mut self.tables[table];
+        table_ty.minimum = table_ty.minimum.max(1);
+        let offset = if table_ty.table64 {
+            Offset::Const64(0)
+        } else {
+            Offset::Const32(0)
+        };
+        self.elems.push(ElementSegment {
+            kind: ElementKind::Active {
+                table: if table == 0 {
+                    None
+                } else {
+                    Some(u32::try_from(table).unwrap())
+                },
+                offset,
+            },
+            ty: RefType::FUNCREF,
+            items: Elements::Functions(vec![func]),
+        });
+
+        if !self
+            .exports
+            .iter()
+            .any(|(_, kind, idx)| *kind == ExportKind::Func && *idx == func)
+        {
+            let name = self.arbitrary_export_name(u)?;
+            self.exports.push((name, ExportKind::Func, func));
+        }
+
+        let ty = self.funcs[func as usize].1.clone();
+        let mut call = Vec::with_capacity(ty.params.len() + ty.results.len() + 1);
+        for param in ty.params.iter() {
+            call.push(match param {
+                ValType::I32 => Instruction::I32Const(0),
+                ValType::I64 => Instruction::I64Const(0),
+                ValType::F32 => Instruction::F32Const(0.0.into()),
+                ValType::F64 => Instruction::F64Const(0.0.into()),
+                ValType::V128 => Instruction::V128Const(0),
+                ValType::Ref(r) => Instruction::RefNull(r.heap_type),
+            });
+        }
+        call.push(Instruction::Call(func));
+        call.extend(ty.results.iter().map(|_| Instruction::Drop));
+        let caller = &mut self.code[caller as usize - num_imported_funcs];
+        if let Instructions::Generated(instructions) = &mut caller.instructions {
+            instructions.splice(0..0, call);
+        }
+        Ok(())
+    }
+
+    /// Prepend an instruction sequence with an observable side effect to the
+    /// start function's body for `meaningful_start`.
+    fn add_start_side_effect(&mut self, u: &mut Unstructured) -> Result<()> {
+        let Some(start) = self.start else {
+            return Ok(());
+        };
+        let Some(defined) = (start as usize).checked_sub(self.num_imported_functions()) else {
+            return Ok(());
+        };
+        if !matches!(self.code[defined].instructions, Instructions::Generated(_)) {
+            return Ok(());
+        }
+
+        let index_const = |is_64: bool, value: i32| {
+            if is_64 {
+                Instruction::I64Const(value.into())
+            } else {
+                Instruction::I32Const(value)
+            }
+        };
+        let mut choices: Vec<Vec<Instruction>> = Vec::new();
+        for (i, g) in self.globals.iter().enumerate() {
+            if !g.mutable {
+                continue;
+            }
+            let value = match g.val_type {
+                ValType::I32 => Instruction::I32Const(0),
+                ValType::I64 => Instruction::I64Const(0),
+                ValType::F32 => Instruction::F32Const(0.0.into()),
+                ValType::F64 => Instruction::F64Const(0.0.into()),
+                ValType::V128 => Instruction::V128Const(0),
+                ValType::Ref(r) if r.nullable => Instruction::RefNull(r.heap_type),
+                ValType::Ref(_) => continue,
+            };
+            choices.push(vec![value, Instruction::GlobalSet(i as u32)]);
+        }
+        for (i, m) in self.memories.iter().enumerate() {
+            // `memory.grow` returns -1 rather than trapping when the memory
+            // can't grow.
+            choices.push(vec![
+                index_const(m.memory64, 1),
+                Instruction::MemoryGrow(i as u32),
+                Instruction::Drop,
+            ]);
+        }
+        if self.config.reference_types_enabled {
+            for (i, t) in self.tables.iter().enumerate() {
+                if !t.element_type.nullable {
+                    continue;
+                }
+                // Filling zero elements at offset zero is always in bounds.
+                choices.push(vec![
+                    index_const(t.table64, 0),
+                    Instruction::RefNull(t.element_type.heap_type),
+                    index_const(t.table64, 0),
+                    Instruction::TableFill(i as u32),
+                ]);
+            }
+        }
+        let side_effect = if !choices.is_empty() {
+            u.choose(&choices)?.clone()
+        } else if self.config.definitions_allowed {
+            let global = self.add_arbitrary_global_of_type(
+                GlobalType {
+                    val_type: ValType::I32,
+                    mutable: true,
+                    shared: false,
+                },
+                u,
+            )?;
+            vec![Instruction::I32Const(1), Instruction::GlobalSet(global)]
+        } else {
+            return Ok(());
+        };
+
+        if let Instructions::Generated(instructions) = &mut self.code[defined].instructions {
+            instructions.splice(0..0, side_effect);
+        }
+        Ok(())
+    }
+
+    fn exercise_memory_lifecycle(&self) -> bool {
+        self.config.exercise_memory_lifecycle
+            && self.config.bulk_memory_enabled
+            && !self.memories.is_empty()
+    }
+
+    /// Ensure that there is both an active and a passive data segment for
+    /// `exercise_memory_lifecycle`.
+    fn add_memory_lifecycle_segments(&mut self, u: &mut Unstructured) -> Result<()> {
+        if !self
+            .data
+            .iter()
+            .any(|d| matches!(d.kind, DataSegmentKind::Active { .. }))
+        {
+            // An empty segment at offset zero is in bounds of any memory.
+            let offset = if self.memories[0].memory64 {
+                Offset::Const64(0)
+            } else {
+                Offset::Const32(0)
+            };
+            self.data.push(DataSegment {
+                kind: DataSegmentKind::Active {
+                    memory_index: 0,
+                    offset,
+                },
+                init: Vec::new(),
+            });
+        }
+        if !self
+            .data
+            .iter()
+            .any(|d| matches!(d.kind, DataSegmentKind::Passive))
+        {
+            self.data.push(DataSegment {
+                kind: DataSegmentKind::Passive,
+                init: self.arbitrary_data_init(u)?,
+            });
+        }
+        Ok(())
+    }
+
+    /// Prepend `memory.init`, `data.drop`, and `memory.copy` instructions to
+    /// the first generated function body for `exercise_memory_lifecycle`.
+    fn add_memory_lifecycle_instructions(&mut self) {
+        let Some(mem) = self.data.iter().find_map(|d| match d.kind {
+            DataSegmentKind::Active { memory_index, .. } => Some(memory_index),
+            DataSegmentKind::Passive => None,
+        }) else {
+            return;
+        };
+        let Some(data_index) = self
+            .data
+            .iter()
+            .position(|d| matches!(d.kind, DataSegmentKind::Passive))
+        else {
+            return;
+        };
+        let data_index = u32::try_from(data_index).unwrap();
+        let Some(instructions) = self
+            .code
+            .iter_mut()
+            .find_map(|c| match &mut c.instructions {
+                Instructions::Generated(instructions) => Some(instructions),
+                Instructions::Arbitrary(_) => None,
+            })
+        else {
+            return;
+        };
+
+        // Everything operates on zero bytes at offset zero so that none of
+        // these can trap, even after the segment is dropped.
+        let zero = if self.memories[mem as usize].memory64 {
+            Instruction::I64Const(0)
+        } else {
+            Instruction::I32Const(0)
+        };
+        instructions.splice(
+            0..0,
+            [
+                zero.clone(),
+                Instruction::I32Const(0),
+                Instruction::I32Const(0),
+                Instruction::MemoryInit { mem, data_index },
+                Instruction::DataDrop(data_index),
+                zero.clone(),
+                zero.clone(),
+                zero,
+                Instruction::MemoryCopy {
+                    src_mem: mem,
+                    dst_mem: mem,
+                },
+            ],
+        );
     }
 
     fn params_results(&self, ty: &BlockType) -> (Vec<ValType>, Vec<ValType>) {
@@ -2890,6 +4517,17 @@ impl Module {
                 }
             }
 
+            // Page counts are interesting as `memory.grow` deltas, especially
+            // those that grow the memory to exactly its maximum.
+            interesting(m.minimum);
+            if let Some(max) = m.maximum {
+                interesting(max);
+                let delta = max.saturating_sub(m.minimum);
+                interesting(delta);
+                interesting(delta.saturating_sub(1));
+                interesting(delta.saturating_add(1));
+            }
+
             if let Some(max) = m.maximum {
                 let max = max.saturating_mul(crate::page_size(m).into());
                 interesting(max);
@@ -2948,6 +4586,9 @@ impl Module {
                 u.arbitrary()?
             })),
             ValType::Ref(ty) => {
+                // `ref.null` is valid for any nullable heap type, abstract or
+                // concrete, so there's nothing kind-specific to handle here
+                // (e.g. for continuation types).
                 assert!(ty.nullable);
                 Ok(Instruction::RefNull(ty.heap_type))
             }
@@ -3081,7 +4722,7 @@ pub(crate) fn arbitrary_table_type(
         assert!(minimum > 0);
     }
     let element_type = match module {
-        Some(module) => module.arbitrary_ref_type(u)?,
+        Some(module) => module.arbitrary_table_element_type(u)?,
         None => RefType::FUNCREF,
     };
 
@@ -3105,7 +4746,11 @@ pub(crate) fn arbitrary_memtype(u: &mut Unstructured, config: &Config) -> Result
     // 25% of the time.
     let shared = config.threads_enabled && u.ratio(1, 4)?;
 
-    let memory64 = config.memory64_enabled && u.arbitrary()?;
+    let memory64 = config.memory64_enabled
+        && match config.uniform_memory_index_type {
+            Some(memory64) => memory64,
+            None => u.arbitrary()?,
+        };
     let page_size_log2 = if config.custom_page_sizes_enabled && u.arbitrary()? {
         Some(if u.arbitrary()? { 0 } else { 16 })
     } else {
@@ -3133,7 +4778,7 @@ pub(crate) fn arbitrary_memtype(u: &mut Unstructured, config: &Config) -> Result
     let max_inbounds = max_this_mem_in_bytes >> page_size_log2.unwrap_or(16);
     let max_inbounds = max_inbounds.clamp(min_pages.unwrap_or(0), max_pages);
 
-    let (minimum, maximum) = arbitrary_limits64(
+    let (mut minimum, maximum) = arbitrary_limits64(
         u,
         min_pages,
         max_pages,
@@ -3141,6 +4786,10 @@ pub(crate) fn arbitrary_memtype(u: &mut Unstructured, config: &Config) -> Result
         max_inbounds,
     )?;
 
+    if !config.disallow_traps && arbitrary_ratio(u, config.min_zero_memory_ratio)? {
+        minimum = 0;
+    }
+
     Ok(MemoryType {
         minimum,
         maximum,
@@ -3281,6 +4930,77 @@ fn gradually_grow(u: &mut Unstructured, min: u64, max_inbounds: u64, max: u64) -
     }
 }
 
+/// Implementation of generation of expressions from the
+/// `extended-const` proposal to WebAssembly. This proposal enabled
+/// using `i{32,64}.{add,sub,mul}` in constant expressions in addition
+/// to the previous `i{32,64}.const` instructions. Note that at this
+/// time this doesn't use the full expression generator in
+/// `code_builder.rs` but instead inlines just what's necessary for
+/// constant expressions here.
+///
+/// The leaves of the expression are either constants or a `global.get`
+/// of one of `globals`, which must all be valid in a constant
+/// expression of type `ty`.
+fn arbitrary_extended_const(
+    u: &mut Unstructured<'_>,
+    ty: ValType,
+    globals: &[u32],
+) -> Result<ConstExpr> {
+    use wasm_encoder::Instruction::*;
+    // This only works for i32/i64, would need refactoring for different
+    // types.
+    assert!(ty == ValType::I32 || ty == ValType::I64);
+    let add = if ty == ValType::I32 { I32Add } else { I64Add };
+    let sub = if ty == ValType::I32 { I32Sub } else { I64Sub };
+    let mul = if ty == ValType::I32 { I32Mul } else { I64Mul };
+    let const_: fn(&mut Unstructured<'_>) -> Result<wasm_encoder::Instruction<'static>> =
+        if ty == ValType::I32 {
+            |u| u.arbitrary().map(I32Const)
+        } else {
+            |u| u.arbitrary().map(I64Const)
+        };
+    // Here `instrs` is the list of instructions, in reverse order, that
+    // are going to be emitted. The `needed` value keeps track of how
+    // many values are needed to complete this expression. New
+    // instructions must be generated while some more items are needed.
+    let mut instrs = Vec::new();
+    let mut needed = 1;
+    while needed > 0 {
+        // If fuzz data has been exhausted or if this is a "large
+        // enough" constant expression then force generation of
+        // constants to finish out the expression.
+        let choice = if u.is_empty() || instrs.len() > 10 {
+            0
+        } else {
+            u.int_in_range(0..=3)?
+        };
+        match choice {
+            0 => {
+                if !globals.is_empty() && u.arbitrary()? {
+                    instrs.push(GlobalGet(*u.choose(globals)?));
+                } else {
+                    instrs.push(const_(u)?);
+                }
+                needed -= 1;
+            }
+            1 => {
+                instrs.push(add.clone());
+                needed += 1;
+            }
+            2 => {
+                instrs.push(sub.clone());
+                needed += 1;
+            }
+            3 => {
+                instrs.push(mul.clone());
+                needed += 1;
+            }
+            _ => unreachable!(),
+        }
+    }
+    Ok(ConstExpr::extended(instrs.into_iter().rev()))
+}
+
 /// Selects a reasonable offset for an element or data segment. This favors
 /// having the segment being in-bounds, but it may still generate
 /// any offset.
@@ -3301,9 +5021,17 @@ fn arbitrary_offset(
     }
 }
 
-fn unique_import_strings(max_size: usize, u: &mut Unstructured) -> Result<(String, String)> {
-    let module = limited_string(max_size, u)?;
-    let field = limited_string(max_size, u)?;
+fn unique_import_strings(
+    max_size: usize,
+    alphabet: Option<StringAlphabet>,
+    field_dictionary: Option<&[String]>,
+    u: &mut Unstructured,
+) -> Result<(String, String)> {
+    let module = limited_string(max_size, alphabet, u)?;
+    let field = match field_dictionary {
+        Some(dictionary) if !dictionary.is_empty() => u.choose(dictionary)?.clone(),
+        _ => limited_string(max_size, alphabet, u)?,
+    };
     Ok((module, field))
 }
 
@@ -3313,13 +5041,29 @@ fn arbitrary_vec_u8(u: &mut Unstructured) -> Result<Vec<u8>> {
 }
 
 impl EntityType {
-    fn size(&self) -> u32 {
+    /// The size of this entity's type, as accounted against
+    /// `Config::max_type_size`.
+    ///
+    /// Any concrete reference types in the entity's signature additionally
+    /// count the structural size of the types they refer to, transitively, so
+    /// that deeply nested GC types use up more of the budget than a flat
+    /// signature does.
+    fn size(&self, module: &Module) -> u32 {
+        let mut seen = HashSet::new();
+        let mut nested = |ty: ValType| module.nested_type_size(ty, &mut seen);
         match self {
-            EntityType::Tag(_)
-            | EntityType::Global(_)
-            | EntityType::Table(_)
-            | EntityType::Memory(_) => 1,
-            EntityType::Func(_, ty) => 1 + (ty.params.len() + ty.results.len()) as u32,
+            EntityType::Tag(t) => {
+                let params = &t.func_type.params;
+                1 + params.len() as u32 + params.iter().map(|t| nested(*t)).sum::<u32>()
+            }
+            EntityType::Global(g) => 1 + nested(g.val_type),
+            EntityType::Table(t) => 1 + nested(ValType::Ref(t.element_type)),
+            EntityType::Memory(_) => 1,
+            EntityType::Func(_, ty) => {
+                let params_and_results = ty.params.iter().chain(ty.results.iter());
+                1 + (ty.params.len() + ty.results.len()) as u32
+                    + params_and_results.map(|t| nested(*t)).sum::<u32>()
+            }
         }
     }
 }
@@ -3435,6 +5179,106 @@ impl FromStr for InstructionKind {
     }
 }
 
+/// The parsed contents of an example module, as provided through the
+/// `module_shape`, `available_imports`, or `exports` configuration options.
+#[cfg(feature = "wasmparser")]
+struct ExampleModule<'a> {
+    /// The type information computed while validating the example module.
+    validated_types: wasmparser::types::Types,
+    /// The number of types in each of the example module's rec groups.
+    rec_groups: Vec<usize>,
+    types: Vec<SubType>,
+    imports: Vec<wasmparser::Import<'a>>,
+    exports: Vec<wasmparser::Export<'a>>,
+    /// The type index of each defined function; imports are not included in
+    /// this or any of the following defined entities.
+    funcs: Vec<u32>,
+    tags: Vec<wasmparser::TagType>,
+    tables: Vec<wasmparser::TableType>,
+    memories: Vec<wasmparser::MemoryType>,
+    globals: Vec<wasmparser::GlobalType>,
+}
+
+#[cfg(feature = "wasmparser")]
+impl<'a> ExampleModule<'a> {
+    /// Validate and parse the example module given to the `option`
+    /// configuration option, panicking if it is invalid.
+    fn parse(wasm: &'a [u8], option: &str) -> Self {
+        let validated_types = wasmparser::Validator::new()
+            .validate_all(wasm)
+            .unwrap_or_else(|e| panic!("Failed to validate `{option}` module: {e}"));
+        let mut example = ExampleModule {
+            validated_types,
+            rec_groups: Vec::new(),
+            types: Vec::new(),
+            imports: Vec::new(),
+            exports: Vec::new(),
+            funcs: Vec::new(),
+            tags: Vec::new(),
+            tables: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+        };
+        let read = |e: wasmparser::BinaryReaderError| -> ! {
+            panic!("could not read the `{option}` module: {e}")
+        };
+        for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+            match payload.unwrap_or_else(|e| read(e)) {
+                wasmparser::Payload::TypeSection(type_reader) => {
+                    for recgrp in type_reader {
+                        let recgrp = recgrp.unwrap_or_else(|e| read(e));
+                        example.rec_groups.push(recgrp.types().len());
+                        for subtype in recgrp.into_types() {
+                            let mut subtype: SubType = subtype.try_into().unwrap();
+                            if let Some(supertype_idx) = subtype.supertype {
+                                subtype.depth = example.types[supertype_idx as usize].depth + 1;
+                            }
+                            example.types.push(subtype);
+                        }
+                    }
+                }
+                wasmparser::Payload::ImportSection(import_reader) => {
+                    for im in import_reader {
+                        example.imports.push(im.unwrap_or_else(|e| read(e)));
+                    }
+                }
+                wasmparser::Payload::ExportSection(export_reader) => {
+                    for ex in export_reader {
+                        example.exports.push(ex.unwrap_or_else(|e| read(e)));
+                    }
+                }
+                wasmparser::Payload::FunctionSection(function_reader) => {
+                    for func in function_reader {
+                        example.funcs.push(func.unwrap_or_else(|e| read(e)));
+                    }
+                }
+                wasmparser::Payload::TagSection(tag_reader) => {
+                    for tag in tag_reader {
+                        example.tags.push(tag.unwrap_or_else(|e| read(e)));
+                    }
+                }
+                wasmparser::Payload::TableSection(table_reader) => {
+                    for table in table_reader {
+                        example.tables.push(table.unwrap_or_else(|e| read(e)).ty);
+                    }
+                }
+                wasmparser::Payload::MemorySection(memory_reader) => {
+                    for memory in memory_reader {
+                        example.memories.push(memory.unwrap_or_else(|e| read(e)));
+                    }
+                }
+                wasmparser::Payload::GlobalSection(global_reader) => {
+                    for global in global_reader {
+                        example.globals.push(global.unwrap_or_else(|e| read(e)).ty);
+                    }
+                }
+                _ => {}
+            }
+        }
+        example
+    }
+}
+
 // Conversions from `wasmparser` to `wasm-smith`. Currently, only type conversions
 // have been implemented.
 #[cfg(feature = "wasmparser")]
@@ -1,14 +1,14 @@
 use super::{
-    CompositeInnerType, Elements, FuncType, Instruction, InstructionKind::*, InstructionKinds,
-    Module, ValType,
+    CompositeInnerType, DataSegmentKind, Elements, FuncType, Instruction, InstructionKind::*,
+    InstructionKinds, Module, ValType,
 };
-use crate::{MemoryOffsetChoices, unique_string};
+use crate::{MemoryOffsetChoices, PathologyKind, arbitrary_ratio};
 use arbitrary::{Result, Unstructured};
 use std::collections::{BTreeMap, BTreeSet};
 use std::rc::Rc;
 use wasm_encoder::{
     AbstractHeapType, ArrayType, BlockType, Catch, ConstExpr, ExportKind, FieldType, GlobalType,
-    HeapType, MemArg, RefType, StorageType, StructType,
+    HeapType, MemArg, MemoryType, Ordering, RefType, StorageType, StructType,
 };
 mod no_traps;
 
@@ -97,6 +97,10 @@ instructions! {
     (None, block, Control),
     (None, r#loop, Control),
     (Some(try_table_valid), try_table, Control),
+    (Some(try_valid), r#try, Control),
+    (Some(delegate_valid), delegate, Control),
+    (Some(catch_valid), catch, Control),
+    (Some(catch_all_valid), catch_all, Control),
     (Some(if_valid), r#if, Control),
     (Some(else_valid), r#else, Control),
     (Some(end_valid), end, Control),
@@ -112,6 +116,7 @@ instructions! {
     (Some(return_call_indirect_valid), return_call_indirect, Control),
     (Some(throw_valid), throw, Control, 850),
     (Some(throw_ref_valid), throw_ref, Control, 850),
+    (Some(rethrow_valid), rethrow, Control, 850),
     (Some(br_on_null_valid), br_on_null, Control),
     (Some(br_on_non_null_valid), br_on_non_null, Control),
     (Some(br_on_cast_valid), br_on_cast, Control),
@@ -155,6 +160,7 @@ instructions! {
     (Some(data_drop_valid), data_drop, MemoryInt),
     (Some(memory_copy_valid), memory_copy, MemoryInt),
     (Some(memory_fill_valid), memory_fill, MemoryInt),
+    (Some(atomic_fence_valid), atomic_fence, MemoryInt),
     // Numeric instructions.
     (None, i32_const, NumericInt),
     (None, i64_const, NumericInt),
@@ -259,16 +265,16 @@ instructions! {
     (Some(f64_f64_on_stack), f64_max, Numeric),
     (Some(f64_f64_on_stack), f64_copysign, Numeric),
     (Some(i64_on_stack), i32_wrap_i64, NumericInt),
-    (Some(f32_on_stack), i32_trunc_f32_s, Numeric),
-    (Some(f32_on_stack), i32_trunc_f32_u, Numeric),
-    (Some(f64_on_stack), i32_trunc_f64_s, Numeric),
-    (Some(f64_on_stack), i32_trunc_f64_u, Numeric),
+    (Some(trapping_f32_on_stack), i32_trunc_f32_s, Numeric),
+    (Some(trapping_f32_on_stack), i32_trunc_f32_u, Numeric),
+    (Some(trapping_f64_on_stack), i32_trunc_f64_s, Numeric),
+    (Some(trapping_f64_on_stack), i32_trunc_f64_u, Numeric),
     (Some(i32_on_stack), i64_extend_i32_s, NumericInt),
     (Some(i32_on_stack), i64_extend_i32_u, NumericInt),
-    (Some(f32_on_stack), i64_trunc_f32_s, Numeric),
-    (Some(f32_on_stack), i64_trunc_f32_u, Numeric),
-    (Some(f64_on_stack), i64_trunc_f64_s, Numeric),
-    (Some(f64_on_stack), i64_trunc_f64_u, Numeric),
+    (Some(trapping_f32_on_stack), i64_trunc_f32_s, Numeric),
+    (Some(trapping_f32_on_stack), i64_trunc_f32_u, Numeric),
+    (Some(trapping_f64_on_stack), i64_trunc_f64_s, Numeric),
+    (Some(trapping_f64_on_stack), i64_trunc_f64_u, Numeric),
     (Some(i32_on_stack), f32_convert_i32_s, Numeric),
     (Some(i32_on_stack), f32_convert_i32_u, Numeric),
     (Some(i64_on_stack), f32_convert_i64_s, Numeric),
@@ -307,6 +313,10 @@ instructions! {
     (Some(table_fill_valid), table_fill, Reference),
     (Some(table_set_valid), table_set, Reference),
     (Some(table_get_valid), table_get, Reference),
+    (Some(table_atomic_get_valid), table_atomic_get, Reference),
+    (Some(table_atomic_set_valid), table_atomic_set, Reference),
+    (Some(table_atomic_rmw_xchg_valid), table_atomic_rmw_xchg, Reference),
+    (Some(table_atomic_rmw_cmpxchg_valid), table_atomic_rmw_cmpxchg, Reference),
     (Some(table_size_valid), table_size, Reference),
     (Some(table_grow_valid), table_grow, Reference),
     (Some(table_copy_valid), table_copy, Reference),
@@ -316,6 +326,7 @@ instructions! {
     (Some(struct_new_valid), struct_new, Aggregate),
     (Some(struct_new_default_valid), struct_new_default, Aggregate),
     (Some(struct_get_valid), struct_get, Aggregate),
+    (Some(struct_get_cast_chain_valid), struct_get_cast_chain, Aggregate),
     (Some(struct_set_valid), struct_set, Aggregate),
     (Some(array_new_valid), array_new, Aggregate),
     (Some(array_new_fixed_valid), array_new_fixed, Aggregate),
@@ -333,6 +344,7 @@ instructions! {
     (Some(i31_get_valid), i31_get, Aggregate),
     (Some(any_convert_extern_valid), any_convert_extern, Aggregate),
     (Some(extern_convert_any_valid), extern_convert_any, Aggregate),
+    (Some(extern_round_trip_valid), extern_round_trip, Aggregate),
     // SIMD instructions.
     (Some(simd_have_memory_and_offset), v128_load, VectorInt),
     (Some(simd_have_memory_and_offset), v128_load8x8s, VectorInt),
@@ -588,8 +600,8 @@ instructions! {
     (Some(simd_v128_v128_on_stack_relaxed), f64x2_relaxed_min, Vector),
     (Some(simd_v128_v128_on_stack_relaxed), f64x2_relaxed_max, Vector),
     (Some(simd_v128_v128_on_stack_relaxed), i16x8_relaxed_q15mulr_s, VectorInt),
-    (Some(simd_v128_v128_on_stack_relaxed), i16x8_relaxed_dot_i8x16_i7x16_s, VectorInt),
-    (Some(simd_v128_v128_v128_on_stack_relaxed), i32x4_relaxed_dot_i8x16_i7x16_add_s, VectorInt),
+    (Some(relaxed_dot_valid), i16x8_relaxed_dot_i8x16_i7x16_s, VectorInt),
+    (Some(relaxed_dot_valid), i32x4_relaxed_dot_i8x16_i7x16_add_s, VectorInt),
     (Some(wide_arithmetic_binop128_on_stack), i64_add128, NumericInt),
     (Some(wide_arithmetic_binop128_on_stack), i64_sub128, NumericInt),
     (Some(wide_arithmetic_mul_wide_on_stack), i64_mul_wide_s, NumericInt),
@@ -682,6 +694,7 @@ pub(crate) struct CodeBuilder<'a> {
     f32_scratch: Option<usize>,
     f64_scratch: Option<usize>,
     v128_scratch: Option<usize>,
+    anyref_scratch: Option<u32>,
 }
 
 /// A control frame.
@@ -713,6 +726,9 @@ enum ControlKind {
     If,
     Loop,
     TryTable,
+    Try,
+    Catch,
+    CatchAll,
 }
 
 enum Float {
@@ -927,6 +943,7 @@ impl CodeBuilderAllocations {
             f32_scratch: None,
             f64_scratch: None,
             v128_scratch: None,
+            anyref_scratch: None,
         }
     }
 
@@ -945,11 +962,18 @@ impl CodeBuilderAllocations {
             });
             module.defined_globals.push((global_idx, init));
 
-            if self.disallow_exporting || u.ratio(1, 100).unwrap_or(false) {
+            // Always export a global if `export_all_kinds` requires it, since
+            // there may not have been any globals to export before.
+            let required = module.config.export_all_kinds
+                && !module
+                    .exports
+                    .iter()
+                    .any(|(_, kind, _)| *kind == ExportKind::Global);
+            if self.disallow_exporting || (!required && u.ratio(1, 100).unwrap_or(false)) {
                 continue;
             }
 
-            let name = unique_string(1_000, &mut module.export_names, u)?;
+            let name = module.arbitrary_export_name(u)?;
             module.add_arbitrary_export(name, ExportKind::Global, global_idx)?;
         }
         Ok(())
@@ -1241,12 +1265,35 @@ impl CodeBuilder<'_> {
         f(u)
     }
 
+    /// Sometimes choose a function type with parameters, all of which are on
+    /// the stack, as the block type of a loop as configured by
+    /// `parameterized_loop_ratio`.
+    fn arbitrary_loop_params_type(
+        &self,
+        u: &mut Unstructured,
+        module: &Module,
+    ) -> Result<Option<u32>> {
+        let ratio = module.config.parameterized_loop_ratio;
+        if ratio <= 0.0 || !module.config.multi_value_enabled {
+            return Ok(None);
+        }
+        let candidates = module
+            .func_types()
+            .filter(|(_, ty)| !ty.params.is_empty() && self.types_on_stack(module, &ty.params))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() || !arbitrary_ratio(u, ratio)? {
+            return Ok(None);
+        }
+        Ok(Some(*u.choose(&candidates)?))
+    }
+
     pub(crate) fn arbitrary(
         mut self,
         u: &mut Unstructured,
         module: &Module,
+        max_instructions: usize,
     ) -> Result<Vec<Instruction>> {
-        let max_instructions = module.config.max_instructions;
         let allowed_instructions = if module.config.allow_floats {
             module.config.allowed_instructions
         } else {
@@ -1254,9 +1301,19 @@ impl CodeBuilder<'_> {
         };
         let mut instructions = vec![];
 
+        if let Some(PathologyKind::DeepBlockNesting(depth)) = module.config.pathological {
+            for _ in 0..depth {
+                self.push_control(ControlKind::Block, [], []);
+                instructions.push(Instruction::Block(BlockType::Empty));
+            }
+        }
+
         while !self.allocs.controls.is_empty() {
             let keep_going = instructions.len() < max_instructions && u.arbitrary::<u8>()? != 0;
             if !keep_going {
+                if module.config.use_all_locals {
+                    self.use_all_locals(&mut instructions);
+                }
                 self.end_active_control_frames(
                     u,
                     module,
@@ -1275,6 +1332,9 @@ impl CodeBuilder<'_> {
                 // instructions. In this case we swallow that error and instead
                 // just terminate our wasm function's frames.
                 None => {
+                    if module.config.use_all_locals {
+                        self.use_all_locals(&mut instructions);
+                    }
                     self.end_active_control_frames(
                         u,
                         module,
@@ -1348,6 +1408,12 @@ impl CodeBuilder<'_> {
 
         self.locals.extend(self.extra_locals.drain(..));
 
+        if module.config.assert_atomic_alignment {
+            for instruction in &instructions {
+                assert_atomic_alignment(instruction);
+            }
+        }
+
         Ok(instructions)
     }
 
@@ -1413,6 +1479,40 @@ impl CodeBuilder<'_> {
         });
     }
 
+    /// Appends a `local.get` / `local.set` pair for every parameter and local
+    /// that `instructions` doesn't already both read and write.
+    ///
+    /// The pair is stack-neutral so it's valid wherever it's placed. Generated
+    /// locals are always defaultable (see `Module::arbitrary_locals`) and
+    /// parameters are always initialized, so reading a local that hasn't been
+    /// written yet is fine.
+    fn use_all_locals(&mut self, instructions: &mut Vec<Instruction>) {
+        let n = self.func_ty.params.len() + self.locals.len();
+        let mut read = vec![false; n];
+        let mut written = vec![false; n];
+        for instr in instructions.iter() {
+            let (i, is_read, is_write) = match *instr {
+                Instruction::LocalGet(i) => (i, true, false),
+                Instruction::LocalSet(i) => (i, false, true),
+                Instruction::LocalTee(i) => (i, false, true),
+                _ => continue,
+            };
+            // Scratch locals in `extra_locals` are never chosen here.
+            let Some(i) = usize::try_from(i).ok().filter(|i| *i < n) else {
+                continue;
+            };
+            read[i] |= is_read;
+            written[i] |= is_write;
+        }
+        for i in 0..n {
+            if !read[i] || !written[i] {
+                let i = u32::try_from(i).unwrap();
+                instructions.push(Instruction::LocalGet(i));
+                instructions.push(Instruction::LocalSet(i));
+            }
+        }
+    }
+
     fn alloc_local(&mut self, ty: ValType) -> u32 {
         let val = self.locals.len() + self.func_ty.params.len() + self.extra_locals.len();
         self.extra_locals.push(ty);
@@ -1628,12 +1728,48 @@ fn unreachable_valid(module: &Module, _: &mut CodeBuilder) -> bool {
 }
 
 fn unreachable(
-    _: &mut Unstructured,
-    _: &Module,
+    u: &mut Unstructured,
+    module: &Module,
     _: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
     instructions.push(Instruction::Unreachable);
+    arbitrary_dead_code(u, module, instructions)
+}
+
+/// Maybe append some dead code after an unconditional control transfer,
+/// according to `dead_code_ratio`.
+///
+/// Each snippet leaves the stack height unchanged, popping any operands it
+/// needs from the polymorphic bottom of the unreachable stack, so that the
+/// builder's view of the operand stack remains valid.
+fn arbitrary_dead_code(
+    u: &mut Unstructured,
+    module: &Module,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    if !arbitrary_ratio(u, module.config.dead_code_ratio)? {
+        return Ok(());
+    }
+    // Only use parametric and numeric instructions when they're allowed; the
+    // control instructions are fine since we're already following one.
+    let allowed = module.config.allowed_instructions;
+    let mut choices: Vec<&[Instruction]> = vec![
+        &[Instruction::Nop],
+        &[Instruction::Block(BlockType::Empty), Instruction::End],
+        &[Instruction::Unreachable],
+    ];
+    if allowed.contains(Parametric) {
+        choices.push(&[Instruction::Drop]);
+        choices.push(&[Instruction::Select, Instruction::Drop]);
+        if allowed.contains(NumericInt) {
+            choices.push(&[Instruction::I32Add, Instruction::Drop]);
+            choices.push(&[Instruction::I32Const(0), Instruction::Drop]);
+        }
+    }
+    for _ in 0..u.int_in_range(1..=3)? {
+        instructions.extend_from_slice(u.choose(&choices)?);
+    }
     Ok(())
 }
 
@@ -1662,7 +1798,7 @@ fn block(
 
 #[inline]
 fn try_table_valid(module: &Module, _: &mut CodeBuilder) -> bool {
-    module.config.exceptions_enabled
+    module.config.exceptions_enabled && !module.config.legacy_exceptions
 }
 
 fn try_table(
@@ -1740,7 +1876,12 @@ fn try_table(
     Ok(())
 }
 
-fn r#loop(
+#[inline]
+fn try_valid(module: &Module, _: &mut CodeBuilder) -> bool {
+    module.config.exceptions_enabled && module.config.legacy_exceptions
+}
+
+fn r#try(
     u: &mut Unstructured,
     module: &Module,
     builder: &mut CodeBuilder,
@@ -1748,6 +1889,109 @@ fn r#loop(
 ) -> Result<()> {
     let block_ty = builder.arbitrary_block_type(u, module)?;
     let (params, results) = module.params_results(&block_ty);
+    builder.push_control(ControlKind::Try, params, results);
+    instructions.push(Instruction::Try(block_ty));
+    Ok(())
+}
+
+#[inline]
+fn delegate_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    // `delegate` ends a `try` block the same way `end` does, it just can't
+    // follow any `catch` clauses.
+    builder.allocs.controls.last().unwrap().kind == ControlKind::Try && end_valid(module, builder)
+}
+
+fn delegate(
+    u: &mut Unstructured,
+    _: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    builder.pop_control();
+    // The label is relative to the frames outside of the `try` block, which
+    // always include at least the function's own frame. Only target the bodies
+    // of enclosing `try` blocks, where a delegated exception can be caught, or
+    // the function's frame, which rethrows it to the caller.
+    let n = builder.allocs.controls.len();
+    let targets = builder
+        .allocs
+        .controls
+        .iter()
+        .rev()
+        .enumerate()
+        .filter(|(depth, c)| c.kind == ControlKind::Try || *depth == n - 1)
+        .map(|(depth, _)| u32::try_from(depth).unwrap())
+        .collect::<Vec<_>>();
+    let label = *u.choose(&targets)?;
+    instructions.push(Instruction::Delegate(label));
+    Ok(())
+}
+
+#[inline]
+fn catch_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    legacy_catch_valid(module, builder) && !module.tags.is_empty()
+}
+
+fn catch(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let tag_idx = u.int_in_range(0..=module.tags.len() - 1)?;
+    let tag_type = &module.tags[tag_idx];
+    let control = builder.pop_control();
+    builder.pop_operands(module, &control.results);
+    builder.push_operands(&tag_type.func_type.params);
+    builder.allocs.controls.push(Control {
+        kind: ControlKind::Catch,
+        ..control
+    });
+    instructions.push(Instruction::Catch(tag_idx as u32));
+    Ok(())
+}
+
+#[inline]
+fn catch_all_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    legacy_catch_valid(module, builder)
+}
+
+fn catch_all(
+    _: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let control = builder.pop_control();
+    builder.pop_operands(module, &control.results);
+    builder.allocs.controls.push(Control {
+        kind: ControlKind::CatchAll,
+        ..control
+    });
+    instructions.push(Instruction::CatchAll);
+    Ok(())
+}
+
+/// Whether the innermost frame is a `try` block, or one of its `catch`
+/// clauses, that can be followed by another `catch` or `catch_all` clause.
+fn legacy_catch_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    let control = builder.allocs.controls.last().unwrap();
+    matches!(control.kind, ControlKind::Try | ControlKind::Catch)
+        && builder.operands().len() == control.results.len()
+        && builder.types_on_stack(module, &control.results)
+}
+
+fn r#loop(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let block_ty = match builder.arbitrary_loop_params_type(u, module)? {
+        Some(ty) => BlockType::FunctionType(ty),
+        None => builder.arbitrary_block_type(u, module)?,
+    };
+    let (params, results) = module.params_results(&block_ty);
     builder.push_control(ControlKind::Loop, params, results);
     instructions.push(Instruction::Loop(block_ty));
     Ok(())
@@ -1829,33 +2073,45 @@ fn br_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
         .any(|l| builder.label_types_on_stack(module, l))
 }
 
+/// Choose the relative depth of a label whose types are on the stack, biased
+/// toward labels other than the two innermost ones by `deep_branch_ratio`.
+fn arbitrary_branch_target(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &CodeBuilder,
+) -> Result<u32> {
+    let targets = || {
+        builder
+            .allocs
+            .controls
+            .iter()
+            .rev()
+            .enumerate()
+            .filter(|(_, l)| builder.label_types_on_stack(module, l))
+            .map(|(depth, _)| u32::try_from(depth).unwrap())
+    };
+
+    if targets().any(|depth| depth > 1) && arbitrary_ratio(u, module.config.deep_branch_ratio)? {
+        let deep = targets().filter(|depth| *depth > 1).collect::<Vec<_>>();
+        return Ok(*u.choose(&deep)?);
+    }
+
+    let n = targets().count();
+    debug_assert!(n > 0);
+    let i = u.int_in_range(0..=n - 1)?;
+    Ok(targets().nth(i).unwrap())
+}
+
 fn br(
     u: &mut Unstructured,
     module: &Module,
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let n = builder
-        .allocs
-        .controls
-        .iter()
-        .filter(|l| builder.label_types_on_stack(module, l))
-        .count();
-    debug_assert!(n > 0);
-    let i = u.int_in_range(0..=n - 1)?;
-    let (target, _) = builder
-        .allocs
-        .controls
-        .iter()
-        .rev()
-        .enumerate()
-        .filter(|(_, l)| builder.label_types_on_stack(module, l))
-        .nth(i)
-        .unwrap();
-    let target = u32::try_from(target).unwrap();
+    let target = arbitrary_branch_target(u, module, builder)?;
     builder.pop_label_types(module, target);
     instructions.push(Instruction::Br(target));
-    Ok(())
+    arbitrary_dead_code(u, module, instructions)
 }
 
 #[inline]
@@ -1881,24 +2137,7 @@ fn br_if(
 ) -> Result<()> {
     builder.pop_operands(module, &[ValType::I32]);
 
-    let n = builder
-        .allocs
-        .controls
-        .iter()
-        .filter(|l| builder.label_types_on_stack(module, l))
-        .count();
-    debug_assert!(n > 0);
-    let i = u.int_in_range(0..=n - 1)?;
-    let (target, _) = builder
-        .allocs
-        .controls
-        .iter()
-        .rev()
-        .enumerate()
-        .filter(|(_, l)| builder.label_types_on_stack(module, l))
-        .nth(i)
-        .unwrap();
-    let target = u32::try_from(target).unwrap();
+    let target = arbitrary_branch_target(u, module, builder)?;
     builder.pop_push_label_types(module, target);
     instructions.push(Instruction::BrIf(target));
     Ok(())
@@ -1966,7 +2205,7 @@ fn return_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
 }
 
 fn r#return(
-    _: &mut Unstructured,
+    u: &mut Unstructured,
     module: &Module,
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
@@ -1974,7 +2213,7 @@ fn r#return(
     let results = builder.allocs.controls[0].results.clone();
     builder.pop_operands(module, &results);
     instructions.push(Instruction::Return);
-    Ok(())
+    arbitrary_dead_code(u, module, instructions)
 }
 
 #[inline]
@@ -1992,7 +2231,7 @@ fn call(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let candidates = builder
+    let mut candidates = builder
         .allocs
         .functions
         .iter()
@@ -2000,6 +2239,12 @@ fn call(
         .flat_map(|(_, v)| v.iter().copied())
         .collect::<Vec<_>>();
     assert!(candidates.len() > 0);
+    if module.config.prefer_calling_imports {
+        let num_imported_funcs = module.num_imported_functions() as u32;
+        if candidates.iter().any(|f| *f < num_imported_funcs) {
+            candidates.retain(|f| *f < num_imported_funcs);
+        }
+    }
     let i = u.int_in_range(0..=candidates.len() - 1)?;
     let (func_idx, ty) = module.funcs().nth(candidates[i] as usize).unwrap();
     builder.pop_operands(module, &ty.params);
@@ -2017,6 +2262,9 @@ fn call_ref_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
         Some(f) => f,
         None => return false,
     };
+    // A null reference would trap, so when traps are disallowed only call
+    // references whose type proves they're non-null, such as the result of
+    // `ref.func`.
     if module.config.disallow_traps && funcref.nullable {
         return false;
     }
@@ -2033,15 +2281,21 @@ fn call_ref_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
 }
 
 fn call_ref(
-    _u: &mut Unstructured,
+    u: &mut Unstructured,
     module: &Module,
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let heap_ty = match builder.pop_operand() {
-        Some(ValType::Ref(r)) => r.heap_type,
+    let ref_ty = match builder.pop_operand() {
+        Some(ValType::Ref(r)) => r,
         _ => unreachable!(),
     };
+    // Sometimes explicitly assert that a nullable callee is non-null first,
+    // which is the same trap that `call_ref` itself would raise.
+    if ref_ty.nullable && u.arbitrary()? {
+        instructions.push(Instruction::RefAsNonNull);
+    }
+    let heap_ty = ref_ty.heap_type;
     let idx = match heap_ty {
         HeapType::Concrete(idx) => idx,
         _ => unreachable!(),
@@ -2095,7 +2349,7 @@ fn call_indirect(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let table = select_call_indirect_table(u, module, builder)?;
+    let table = select_call_indirect_table(u, module, builder, instructions)?;
 
     let choices = module
         .func_types()
@@ -2115,6 +2369,7 @@ fn select_call_indirect_table(
     u: &mut Unstructured,
     module: &Module,
     builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
 ) -> Result<u32> {
     let tables = if builder.type_on_stack(module, ValType::I32) {
         builder.pop_operands(module, &[ValType::I32]);
@@ -2123,7 +2378,43 @@ fn select_call_indirect_table(
         builder.pop_operands(module, &[ValType::I64]);
         &builder.allocs.table64_with_funcref
     };
-    Ok(*u.choose(tables)?)
+    let table = *u.choose(tables)?;
+    if arbitrary_ratio(u, module.config.trapping_indirect_call_ratio)? {
+        trapping_call_indirect_index(u, module, table, instructions)?;
+    }
+    Ok(table)
+}
+
+/// Replace the index on top of the stack with one that makes an indirect call
+/// through `table` trap.
+fn trapping_call_indirect_index(
+    u: &mut Unstructured,
+    module: &Module,
+    table: u32,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let ty = &module.tables[table as usize];
+    instructions.push(Instruction::Drop);
+    if module.config.reference_types_enabled && u.arbitrary()? {
+        // `table.grow` returns the index of the first new slot, which holds
+        // null. If growing fails it returns -1 instead, which is out of bounds.
+        instructions.push(Instruction::RefNull(HeapType::FUNC));
+        instructions.push(if ty.table64 {
+            Instruction::I64Const(1)
+        } else {
+            Instruction::I32Const(1)
+        });
+        instructions.push(Instruction::TableGrow(table));
+    } else if ty.table64 {
+        let min = ty.maximum.unwrap_or(u64::MAX);
+        instructions.push(Instruction::I64Const(u.int_in_range(min..=u64::MAX)? as i64));
+    } else {
+        let min = ty
+            .maximum
+            .map_or(u32::MAX, |max| u32::try_from(max).unwrap_or(u32::MAX));
+        instructions.push(Instruction::I32Const(u.int_in_range(min..=u32::MAX)? as i32));
+    }
+    Ok(())
 }
 
 #[inline]
@@ -2229,7 +2520,7 @@ fn return_call_indirect(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let table = select_call_indirect_table(u, module, builder)?;
+    let table = select_call_indirect_table(u, module, builder, instructions)?;
 
     let choices = module
         .func_types()
@@ -2255,7 +2546,7 @@ fn throw_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
             .allocs
             .tags
             .keys()
-            .any(|k| builder.types_on_stack(module, k))
+            .any(|k| builder.types_on_stack(module, k) || k.iter().all(|ty| ty.is_defaultable()))
 }
 
 fn throw(
@@ -2271,19 +2562,40 @@ fn throw(
         .filter(|(k, _)| builder.types_on_stack(module, k))
         .flat_map(|(_, v)| v.iter().copied())
         .collect::<Vec<_>>();
-    assert!(candidates.len() > 0);
-    let i = u.int_in_range(0..=candidates.len() - 1)?;
-    let (tag_idx, tag_type) = module.tags().nth(candidates[i] as usize).unwrap();
+
+    // Prefer throwing operands that are already on the stack, but otherwise
+    // throw a tag whose payload can be made entirely of constants.
+    let (tag_idx, tag_type) = if candidates.len() > 0 {
+        let i = u.int_in_range(0..=candidates.len() - 1)?;
+        let (tag_idx, tag_type) = module.tags().nth(candidates[i] as usize).unwrap();
+        builder.pop_operands(module, &tag_type.func_type.params);
+        (tag_idx, tag_type)
+    } else {
+        let candidates = builder
+            .allocs
+            .tags
+            .iter()
+            .filter(|(k, _)| k.iter().all(|ty| ty.is_defaultable()))
+            .flat_map(|(_, v)| v.iter().copied())
+            .collect::<Vec<_>>();
+        let i = u.int_in_range(0..=candidates.len() - 1)?;
+        let (tag_idx, tag_type) = module.tags().nth(candidates[i] as usize).unwrap();
+        for ty in tag_type.func_type.params.iter() {
+            instructions.push(module.arbitrary_const_instruction(*ty, u)?);
+        }
+        (tag_idx, tag_type)
+    };
     // Tags have no results, throwing cannot return
     assert!(tag_type.func_type.results.len() == 0);
-    builder.pop_operands(module, &tag_type.func_type.params);
     instructions.push(Instruction::Throw(tag_idx));
     Ok(())
 }
 
 #[inline]
 fn throw_ref_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
-    module.config.exceptions_enabled && builder.types_on_stack(module, &[ValType::EXNREF])
+    module.config.exceptions_enabled
+        && !module.config.legacy_exceptions
+        && builder.types_on_stack(module, &[ValType::EXNREF])
 }
 
 fn throw_ref(
@@ -2297,6 +2609,37 @@ fn throw_ref(
     Ok(())
 }
 
+#[inline]
+fn rethrow_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    // There must be an enclosing `catch` or `catch_all` clause whose caught
+    // exception can be rethrown.
+    module.config.exceptions_enabled
+        && builder
+            .allocs
+            .controls
+            .iter()
+            .any(|c| matches!(c.kind, ControlKind::Catch | ControlKind::CatchAll))
+}
+
+fn rethrow(
+    u: &mut Unstructured,
+    _: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let candidates = builder
+        .allocs
+        .controls
+        .iter()
+        .rev()
+        .enumerate()
+        .filter(|(_, c)| matches!(c.kind, ControlKind::Catch | ControlKind::CatchAll))
+        .map(|(i, _)| i as u32)
+        .collect::<Vec<_>>();
+    instructions.push(Instruction::Rethrow(*u.choose(&candidates)?));
+    Ok(())
+}
+
 #[inline]
 fn br_on_null_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
     if !module.config.gc_enabled {
@@ -2645,27 +2988,71 @@ fn drop(
 
 #[inline]
 fn select_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    // Vector instructions rarely leave an `i32` on top of two vectors, so a
+    // `v128` operand can also be selected against using a constant condition
+    // and, if necessary, a constant second operand.
+    if builder.type_on_stack(module, ValType::V128) {
+        return true;
+    }
+    // The same goes for two references, which are selected between as their
+    // least upper bound.
+    if select_ref_lub(module, builder, 0).is_some() {
+        return true;
+    }
     if !(builder.operands().len() >= 3 && builder.type_on_stack(module, ValType::I32)) {
         return false;
     }
     let t = builder.operands()[builder.operands().len() - 2];
     let u = builder.operands()[builder.operands().len() - 3];
-    t.is_none() || u.is_none() || t == u
+    t.is_none() || u.is_none() || t == u || select_ref_lub(module, builder, 1).is_some()
+}
+
+/// The least upper bound of the two reference types `depth` operands down
+/// from the top of the stack, if both are references and they have one.
+fn select_ref_lub(module: &Module, builder: &CodeBuilder, depth: usize) -> Option<RefType> {
+    let operands = builder.operands();
+    let len = operands.len().checked_sub(depth)?;
+    match operands.get(len.checked_sub(2)?..len)? {
+        [Some(ValType::Ref(t)), Some(ValType::Ref(u))] => module.ref_type_lub(*t, *u),
+        _ => None,
+    }
 }
 
 fn select(
-    _: &mut Unstructured,
-    _module: &Module,
+    u: &mut Unstructured,
+    module: &Module,
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    builder.pop_operand();
+    if builder.type_on_stack(module, ValType::V128) {
+        if !builder.types_on_stack(module, &[ValType::V128, ValType::V128]) {
+            instructions.push(Instruction::V128Const(u.arbitrary()?));
+            builder.push_operand(Some(ValType::V128));
+        }
+        instructions.push(Instruction::I32Const(u.arbitrary()?));
+    } else if builder.type_on_stack(module, ValType::I32) {
+        builder.pop_operand();
+    } else {
+        instructions.push(Instruction::I32Const(u.arbitrary()?));
+    }
     let t = builder.pop_operand();
-    let u = builder.pop_operand();
-    let ty = t.or(u);
+    let v = builder.pop_operand();
+    let ty = match (t, v) {
+        (Some(ValType::Ref(t)), Some(ValType::Ref(v))) => {
+            Some(ValType::Ref(module.ref_type_lub(t, v).unwrap()))
+        }
+        _ => t.or(v),
+    };
     builder.allocs.operands.push(ty);
     match ty {
         Some(ty @ ValType::Ref(_)) => instructions.push(Instruction::TypedSelect(ty)),
+        // Numeric and vector operands may use either form of `select`, but the
+        // annotated form is only available with reference types.
+        Some(ty @ (ValType::I32 | ValType::I64 | ValType::F32 | ValType::F64 | ValType::V128))
+            if module.config.reference_types_enabled && u.arbitrary()? =>
+        {
+            instructions.push(Instruction::TypedSelect(ty))
+        }
         Some(ValType::I32) | Some(ValType::I64) | Some(ValType::F32) | Some(ValType::F64)
         | Some(ValType::V128) | None => instructions.push(Instruction::Select),
     }
@@ -2766,8 +3153,23 @@ fn local_tee(
 }
 
 #[inline]
-fn global_get_valid(module: &Module, _: &mut CodeBuilder) -> bool {
-    module.globals.len() > 0
+fn global_get_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    global_candidates(module, builder, 0..module.globals.len() as u32)
+        .next()
+        .is_some()
+}
+
+/// Filters `globals` down to those accessible from the function being built,
+/// since shared functions may only access shared globals.
+fn global_candidates<'a>(
+    module: &'a Module,
+    builder: &CodeBuilder,
+    globals: impl IntoIterator<Item = u32> + 'a,
+) -> impl Iterator<Item = u32> + 'a {
+    let shared = builder.shared;
+    globals
+        .into_iter()
+        .filter(move |i| !shared || module.globals[*i as usize].shared)
 }
 
 fn global_get(
@@ -2776,23 +3178,25 @@ fn global_get(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    debug_assert!(module.globals.len() > 0);
-    let global_idx = u.int_in_range(0..=module.globals.len() - 1)?;
+    let candidates =
+        global_candidates(module, builder, 0..module.globals.len() as u32).collect::<Vec<_>>();
+    let global_idx = *u.choose(&candidates)?;
     builder
         .allocs
         .operands
-        .push(Some(module.globals[global_idx].val_type));
-    instructions.push(Instruction::GlobalGet(global_idx as u32));
+        .push(Some(module.globals[global_idx as usize].val_type));
+    instructions.push(Instruction::GlobalGet(global_idx));
     Ok(())
 }
 
 #[inline]
 fn global_set_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
-    builder
-        .allocs
-        .mutable_globals
-        .iter()
-        .any(|(ty, _)| builder.type_on_stack(module, *ty))
+    builder.allocs.mutable_globals.iter().any(|(ty, globals)| {
+        builder.type_on_stack(module, *ty)
+            && global_candidates(module, builder, globals.iter().copied())
+                .next()
+                .is_some()
+    })
 }
 
 fn global_set(
@@ -2805,12 +3209,15 @@ fn global_set(
         .allocs
         .mutable_globals
         .iter()
-        .find(|(ty, _)| builder.type_on_stack(module, **ty))
-        .unwrap()
-        .1;
-    let i = u.int_in_range(0..=candidates.len() - 1)?;
+        .filter(|(ty, _)| builder.type_on_stack(module, **ty))
+        .map(|(_, globals)| {
+            global_candidates(module, builder, globals.iter().copied()).collect::<Vec<_>>()
+        })
+        .find(|candidates| !candidates.is_empty())
+        .unwrap();
+    let global_idx = *u.choose(&candidates)?;
     builder.allocs.operands.pop();
-    instructions.push(Instruction::GlobalSet(candidates[i]));
+    instructions.push(Instruction::GlobalSet(global_idx));
     Ok(())
 }
 
@@ -3335,17 +3742,64 @@ fn memory_grow(
         ValType::I64
     };
     let index = memory_index(u, builder, ty)?;
+
+    // Sometimes replace the delta on the stack with one that grows the memory
+    // right up to (or just past) its declared maximum.
+    if u.ratio(1, 4)? {
+        builder.drop_operand(u, Some(ty), instructions)?;
+        let delta = arbitrary_grow_delta(u, module, index)?;
+        instructions.push(if ty == ValType::I32 {
+            Instruction::I32Const(delta as i32)
+        } else {
+            Instruction::I64Const(delta as i64)
+        });
+    }
+
     builder.pop_operands(module, &[ty]);
     builder.push_operands(&[ty]);
     instructions.push(Instruction::MemoryGrow(index));
     Ok(())
 }
 
+/// Pick a `memory.grow` delta, in pages, that is near the boundary of the
+/// given memory's maximum size.
+fn arbitrary_grow_delta(u: &mut Unstructured, module: &Module, index: u32) -> Result<u64> {
+    // Without a declared maximum, favor deltas that are large but not so large
+    // that they would always fail.
+    const MAX_UNBOUNDED_DELTA: u64 = 1 << 10;
+
+    let mem = &module.memories[index as usize];
+    let Some(maximum) = mem.maximum else {
+        return u.int_in_range(1..=MAX_UNBOUNDED_DELTA);
+    };
+    let exact = maximum.saturating_sub(mem.minimum);
+    Ok(match u.int_in_range(0..=3)? {
+        0 => exact,
+        1 => exact.saturating_sub(1),
+        2 => exact.saturating_add(1),
+        _ => {
+            if mem.memory64 {
+                *u.choose(&module.interesting_values64)?
+            } else {
+                u64::from(*u.choose(&module.interesting_values32)?)
+            }
+        }
+    })
+}
+
 #[inline]
 fn memory_init_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
-    module.config.bulk_memory_enabled
-        && have_data(module, builder)
-        && !module.config.disallow_traps // Non-trapping memory init not yet implemented
+    if !module.config.bulk_memory_enabled || !have_data(module, builder) {
+        return false;
+    }
+
+    // Ranged inits push their own in-bounds operands, so they're valid
+    // regardless of what is on the stack.
+    if module.config.partial_memory_init_ratio > 0.0 && !memory_init_ranges(module).is_empty() {
+        return true;
+    }
+
+    !module.config.disallow_traps // Non-trapping memory init not yet implemented
         && (builder.allocs.memory32.len() > 0
             && builder.types_on_stack(module, &[ValType::I32, ValType::I32, ValType::I32])
             || (builder.allocs.memory64.len() > 0
@@ -3358,6 +3812,20 @@ fn memory_init(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
+    let ranges = memory_init_ranges(module);
+    if !ranges.is_empty() && module.config.partial_memory_init_ratio > 0.0 {
+        let operands_on_stack = !module.config.disallow_traps
+            && (builder.allocs.memory32.len() > 0
+                && builder.types_on_stack(module, &[ValType::I32, ValType::I32, ValType::I32])
+                || (builder.allocs.memory64.len() > 0
+                    && builder
+                        .types_on_stack(module, &[ValType::I64, ValType::I32, ValType::I32])));
+        if !operands_on_stack || arbitrary_ratio(u, module.config.partial_memory_init_ratio)? {
+            let (mem, data_index) = *u.choose(&ranges)?;
+            return ranged_memory_init(u, module, instructions, mem, data_index);
+        }
+    }
+
     builder.pop_operands(module, &[ValType::I32, ValType::I32]);
     let ty = if builder.type_on_stack(module, ValType::I32) {
         ValType::I32
@@ -3371,6 +3839,78 @@ fn memory_init(
     Ok(())
 }
 
+/// Returns the pairs of memory and passive data segment for which a ranged
+/// `memory.init` can be generated: the memory must be non-empty at its
+/// minimum size, and the segment non-empty.
+///
+/// When traps are disallowed, the passive segment which is dropped by
+/// `exercise_memory_lifecycle` is excluded.
+fn memory_init_ranges(module: &Module) -> Vec<(u32, u32)> {
+    let mut passive = module
+        .data
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| matches!(d.kind, DataSegmentKind::Passive))
+        .map(|(i, d)| (u32::try_from(i).unwrap(), d.init.len()));
+    if module.config.disallow_traps && module.exercise_memory_lifecycle() {
+        passive.next();
+    }
+    let segments = passive
+        .filter(|(_, len)| *len > 0)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    let mut ranges = Vec::new();
+    for (mem, memory) in module.memories.iter().enumerate() {
+        if memory_min_bytes(memory) == 0 {
+            continue;
+        }
+        let mem = u32::try_from(mem).unwrap();
+        ranges.extend(segments.iter().map(|&data_index| (mem, data_index)));
+    }
+    ranges
+}
+
+/// Generates a `memory.init` from the passive segment `data_index` into `mem`
+/// whose constant operands describe a full or partial range of the segment
+/// that is in bounds of both the segment and the memory's minimum size.
+fn ranged_memory_init(
+    u: &mut Unstructured,
+    module: &Module,
+    instructions: &mut Vec<Instruction>,
+    mem: u32,
+    data_index: u32,
+) -> Result<()> {
+    let memory = &module.memories[mem as usize];
+    let mem_size = memory_min_bytes(memory);
+    let segment_len = module.data[data_index as usize].init.len() as u64;
+
+    // The whole segment can only be copied if it fits in the memory, and
+    // otherwise pick a range which starts after the segment's beginning or
+    // ends before its end.
+    let (src, len) = if segment_len <= mem_size && u.arbitrary()? {
+        (0, segment_len)
+    } else {
+        let src = u.int_in_range(0..=segment_len - 1)?;
+        let mut len = u.int_in_range(0..=(segment_len - src).min(mem_size))?;
+        if src == 0 && len == segment_len {
+            len -= 1;
+        }
+        (src, len)
+    };
+    let dst = u.int_in_range(0..=mem_size - len)?;
+
+    instructions.push(if memory.memory64 {
+        Instruction::I64Const(dst as i64)
+    } else {
+        Instruction::I32Const(dst as i32)
+    });
+    instructions.push(Instruction::I32Const(src as i32));
+    instructions.push(Instruction::I32Const(len as i32));
+    instructions.push(Instruction::MemoryInit { mem, data_index });
+    Ok(())
+}
+
 #[inline]
 fn memory_fill_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
     module.config.bulk_memory_enabled
@@ -3404,12 +3944,22 @@ fn memory_copy_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
         return false;
     }
 
-    // The non-trapping case for memory copy has not yet been implemented,
-    // so we are excluding them for now
+    // Overlapping copies push their own in-bounds operands, so they're valid
+    // regardless of what is on the stack.
+    if module.config.overlapping_copy_ratio > 0.0 && !overlapping_copy_memories(module).is_empty() {
+        return true;
+    }
+
+    // The non-trapping case for memory copy has only been implemented for
+    // overlapping copies, so we are excluding other copies for now
     if module.config.disallow_traps {
         return false;
     }
 
+    memory_copy_operands_on_stack(module, builder)
+}
+
+fn memory_copy_operands_on_stack(module: &Module, builder: &mut CodeBuilder) -> bool {
     let n32 = builder.allocs.memory32.len();
     let n64 = builder.allocs.memory64.len();
 
@@ -3441,6 +3991,17 @@ fn memory_copy(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
+    let mems = overlapping_copy_memories(module);
+    if !mems.is_empty() && module.config.overlapping_copy_ratio > 0.0 {
+        if module.config.disallow_traps
+            || !memory_copy_operands_on_stack(module, builder)
+            || arbitrary_ratio(u, module.config.overlapping_copy_ratio)?
+        {
+            let mem = *u.choose(&mems)?;
+            return overlapping_memory_copy(u, module, instructions, mem);
+        }
+    }
+
     let (src, dst) = gen_copy_src_and_dst(module, builder);
     let src_mem = src.choose(u, &builder.allocs.memory32, &builder.allocs.memory64)?;
     let dst_mem = dst.choose(u, &builder.allocs.memory32, &builder.allocs.memory64)?;
@@ -3448,6 +4009,62 @@ fn memory_copy(
     Ok(())
 }
 
+/// Returns the memories that are large enough, at their minimum size, for an
+/// overlapping `memory.copy`.
+fn overlapping_copy_memories(module: &Module) -> Vec<u32> {
+    module
+        .memories
+        .iter()
+        .enumerate()
+        .filter(|(_, mem)| memory_min_bytes(mem) >= 2)
+        .map(|(i, _)| u32::try_from(i).unwrap())
+        .collect()
+}
+
+fn memory_min_bytes(mem: &MemoryType) -> u64 {
+    let page_size = 1u64 << mem.page_size_log2.unwrap_or(16);
+    mem.minimum.saturating_mul(page_size)
+}
+
+/// Generates a `memory.copy` within `mem` whose constant operands describe
+/// overlapping source and destination ranges that are in bounds of the
+/// memory's minimum size.
+fn overlapping_memory_copy(
+    u: &mut Unstructured,
+    module: &Module,
+    instructions: &mut Vec<Instruction>,
+    mem: u32,
+) -> Result<()> {
+    let memory = &module.memories[mem as usize];
+
+    // Pick a length, the distance between the two ranges (less than the
+    // length so that they overlap), and then where the lower of the two
+    // ranges starts such that both fit within the memory's minimum size.
+    let size = memory_min_bytes(memory);
+    let len = u.int_in_range(1..=size.min(4096))?;
+    let distance = u.int_in_range(0..=(len - 1).min(size - len))?;
+    let low = u.int_in_range(0..=size - len - distance)?;
+    let high = low + distance;
+    let (dst, src) = if u.arbitrary()? {
+        (low, high)
+    } else {
+        (high, low)
+    };
+
+    for value in [dst, src, len] {
+        instructions.push(if memory.memory64 {
+            Instruction::I64Const(value as i64)
+        } else {
+            Instruction::I32Const(value as i32)
+        });
+    }
+    instructions.push(Instruction::MemoryCopy {
+        dst_mem: mem,
+        src_mem: mem,
+    });
+    Ok(())
+}
+
 enum CopyIndexSize {
     I32,
     I64,
@@ -3485,7 +4102,10 @@ fn gen_copy_src_and_dst(
 
 #[inline]
 fn data_drop_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
-    have_data(module, builder) && module.config.bulk_memory_enabled
+    have_data(module, builder)
+        && module.config.bulk_memory_enabled
+        // Ranged `memory.init`s mustn't trap on a dropped segment.
+        && !(module.config.disallow_traps && module.config.partial_memory_init_ratio > 0.0)
 }
 
 fn data_drop(
@@ -3498,6 +4118,23 @@ fn data_drop(
     Ok(())
 }
 
+#[inline]
+fn atomic_fence_valid(module: &Module, _: &mut CodeBuilder) -> bool {
+    // Unlike the other atomic instructions, `atomic.fence` doesn't operate on
+    // a memory so it's valid even without a (shared) memory.
+    module.config.threads_enabled && module.config.allow_atomics
+}
+
+fn atomic_fence(
+    _: &mut Unstructured,
+    _: &Module,
+    _: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    instructions.push(Instruction::AtomicFence);
+    Ok(())
+}
+
 fn i32_const(
     u: &mut Unstructured,
     module: &Module,
@@ -4804,6 +5441,16 @@ fn nontrapping_f32_on_stack(module: &Module, builder: &mut CodeBuilder) -> bool
     module.config.saturating_float_to_int_enabled && f32_on_stack(module, builder)
 }
 
+/// Whether a trapping float-to-int conversion may be generated for the `f32`
+/// on the stack.
+///
+/// When traps are disallowed the trapping conversions need a lengthy guard
+/// sequence, so prefer the saturating conversions whenever they're available.
+fn trapping_f32_on_stack(module: &Module, builder: &mut CodeBuilder) -> bool {
+    !(module.config.disallow_traps && module.config.saturating_float_to_int_enabled)
+        && f32_on_stack(module, builder)
+}
+
 fn i32_trunc_f32_s(
     _: &mut Unstructured,
     module: &Module,
@@ -4840,6 +5487,12 @@ fn nontrapping_f64_on_stack(module: &Module, builder: &mut CodeBuilder) -> bool
     module.config.saturating_float_to_int_enabled && f64_on_stack(module, builder)
 }
 
+/// Same as `trapping_f32_on_stack` but for `f64`.
+fn trapping_f64_on_stack(module: &Module, builder: &mut CodeBuilder) -> bool {
+    !(module.config.disallow_traps && module.config.saturating_float_to_int_enabled)
+        && f64_on_stack(module, builder)
+}
+
 fn i32_trunc_f64_s(
     _: &mut Unstructured,
     module: &Module,
@@ -5333,6 +5986,12 @@ fn memory_offset(u: &mut Unstructured, module: &Module, memory_index: u32) -> Re
             (min.min(no_trap_max), no_trap_max, no_trap_max)
         }
     };
+    let (min, max, true_max) = if memory_type.memory64 && module.config.clamp_offsets_to_32bit {
+        let limit = u64::from(u32::MAX);
+        (min.min(limit), max.min(limit), true_max.min(limit))
+    } else {
+        (min, max, true_max)
+    };
 
     let choice = u.int_in_range(0..=a + b + c - 1)?;
     if choice < a {
@@ -5366,6 +6025,85 @@ fn mem_arg(
     })
 }
 
+/// Panics if `instruction` is an atomic memory access whose alignment isn't
+/// its natural alignment, which atomics require.
+fn assert_atomic_alignment(instruction: &Instruction) {
+    use wasm_encoder::Instruction::*;
+    let (natural, memarg) = match instruction {
+        I32AtomicLoad8U(memarg)
+        | I64AtomicLoad8U(memarg)
+        | I32AtomicStore8(memarg)
+        | I64AtomicStore8(memarg)
+        | I32AtomicRmw8AddU(memarg)
+        | I64AtomicRmw8AddU(memarg)
+        | I32AtomicRmw8SubU(memarg)
+        | I64AtomicRmw8SubU(memarg)
+        | I32AtomicRmw8AndU(memarg)
+        | I64AtomicRmw8AndU(memarg)
+        | I32AtomicRmw8OrU(memarg)
+        | I64AtomicRmw8OrU(memarg)
+        | I32AtomicRmw8XorU(memarg)
+        | I64AtomicRmw8XorU(memarg)
+        | I32AtomicRmw8XchgU(memarg)
+        | I64AtomicRmw8XchgU(memarg)
+        | I32AtomicRmw8CmpxchgU(memarg)
+        | I64AtomicRmw8CmpxchgU(memarg) => (0, memarg),
+        I32AtomicLoad16U(memarg)
+        | I64AtomicLoad16U(memarg)
+        | I32AtomicStore16(memarg)
+        | I64AtomicStore16(memarg)
+        | I32AtomicRmw16AddU(memarg)
+        | I64AtomicRmw16AddU(memarg)
+        | I32AtomicRmw16SubU(memarg)
+        | I64AtomicRmw16SubU(memarg)
+        | I32AtomicRmw16AndU(memarg)
+        | I64AtomicRmw16AndU(memarg)
+        | I32AtomicRmw16OrU(memarg)
+        | I64AtomicRmw16OrU(memarg)
+        | I32AtomicRmw16XorU(memarg)
+        | I64AtomicRmw16XorU(memarg)
+        | I32AtomicRmw16XchgU(memarg)
+        | I64AtomicRmw16XchgU(memarg)
+        | I32AtomicRmw16CmpxchgU(memarg)
+        | I64AtomicRmw16CmpxchgU(memarg) => (1, memarg),
+        MemoryAtomicNotify(memarg)
+        | MemoryAtomicWait32(memarg)
+        | I32AtomicLoad(memarg)
+        | I64AtomicLoad32U(memarg)
+        | I32AtomicStore(memarg)
+        | I64AtomicStore32(memarg)
+        | I32AtomicRmwAdd(memarg)
+        | I64AtomicRmw32AddU(memarg)
+        | I32AtomicRmwSub(memarg)
+        | I64AtomicRmw32SubU(memarg)
+        | I32AtomicRmwAnd(memarg)
+        | I64AtomicRmw32AndU(memarg)
+        | I32AtomicRmwOr(memarg)
+        | I64AtomicRmw32OrU(memarg)
+        | I32AtomicRmwXor(memarg)
+        | I64AtomicRmw32XorU(memarg)
+        | I32AtomicRmwXchg(memarg)
+        | I64AtomicRmw32XchgU(memarg)
+        | I32AtomicRmwCmpxchg(memarg)
+        | I64AtomicRmw32CmpxchgU(memarg) => (2, memarg),
+        MemoryAtomicWait64(memarg)
+        | I64AtomicLoad(memarg)
+        | I64AtomicStore(memarg)
+        | I64AtomicRmwAdd(memarg)
+        | I64AtomicRmwSub(memarg)
+        | I64AtomicRmwAnd(memarg)
+        | I64AtomicRmwOr(memarg)
+        | I64AtomicRmwXor(memarg)
+        | I64AtomicRmwXchg(memarg)
+        | I64AtomicRmwCmpxchg(memarg) => (3, memarg),
+        _ => return,
+    };
+    assert_eq!(
+        memarg.align, natural,
+        "misaligned atomic instruction: {instruction:?}"
+    );
+}
+
 fn memory_index(u: &mut Unstructured, builder: &CodeBuilder, ty: ValType) -> Result<u32> {
     if ty == ValType::I32 {
         Ok(*u.choose(&builder.allocs.memory32)?)
@@ -5395,38 +6133,56 @@ fn ref_null(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let mut choices = vec![RefType::EXTERNREF, RefType::FUNCREF];
-    if module.config.exceptions_enabled {
-        choices.push(RefType::EXNREF);
+    // Choose between abstract and concrete heap types evenly, rather than
+    // uniformly over all heap types, so that every enabled abstract heap type
+    // still shows up regularly in modules with many types.
+    let heap_type = if module.config.gc_enabled && !module.types.is_empty() && u.arbitrary()? {
+        HeapType::Concrete(u.int_in_range(0..=u32::try_from(module.types.len()).unwrap() - 1)?)
+    } else {
+        *u.choose(&ref_null_abstract_heap_types(module))?
+    };
+    builder.push_operand(Some(
+        RefType {
+            nullable: true,
+            heap_type,
+        }
+        .into(),
+    ));
+    instructions.push(Instruction::RefNull(heap_type));
+    Ok(())
+}
+
+/// Returns the abstract heap types that `ref.null` may be generated for with
+/// the module's configuration.
+fn ref_null_abstract_heap_types(module: &Module) -> Vec<HeapType> {
+    use AbstractHeapType::*;
+    let mut tys = vec![Extern, Func];
+    if module.config.exceptions_enabled && !module.config.legacy_exceptions {
+        tys.push(Exn);
+        if module.config.gc_enabled {
+            tys.push(NoExn);
+        }
     }
+    let mut choices = tys
+        .iter()
+        .map(|&ty| HeapType::Abstract { shared: false, ty })
+        .collect::<Vec<_>>();
     if module.config.gc_enabled {
-        use AbstractHeapType::*;
         let abs_ref_types = [Any, Eq, Array, Struct, I31, None, NoFunc, NoExtern];
         choices.extend(
             abs_ref_types
                 .iter()
-                .map(|&ty| RefType::new_abstract(ty, true, false)),
+                .map(|&ty| HeapType::Abstract { shared: false, ty }),
         );
         if module.config().shared_everything_threads_enabled {
             choices.extend(
                 abs_ref_types
                     .iter()
-                    .map(|&ty| RefType::new_abstract(ty, true, true)),
+                    .map(|&ty| HeapType::Abstract { shared: true, ty }),
             );
         }
-
-        for i in 0..module.types.len() {
-            let i = u32::try_from(i).unwrap();
-            choices.push(RefType {
-                nullable: true,
-                heap_type: HeapType::Concrete(i),
-            });
-        }
     }
-    let ty = *u.choose(&choices)?;
-    builder.push_operand(Some(ty.into()));
-    instructions.push(Instruction::RefNull(ty.heap_type));
-    Ok(())
+    choices
 }
 
 #[inline]
@@ -5456,7 +6212,17 @@ fn ref_func(
 
 #[inline]
 fn ref_as_non_null_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
-    module.config.gc_enabled && builder.ref_type_on_stack().is_some()
+    if !module.config.gc_enabled {
+        return false;
+    }
+    match builder.ref_type_on_stack() {
+        None => false,
+        // `ref.as_non_null` traps on null, so when traps are disallowed it's
+        // only generated for references already known to be non-null.
+        Some(Some(r)) if module.config.disallow_traps => !r.nullable,
+        Some(None) if module.config.disallow_traps => false,
+        Some(_) => true,
+    }
 }
 
 fn ref_as_non_null(
@@ -5469,6 +6235,9 @@ fn ref_as_non_null(
         Some(r) => r,
         None => module.arbitrary_ref_type(u)?,
     };
+    // Push the non-nullable type so that following instructions which require
+    // a non-null reference, like `call_ref` when traps are disallowed or
+    // `local.set` of a non-nullable local, can consume it.
     builder.push_operand(Some(ValType::Ref(RefType {
         nullable: false,
         heap_type: ref_ty.heap_type,
@@ -5621,7 +6390,8 @@ fn table_fill(
 fn table_set_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
     module.config.reference_types_enabled
     && !module.config.disallow_traps // Non-trapping table.set generation not yet implemented
-    && table_set_candidates(module, builder).next().is_some()
+    && (table_set_candidates(module, builder).next().is_some()
+        || table_set_aggregate_candidates(module, builder).next().is_some())
 }
 
 fn table_set_candidates<'a>(
@@ -5638,15 +6408,61 @@ fn table_set_candidates<'a>(
         .map(|(i, _)| i as u32)
 }
 
+/// Tables of references to struct or array types whose index is on top of the
+/// stack and for which `table.set` can materialize a value itself, since such
+/// values are rarely already on the stack.
+fn table_set_aggregate_candidates<'a>(
+    module: &'a Module,
+    builder: &'a CodeBuilder,
+) -> impl Iterator<Item = u32> + 'a {
+    module
+        .tables
+        .iter()
+        .enumerate()
+        .filter(move |(_, t)| {
+            let HeapType::Concrete(i) = t.element_type.heap_type else {
+                return false;
+            };
+            module.config.gc_enabled
+                && (module.struct_types.contains(&i) || module.array_types.contains(&i))
+                && builder.type_on_stack(module, t.index_type())
+                && field_value_available(module, StorageType::Val(t.element_type.into()))
+        })
+        .map(|(i, _)| i as u32)
+}
+
 fn table_set(
     u: &mut Unstructured,
     module: &Module,
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let table = *u.choose(&table_set_candidates(module, builder).collect::<Vec<_>>())?;
+    let candidates = table_set_candidates(module, builder)
+        .map(|i| (i, false))
+        .chain(table_set_aggregate_candidates(module, builder).map(|i| (i, true)))
+        .collect::<Vec<_>>();
+    let (table, materialize) = *u.choose(&candidates)?;
     let ty = &module.tables[table as usize];
-    builder.pop_operands(module, &[ty.index_type(), ty.element_type.into()]);
+    if materialize {
+        // Allocate a value of a subtype of the element type when possible,
+        // rather than only ever storing nulls.
+        builder.pop_operands(module, &[ty.index_type()]);
+        let non_null = RefType {
+            nullable: false,
+            ..ty.element_type
+        };
+        match non_null_ref_value(module, non_null) {
+            Some(value) => instructions.extend(value),
+            None => arbitrary_field_value(
+                u,
+                module,
+                StorageType::Val(ty.element_type.into()),
+                instructions,
+            )?,
+        }
+    } else {
+        builder.pop_operands(module, &[ty.index_type(), ty.element_type.into()]);
+    }
     instructions.push(Instruction::TableSet(table));
     Ok(())
 }
@@ -5689,6 +6505,159 @@ fn table_get(
     Ok(())
 }
 
+/// Returns the indices of shared tables whose element type is a subtype of
+/// `(ref null (shared <supertype>))` and whose index followed by `elements`
+/// element-typed operands is on the stack, as the `table.atomic.*`
+/// instructions require.
+fn table_atomic_candidates<'a>(
+    module: &'a Module,
+    builder: &'a CodeBuilder,
+    supertype: AbstractHeapType,
+    elements: usize,
+) -> impl Iterator<Item = u32> + 'a {
+    let supertype = RefType {
+        nullable: true,
+        heap_type: HeapType::Abstract {
+            shared: true,
+            ty: supertype,
+        },
+    };
+    module
+        .tables
+        .iter()
+        .enumerate()
+        .filter(move |(_, t)| {
+            if !t.shared || !module.ref_type_is_sub_type(t.element_type, supertype) {
+                return false;
+            }
+            let mut operands = vec![t.index_type()];
+            operands.extend(std::iter::repeat(ValType::Ref(t.element_type)).take(elements));
+            builder.types_on_stack(module, &operands)
+        })
+        .map(|(i, _)| i as u32)
+}
+
+fn arbitrary_ordering(u: &mut Unstructured) -> Result<Ordering> {
+    Ok(*u.choose(&[Ordering::SeqCst, Ordering::AcqRel])?)
+}
+
+#[inline]
+fn table_atomic_valid(
+    module: &Module,
+    builder: &CodeBuilder,
+    supertype: AbstractHeapType,
+    elements: usize,
+) -> bool {
+    module.config.shared_everything_threads_enabled
+        && module.config.allow_atomics
+        // Non-trapping table accesses are not yet implemented
+        && !module.config.disallow_traps
+        && table_atomic_candidates(module, builder, supertype, elements)
+            .next()
+            .is_some()
+}
+
+/// Chooses a table for a `table.atomic.*` instruction, pops its operands, and
+/// pushes `results` element-typed results.
+fn table_atomic(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    supertype: AbstractHeapType,
+    elements: usize,
+    results: usize,
+) -> Result<u32> {
+    let candidates =
+        table_atomic_candidates(module, builder, supertype, elements).collect::<Vec<_>>();
+    let table = *u.choose(&candidates)?;
+    let ty = &module.tables[table as usize];
+    let mut operands = vec![ty.index_type()];
+    operands.extend(std::iter::repeat(ValType::Ref(ty.element_type)).take(elements));
+    builder.pop_operands(module, &operands);
+    builder.push_operands(&vec![ValType::Ref(ty.element_type); results]);
+    Ok(table)
+}
+
+#[inline]
+fn table_atomic_get_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    table_atomic_valid(module, builder, AbstractHeapType::Any, 0)
+}
+
+fn table_atomic_get(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let table_index = table_atomic(u, module, builder, AbstractHeapType::Any, 0, 1)?;
+    let ordering = arbitrary_ordering(u)?;
+    instructions.push(Instruction::TableAtomicGet {
+        ordering,
+        table_index,
+    });
+    Ok(())
+}
+
+#[inline]
+fn table_atomic_set_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    table_atomic_valid(module, builder, AbstractHeapType::Any, 1)
+}
+
+fn table_atomic_set(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let table_index = table_atomic(u, module, builder, AbstractHeapType::Any, 1, 0)?;
+    let ordering = arbitrary_ordering(u)?;
+    instructions.push(Instruction::TableAtomicSet {
+        ordering,
+        table_index,
+    });
+    Ok(())
+}
+
+#[inline]
+fn table_atomic_rmw_xchg_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    table_atomic_valid(module, builder, AbstractHeapType::Any, 1)
+}
+
+fn table_atomic_rmw_xchg(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let table_index = table_atomic(u, module, builder, AbstractHeapType::Any, 1, 1)?;
+    let ordering = arbitrary_ordering(u)?;
+    instructions.push(Instruction::TableAtomicRmwXchg {
+        ordering,
+        table_index,
+    });
+    Ok(())
+}
+
+#[inline]
+fn table_atomic_rmw_cmpxchg_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    table_atomic_valid(module, builder, AbstractHeapType::Eq, 2)
+}
+
+fn table_atomic_rmw_cmpxchg(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let table_index = table_atomic(u, module, builder, AbstractHeapType::Eq, 2, 1)?;
+    let ordering = arbitrary_ordering(u)?;
+    instructions.push(Instruction::TableAtomicRmwCmpxchg {
+        ordering,
+        table_index,
+    });
+    Ok(())
+}
+
 #[inline]
 fn table_size_valid(module: &Module, _: &mut CodeBuilder) -> bool {
     module.config.reference_types_enabled && module.tables.len() > 0
@@ -5885,17 +6854,116 @@ fn struct_new(
     Ok(())
 }
 
+/// Whether a value of storage type `ty` can be materialized for a
+/// `struct.new` or `array.new` without relying on the operand stack.
+///
+/// Defaultable types always can be, and non-nullable references can be if
+/// there's a way to allocate a value of a subtype, see
+/// `non_null_ref_value`.
+fn field_value_available(module: &Module, ty: StorageType) -> bool {
+    match ty.unpack() {
+        ValType::Ref(r) if !r.nullable => non_null_ref_value(module, r).is_some(),
+        _ => true,
+    }
+}
+
+/// Push the instructions to materialize a value of storage type `ty`, which
+/// must satisfy `field_value_available`.
+fn arbitrary_field_value(
+    u: &mut Unstructured,
+    module: &Module,
+    ty: StorageType,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    match ty.unpack() {
+        ValType::Ref(r) if !r.nullable => {
+            instructions.extend(non_null_ref_value(module, r).unwrap());
+        }
+        ty => instructions.push(module.arbitrary_const_instruction(ty, u)?),
+    }
+    Ok(())
+}
+
+/// The instructions to allocate a value of the non-nullable reference type
+/// `ty`, if any.
+///
+/// These allocate either a defaultable struct or array whose type is a
+/// subtype of `ty`, or an `i31`.
+fn non_null_ref_value(module: &Module, ty: RefType) -> Option<Vec<Instruction>> {
+    let is_sub_type = |i| module.heap_type_is_sub_type(HeapType::Concrete(i), ty.heap_type);
+    if let Some(i) = module
+        .struct_types
+        .iter()
+        .copied()
+        .find(|&i| is_sub_type(i) && is_defaultable_struct(module, i))
+    {
+        return Some(vec![Instruction::StructNewDefault(i)]);
+    }
+    if let Some(i) =
+        module.array_types.iter().copied().find(|&i| {
+            is_sub_type(i) && module.ty(i).unwrap_array().0.element_type.is_defaultable()
+        })
+    {
+        return Some(vec![
+            Instruction::I32Const(0),
+            Instruction::ArrayNewDefault(i),
+        ]);
+    }
+    let i31 = HeapType::Abstract {
+        shared: false,
+        ty: AbstractHeapType::I31,
+    };
+    if module.heap_type_is_sub_type(i31, ty.heap_type) {
+        return Some(vec![Instruction::I32Const(0), Instruction::RefI31]);
+    }
+    None
+}
+
+/// The instructions to produce a zero, null, or freshly allocated value for
+/// each of `results` without consuming any input or trapping, if there's a
+/// way to do so for every type.
+pub(crate) fn const_results(module: &Module, results: &[ValType]) -> Option<Vec<Instruction>> {
+    let mut instructions = Vec::new();
+    for ty in results {
+        match *ty {
+            ValType::I32 => instructions.push(Instruction::I32Const(0)),
+            ValType::I64 => instructions.push(Instruction::I64Const(0)),
+            ValType::F32 => instructions.push(Instruction::F32Const(0.0.into())),
+            ValType::F64 => instructions.push(Instruction::F64Const(0.0.into())),
+            ValType::V128 => instructions.push(Instruction::V128Const(0)),
+            ValType::Ref(r) if r.nullable => instructions.push(Instruction::RefNull(r.heap_type)),
+            ValType::Ref(r) => instructions.extend(non_null_ref_value(module, r)?),
+        }
+    }
+    Some(instructions)
+}
+
+fn is_defaultable_struct(module: &Module, ty: u32) -> bool {
+    module
+        .ty(ty)
+        .unwrap_struct()
+        .fields
+        .iter()
+        .all(|f| f.element_type.is_defaultable())
+}
+
+/// Struct types which can be allocated without any operands: either with
+/// `struct.new_default` if all their fields are defaultable, or otherwise with
+/// `struct.new` and explicit field values.
+fn struct_new_default_candidates(module: &Module) -> impl Iterator<Item = u32> + '_ {
+    module.struct_types.iter().copied().filter(|&i| {
+        module
+            .ty(i)
+            .unwrap_struct()
+            .fields
+            .iter()
+            .all(|f| field_value_available(module, f.element_type))
+    })
+}
+
 #[inline]
 fn struct_new_default_valid(module: &Module, _builder: &mut CodeBuilder) -> bool {
-    module.config.gc_enabled
-        && module.struct_types.iter().copied().any(|i| {
-            module
-                .ty(i)
-                .unwrap_struct()
-                .fields
-                .iter()
-                .all(|f| f.element_type.is_defaultable())
-        })
+    module.config.gc_enabled && struct_new_default_candidates(module).next().is_some()
 }
 
 fn struct_new_default(
@@ -5904,41 +6972,26 @@ fn struct_new_default(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let n = module
-        .struct_types
-        .iter()
-        .filter(|i| {
-            module
-                .ty(**i)
-                .unwrap_struct()
-                .fields
-                .iter()
-                .all(|f| f.element_type.is_defaultable())
-        })
-        .count();
+    let n = struct_new_default_candidates(module).count();
     debug_assert!(n > 0);
     let i = u.int_in_range(0..=n - 1)?;
-    let ty = module
-        .struct_types
-        .iter()
-        .copied()
-        .filter(|i| {
-            module
-                .ty(*i)
-                .unwrap_struct()
-                .fields
-                .iter()
-                .all(|f| f.element_type.is_defaultable())
-        })
-        .nth(i)
-        .unwrap();
+    let ty = struct_new_default_candidates(module).nth(i).unwrap();
 
     builder.push_operand(Some(ValType::Ref(RefType {
         nullable: false,
         heap_type: HeapType::Concrete(ty),
     })));
 
-    instructions.push(Instruction::StructNewDefault(ty));
+    // `struct.new_default` is only valid when every field is defaultable, so
+    // supply explicit values for the others.
+    if is_defaultable_struct(module, ty) {
+        instructions.push(Instruction::StructNewDefault(ty));
+    } else {
+        for field in module.ty(ty).unwrap_struct().fields.iter() {
+            arbitrary_field_value(u, module, field.element_type, instructions)?;
+        }
+        instructions.push(Instruction::StructNew(ty));
+    }
     Ok(())
 }
 
@@ -5956,6 +7009,18 @@ fn struct_get(
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
     let (_, struct_type_index) = builder.pop_concrete_ref_type();
+    arbitrary_struct_field_get(u, module, builder, instructions, struct_type_index)
+}
+
+/// Read an arbitrary field of a `struct_type_index` struct whose reference
+/// has already been popped from the stack.
+fn arbitrary_struct_field_get(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+    struct_type_index: u32,
+) -> Result<()> {
     let struct_ty = module.ty(struct_type_index).unwrap_struct();
     let num_fields = u32::try_from(struct_ty.fields.len()).unwrap();
     debug_assert!(num_fields > 0);
@@ -5982,14 +7047,86 @@ fn struct_get(
     Ok(())
 }
 
+#[inline]
+fn struct_get_cast_chain_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    module.config.gc_enabled
+        && !module.config.disallow_traps
+        && builder.non_empty_struct_ref_on_stack(module, true)
+        && struct_get_cast_chain_candidates(module, builder)
+            .next()
+            .is_some()
+}
+
+/// The `(field_index, cast_type_index)` pairs for the struct on top of the
+/// stack where the field holds a reference and the cast type is a non-empty
+/// struct type that is a strict subtype of the field's heap type, and so may
+/// be the actual type of the field's value.
+fn struct_get_cast_chain_candidates<'a>(
+    module: &'a Module,
+    builder: &CodeBuilder,
+) -> impl Iterator<Item = (u32, u32)> + 'a {
+    let fields = match builder.concrete_struct_ref_type_on_stack_at(module, 0) {
+        Some((_, _, ty)) => &ty.fields[..],
+        None => &[],
+    };
+    fields
+        .iter()
+        .enumerate()
+        .filter_map(|(i, field)| match field.element_type {
+            StorageType::Val(ValType::Ref(r)) => Some((u32::try_from(i).unwrap(), r.heap_type)),
+            _ => None,
+        })
+        .flat_map(move |(field_index, heap_type)| {
+            module
+                .struct_types
+                .iter()
+                .copied()
+                .filter(move |&ty| {
+                    let sub = HeapType::Concrete(ty);
+                    sub != heap_type
+                        && !module.ty(ty).unwrap_struct().fields.is_empty()
+                        && module.heap_type_is_sub_type(sub, heap_type)
+                })
+                .map(move |ty| (field_index, ty))
+        })
+}
+
+/// Read a reference field out of a struct, downcast it with `ref.cast` to a
+/// more specific struct type, and then read a field of that.
+fn struct_get_cast_chain(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    let candidates = struct_get_cast_chain_candidates(module, builder).collect::<Vec<_>>();
+    let (field_index, cast_type_index) = *u.choose(&candidates)?;
+    let (_, struct_type_index) = builder.pop_concrete_ref_type();
+    instructions.push(Instruction::StructGet {
+        struct_type_index,
+        field_index,
+    });
+    let cast_type = HeapType::Concrete(cast_type_index);
+    instructions.push(if u.arbitrary()? {
+        Instruction::RefCastNullable(cast_type)
+    } else {
+        Instruction::RefCastNonNull(cast_type)
+    });
+    arbitrary_struct_field_get(u, module, builder, instructions, cast_type_index)
+}
+
 #[inline]
 fn struct_set_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
-    if !module.config.gc_enabled {
-        return false;
-    }
+    module.config.gc_enabled
+        && (struct_set_operand_valid(module, builder) || struct_set_const_valid(module, builder))
+}
+
+/// Whether a struct reference and a value for one of its mutable fields are
+/// on the stack.
+fn struct_set_operand_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
     match builder.concrete_struct_ref_type_on_stack_at(module, 1) {
-        None => return false,
-        Some((true, _, _)) if module.config.disallow_traps => return false,
+        None => false,
+        Some((true, _, _)) if module.config.disallow_traps => false,
         Some((_, _, ty)) => ty
             .fields
             .iter()
@@ -5997,17 +7134,46 @@ fn struct_set_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
     }
 }
 
+/// Whether a struct reference is on top of the stack with a mutable field
+/// that a constant can be stored into.
+///
+/// Values matching a field's type rarely happen to be on the stack just
+/// above the struct, so this lets `struct.set` reach all of a struct's
+/// fields rather than just those of the most common types.
+fn struct_set_const_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    match builder.concrete_struct_ref_type_on_stack_at(module, 0) {
+        None => false,
+        Some((true, _, _)) if module.config.disallow_traps => false,
+        Some((_, _, ty)) => ty
+            .fields
+            .iter()
+            .any(|f| f.mutable && f.element_type.unpack().is_defaultable()),
+    }
+}
+
 fn struct_set(
     u: &mut Unstructured,
     module: &Module,
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let val_ty = builder.pop_operand();
+    let use_const = if !struct_set_operand_valid(module, builder) {
+        true
+    } else {
+        struct_set_const_valid(module, builder) && u.arbitrary()?
+    };
+    let val_ty = if use_const {
+        None
+    } else {
+        builder.pop_operand()
+    };
     let (_, struct_type_index) = builder.pop_concrete_ref_type();
     let struct_ty = module.ty(struct_type_index).unwrap_struct();
 
     let valid_field = |f: &FieldType| -> bool {
+        if use_const {
+            return f.mutable && f.element_type.unpack().is_defaultable();
+        }
         match val_ty {
             None => f.mutable,
             Some(val_ty) => {
@@ -6019,7 +7185,7 @@ fn struct_set(
     let n = struct_ty.fields.iter().filter(|f| valid_field(f)).count();
     debug_assert!(n > 0);
     let i = u.int_in_range(0..=n - 1)?;
-    let (field_index, _) = struct_ty
+    let (field_index, field) = struct_ty
         .fields
         .iter()
         .enumerate()
@@ -6028,6 +7194,9 @@ fn struct_set(
         .unwrap();
     let field_index = u32::try_from(field_index).unwrap();
 
+    if use_const {
+        instructions.push(module.arbitrary_const_instruction(field.element_type.unpack(), u)?);
+    }
     instructions.push(Instruction::StructSet {
         struct_type_index,
         field_index,
@@ -6080,10 +7249,10 @@ fn array_new(
 #[inline]
 fn array_new_fixed_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
     module.config.gc_enabled
-        && module
-            .array_types
-            .iter()
-            .any(|i| builder.field_type_on_stack(module, module.ty(*i).unwrap_array().0))
+        && module.array_types.iter().any(|i| {
+            let field = module.ty(*i).unwrap_array().0;
+            field.element_type.is_defaultable() || builder.field_type_on_stack(module, field)
+        })
 }
 
 fn array_new_fixed(
@@ -6092,37 +7261,41 @@ fn array_new_fixed(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let n = module
-        .array_types
-        .iter()
-        .filter(|i| builder.field_type_on_stack(module, module.ty(**i).unwrap_array().0))
-        .count();
-    debug_assert!(n > 0);
-    let i = u.int_in_range(0..=n - 1)?;
-    let array_type_index = module
+    // Keep the number of elements small to avoid huge operand stacks.
+    const MAX_ARRAY_NEW_FIXED_SIZE: usize = 16;
+
+    let candidates = module
         .array_types
         .iter()
         .copied()
-        .filter(|i| builder.field_type_on_stack(module, module.ty(*i).unwrap_array().0))
-        .nth(i)
-        .unwrap();
-    let elem_ty = module
-        .ty(array_type_index)
-        .unwrap_array()
-        .0
-        .element_type
-        .unpack();
-
-    let m = (0..builder.operands().len())
+        .filter(|i| {
+            let field = module.ty(*i).unwrap_array().0;
+            field.element_type.is_defaultable() || builder.field_type_on_stack(module, field)
+        })
+        .collect::<Vec<_>>();
+    let array_type_index = *u.choose(&candidates)?;
+    let field = module.ty(array_type_index).unwrap_array().0;
+    let elem_ty = field.element_type.unpack();
+
+    // Use some of the matching operands already on the stack as the first
+    // elements, and if the element type has constants then supply the rest
+    // by pushing arbitrary constants.
+    let on_stack = (0..builder.operands().len())
         .take_while(|i| builder.type_on_stack_at(module, *i, elem_ty))
+        .take(MAX_ARRAY_NEW_FIXED_SIZE)
         .count();
-    debug_assert!(m > 0);
-    let array_size = u.int_in_range(0..=m - 1)?;
-    let array_size = u32::try_from(array_size).unwrap();
-
-    for _ in 0..array_size {
+    let from_stack = u.int_in_range(0..=on_stack)?;
+    let from_consts = if field.element_type.is_defaultable() {
+        u.int_in_range(0..=MAX_ARRAY_NEW_FIXED_SIZE - from_stack)?
+    } else {
+        0
+    };
+    for _ in 0..from_stack {
         builder.pop_operand();
     }
+    for _ in 0..from_consts {
+        instructions.push(module.arbitrary_const_instruction(elem_ty, u)?);
+    }
     builder.push_operand(Some(ValType::Ref(RefType {
         nullable: false,
         heap_type: HeapType::Concrete(array_type_index),
@@ -6130,19 +7303,27 @@ fn array_new_fixed(
 
     instructions.push(Instruction::ArrayNewFixed {
         array_type_index,
-        array_size,
+        array_size: u32::try_from(from_stack + from_consts).unwrap(),
     });
     Ok(())
 }
 
+/// Array types which can be allocated with just a length operand: either with
+/// `array.new_default` if their element type is defaultable, or otherwise with
+/// `array.new` and an explicit element value.
+fn array_new_default_candidates(module: &Module) -> impl Iterator<Item = u32> + '_ {
+    module
+        .array_types
+        .iter()
+        .copied()
+        .filter(|&i| field_value_available(module, module.ty(i).unwrap_array().0.element_type))
+}
+
 #[inline]
 fn array_new_default_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
     module.config.gc_enabled
         && builder.type_on_stack(module, ValType::I32)
-        && module
-            .array_types
-            .iter()
-            .any(|i| module.ty(*i).unwrap_array().0.element_type.is_defaultable())
+        && array_new_default_candidates(module).next().is_some()
 }
 
 fn array_new_default(
@@ -6151,34 +7332,27 @@ fn array_new_default(
     builder: &mut CodeBuilder,
     instructions: &mut Vec<Instruction>,
 ) -> Result<()> {
-    let n = module
-        .array_types
-        .iter()
-        .filter(|i| {
-            module
-                .ty(**i)
-                .unwrap_array()
-                .0
-                .element_type
-                .is_defaultable()
-        })
-        .count();
+    let n = array_new_default_candidates(module).count();
     debug_assert!(n > 0);
     let i = u.int_in_range(0..=n - 1)?;
-    let array_type_index = module
-        .array_types
-        .iter()
-        .copied()
-        .filter(|i| module.ty(*i).unwrap_array().0.element_type.is_defaultable())
-        .nth(i)
-        .unwrap();
+    let array_type_index = array_new_default_candidates(module).nth(i).unwrap();
 
-    builder.pop_operand();
+    let element_type = module.ty(array_type_index).unwrap_array().0.element_type;
+    if element_type.is_defaultable() {
+        builder.pop_operand();
+        instructions.push(Instruction::ArrayNewDefault(array_type_index));
+    } else {
+        // `array.new_default` isn't valid for this element type, so leave the
+        // length on the stack and allocate a short array with `array.new` and
+        // an explicit element value instead.
+        arbitrary_field_value(u, module, element_type, instructions)?;
+        instructions.push(Instruction::I32Const(u.int_in_range(0..=4)?));
+        instructions.push(Instruction::ArrayNew(array_type_index));
+    }
     builder.push_operand(Some(ValType::Ref(RefType {
         nullable: false,
         heap_type: HeapType::Concrete(array_type_index),
     })));
-    instructions.push(Instruction::ArrayNewDefault(array_type_index));
     Ok(())
 }
 
@@ -6674,6 +7848,56 @@ fn extern_convert_any(
     Ok(())
 }
 
+#[inline]
+fn extern_round_trip_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    // The scratch `anyref` local isn't shared, so it can't be used from
+    // shared functions.
+    !builder.shared && any_convert_extern_valid(module, builder)
+}
+
+/// Bring a host-provided `externref` into the GC heap, inspect it there, and
+/// convert it back:
+///
+/// ```wat
+/// any.convert_extern
+/// local.tee $scratch
+/// ref.test <heap type>
+/// local.get $scratch
+/// extern.convert_any
+/// ```
+///
+/// This replaces the `externref` on the stack with the `i32` result of the
+/// test followed by the round-tripped `externref`.
+fn extern_round_trip(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    builder.pop_operand();
+    let local = match builder.anyref_scratch {
+        Some(local) => local,
+        None => {
+            let local = builder.alloc_local(ValType::Ref(RefType::ANYREF));
+            builder.anyref_scratch = Some(local);
+            local
+        }
+    };
+    let test_ty = module.arbitrary_matching_heap_type(u, HeapType::ANY)?;
+    instructions.push(Instruction::AnyConvertExtern);
+    instructions.push(Instruction::LocalTee(local));
+    instructions.push(if u.arbitrary()? {
+        Instruction::RefTestNullable(test_ty)
+    } else {
+        Instruction::RefTestNonNull(test_ty)
+    });
+    instructions.push(Instruction::LocalGet(local));
+    instructions.push(Instruction::ExternConvertAny);
+    builder.push_operand(Some(ValType::I32));
+    builder.push_operand(Some(ValType::Ref(RefType::EXTERNREF)));
+    Ok(())
+}
+
 fn lane_index(u: &mut Unstructured, number_of_lanes: u8) -> Result<u8> {
     u.int_in_range(0..=(number_of_lanes - 1))
 }
@@ -6945,8 +8169,40 @@ fn i8x16_shuffle(
     builder.pop_operands(module, &[ValType::V128, ValType::V128]);
     builder.push_operands(&[ValType::V128]);
     let mut lanes = [0; 16];
-    for i in 0..16 {
-        lanes[i] = u.int_in_range(0..=31)?;
+    // Lane patterns that backends tend to special-case when lowering shuffles
+    // are chosen half the time, otherwise the lanes are entirely random.
+    if u.arbitrary()? {
+        // Lane indices 16..32 select from the second operand.
+        let base = if u.arbitrary()? { 16 } else { 0 };
+        match u.int_in_range(0..=3)? {
+            // Identity
+            0 => {
+                for i in 0..16 {
+                    lanes[i] = base + i as u8;
+                }
+            }
+            // Reverse
+            1 => {
+                for i in 0..16 {
+                    lanes[i] = base + 15 - i as u8;
+                }
+            }
+            // Broadcast one lane
+            2 => lanes = [u.int_in_range(0..=31)?; 16],
+            // Interleave the low or high halves of both operands
+            3 => {
+                let half = if u.arbitrary()? { 8 } else { 0 };
+                for i in 0..8 {
+                    lanes[2 * i] = half + i as u8;
+                    lanes[2 * i + 1] = 16 + half + i as u8;
+                }
+            }
+            _ => unreachable!(),
+        }
+    } else {
+        for i in 0..16 {
+            lanes[i] = u.int_in_range(0..=31)?;
+        }
     }
     instructions.push(Instruction::I8x16Shuffle(lanes));
     Ok(())
@@ -7270,11 +8526,58 @@ simd_binop!(F32x4RelaxedMax, f32x4_relaxed_max);
 simd_binop!(F64x2RelaxedMin, f64x2_relaxed_min);
 simd_binop!(F64x2RelaxedMax, f64x2_relaxed_max);
 simd_binop!(I16x8RelaxedQ15mulrS, i16x8_relaxed_q15mulr_s);
-simd_binop!(I16x8RelaxedDotI8x16I7x16S, i16x8_relaxed_dot_i8x16_i7x16_s);
-simd_ternop!(
-    I32x4RelaxedDotI8x16I7x16AddS,
-    i32x4_relaxed_dot_i8x16_i7x16_add_s
-);
+
+/// Whether a relaxed dot product can be generated.
+///
+/// Only one `v128` operand needs to be on the stack; `relaxed_dot_operands`
+/// fills in the rest. Several `v128`s are rarely on the stack at once, so
+/// otherwise these instructions, especially the ternary
+/// `i32x4.relaxed_dot_i8x16_i7x16_add_s`, would hardly ever be generated.
+#[inline]
+fn relaxed_dot_valid(module: &Module, builder: &mut CodeBuilder) -> bool {
+    !module.config.disallow_traps
+        && module.config.relaxed_simd_enabled
+        && builder.type_on_stack(module, ValType::V128)
+}
+
+/// Push `v128.const`s until there are `arity` `v128` operands on the stack.
+fn relaxed_dot_operands(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+    arity: usize,
+) -> Result<()> {
+    while !builder.types_on_stack(module, &[ValType::V128; 3][..arity]) {
+        instructions.push(Instruction::V128Const(u.arbitrary()?));
+        builder.push_operands(&[ValType::V128]);
+    }
+    builder.pop_operands(module, &[ValType::V128; 3][..arity]);
+    builder.push_operands(&[ValType::V128]);
+    Ok(())
+}
+
+fn i16x8_relaxed_dot_i8x16_i7x16_s(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    relaxed_dot_operands(u, module, builder, instructions, 2)?;
+    instructions.push(Instruction::I16x8RelaxedDotI8x16I7x16S);
+    Ok(())
+}
+
+fn i32x4_relaxed_dot_i8x16_i7x16_add_s(
+    u: &mut Unstructured,
+    module: &Module,
+    builder: &mut CodeBuilder,
+    instructions: &mut Vec<Instruction>,
+) -> Result<()> {
+    relaxed_dot_operands(u, module, builder, instructions, 3)?;
+    instructions.push(Instruction::I32x4RelaxedDotI8x16I7x16AddS);
+    Ok(())
+}
 
 #[inline]
 fn wide_arithmetic_binop128_on_stack(module: &Module, builder: &mut CodeBuilder) -> bool {
@@ -0,0 +1,132 @@
+use super::*;
+use wasm_encoder::Encode;
+
+/// Which WebAssembly proposals a generated [`Module`] actually exercises.
+///
+/// Enabling a proposal in the [`Config`] only makes its constructs available
+/// to the generator, it doesn't guarantee that any of them are generated.
+/// This summary can be used to find configurations where a proposal is
+/// enabled but never triggers.
+///
+/// Each flag is `true` if the module contains at least one type, entity, or
+/// instruction that is specific to that proposal. A flag can only be `true`
+/// when the corresponding proposal is enabled.
+///
+/// Function bodies generated as arbitrary bytes (see
+/// [`Config::allow_invalid_funcs`]) are not inspected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct FeatureCoverage {
+    pub bulk_memory: bool,
+    pub custom_page_sizes: bool,
+    pub exceptions: bool,
+    pub gc_array_types: bool,
+    pub gc_instructions: bool,
+    pub gc_struct_types: bool,
+    pub memory64: bool,
+    pub multi_memory: bool,
+    pub multi_value: bool,
+    pub reference_types: bool,
+    pub relaxed_simd: bool,
+    pub saturating_float_to_int: bool,
+    pub sign_extension: bool,
+    pub simd: bool,
+    pub tail_call: bool,
+    pub threads: bool,
+    pub wide_arithmetic: bool,
+}
+
+/// The names that LLVM's `target_features` custom section uses for each
+/// proposal tracked by [`FeatureCoverage`], along with whether it's covered.
+pub(crate) const TARGET_FEATURES: &[(&str, fn(&FeatureCoverage) -> bool)] = &[
+    ("atomics", |c| c.threads),
+    ("bulk-memory", |c| c.bulk_memory),
+    ("custom-page-sizes", |c| c.custom_page_sizes),
+    ("exception-handling", |c| c.exceptions),
+    ("gc", |c| {
+        c.gc_array_types || c.gc_instructions || c.gc_struct_types
+    }),
+    ("memory64", |c| c.memory64),
+    ("multimemory", |c| c.multi_memory),
+    ("multivalue", |c| c.multi_value),
+    ("nontrapping-fptoint", |c| c.saturating_float_to_int),
+    ("reference-types", |c| c.reference_types),
+    ("relaxed-simd", |c| c.relaxed_simd),
+    ("sign-ext", |c| c.sign_extension),
+    ("simd128", |c| c.simd),
+    ("tail-call", |c| c.tail_call),
+    ("wide-arithmetic", |c| c.wide_arithmetic),
+];
+
+impl Module {
+    /// Summarize which of the enabled proposals this module exercises.
+    ///
+    /// See [`FeatureCoverage`] for details.
+    pub fn feature_coverage(&self) -> FeatureCoverage {
+        let mut coverage = FeatureCoverage {
+            custom_page_sizes: self.memories.iter().any(|m| m.page_size_log2.is_some()),
+            exceptions: !self.tags.is_empty(),
+            gc_array_types: !self.array_types.is_empty(),
+            gc_struct_types: !self.struct_types.is_empty(),
+            memory64: self.memories.iter().any(|m| m.memory64)
+                || self.tables.iter().any(|t| t.table64),
+            multi_memory: self.memories.len() > 1,
+            multi_value: self.func_types().any(|(_, ty)| ty.results.len() > 1),
+            reference_types: self.tables.len() > 1,
+            threads: self.memories.iter().any(|m| m.shared),
+            ..FeatureCoverage::default()
+        };
+
+        let mut bytes = Vec::new();
+        for code in &self.code {
+            let Instructions::Generated(instructions) = &code.instructions else {
+                continue;
+            };
+            for instruction in instructions {
+                bytes.clear();
+                instruction.encode(&mut bytes);
+                coverage.add_instruction(&bytes);
+            }
+        }
+
+        coverage
+    }
+}
+
+impl FeatureCoverage {
+    /// Record the proposal that the encoded instruction in `bytes` belongs to,
+    /// if any.
+    fn add_instruction(&mut self, bytes: &[u8]) {
+        match bytes[0] {
+            0x06..=0x0a | 0x18 | 0x19 | 0x1f => self.exceptions = true,
+            0x12 | 0x13 | 0x15 => self.tail_call = true,
+            0x14 | 0xd3..=0xd6 | 0xfb => self.gc_instructions = true,
+            0x1c | 0x25 | 0x26 | 0xd0..=0xd2 => self.reference_types = true,
+            0xc0..=0xc4 => self.sign_extension = true,
+            0xfc => match leb_u32(&bytes[1..]) {
+                0..=7 => self.saturating_float_to_int = true,
+                8..=14 => self.bulk_memory = true,
+                15..=17 => self.reference_types = true,
+                19..=22 => self.wide_arithmetic = true,
+                _ => {}
+            },
+            0xfd => match leb_u32(&bytes[1..]) {
+                0x100..=0x113 => self.relaxed_simd = true,
+                _ => self.simd = true,
+            },
+            0xfe => self.threads = true,
+            _ => {}
+        }
+    }
+}
+
+fn leb_u32(bytes: &[u8]) -> u32 {
+    let mut result = 0;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        result |= u32::from(byte & 0x7f) << (i * 7);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    result
+}
@@ -1,40 +1,207 @@
+use super::coverage::TARGET_FEATURES;
 use super::*;
+use crate::ExportOrdering;
+use wasm_encoder::Encode;
+
+// Maximum encoded sizes of the pieces of a module, used to compute
+// `Module::estimated_encoded_len`.
+const MAX_LEB_U32: usize = 5;
+const MAX_LEB_U64: usize = 10;
+const MAX_VAL_TYPE: usize = 7;
+const MAX_BLOCK_TYPE: usize = MAX_VAL_TYPE;
+const MAX_ENTITY_TYPE: usize = 32;
+const MAX_OFFSET_EXPR: usize = 2 + MAX_LEB_U64;
+// Every fixed-size instruction fits in this many bytes: the largest are SIMD
+// lane memory accesses with a prefix, opcode, memarg, and lane index.
+const MAX_INSTRUCTION: usize = 32;
+
+/// The bytes of a module being encoded, which sections are appended to like
+/// `wasm_encoder::Module` but in a buffer owned by the caller.
+enum ModuleBytes<'a> {
+    /// The sections are encoded one after another into a whole module.
+    Module(&'a mut Vec<u8>),
+    /// Each section's id and contents, without its size, are kept separately.
+    Sections(&'a mut Vec<(u8, Vec<u8>)>),
+}
+
+impl ModuleBytes<'_> {
+    fn section(&mut self, section: &impl wasm_encoder::Section) {
+        match self {
+            ModuleBytes::Module(bytes) => {
+                bytes.push(section.id());
+                section.encode(bytes);
+            }
+            ModuleBytes::Sections(sections) => {
+                let mut bytes = Vec::new();
+                section.encode(&mut bytes);
+                // Strip the LEB128-encoded size that precedes the contents.
+                let size_len = bytes.iter().position(|b| b & 0x80 == 0).unwrap() + 1;
+                bytes.drain(..size_len);
+                sections.push((section.id(), bytes));
+            }
+        }
+    }
+}
 
 impl Module {
     /// Encode this Wasm module into bytes.
     pub fn to_bytes(&self) -> Vec<u8> {
-        self.encoded().finish()
+        let mut bytes = Vec::new();
+        self.encode_into(&mut bytes);
+        bytes
+    }
+
+    /// Encode this Wasm module into `buf`, replacing its contents.
+    ///
+    /// This produces the same bytes as [`Module::to_bytes`] but reuses
+    /// `buf`'s allocation, which avoids allocating a new buffer for each
+    /// module when encoding many of them.
+    pub fn encode_into(&self, buf: &mut Vec<u8>) {
+        buf.clear();
+        buf.extend_from_slice(&wasm_encoder::Module::HEADER);
+        self.encode_all(&mut ModuleBytes::Module(buf));
+    }
+
+    /// Encode each section of this Wasm module separately, returning the
+    /// section id and contents of each in order.
+    ///
+    /// The contents don't include the section's id or size, and there's no
+    /// entry for the module header. This allows comparing a module's encoding
+    /// section by section, for example across versions of `wasm-encoder`, to
+    /// find which sections differ.
+    pub fn encode_sections(&self) -> Vec<(u8, Vec<u8>)> {
+        let mut sections = Vec::new();
+        self.encode_all(&mut ModuleBytes::Sections(&mut sections));
+        sections
     }
 
-    fn encoded(&self) -> wasm_encoder::Module {
-        let mut module = wasm_encoder::Module::new();
-
-        self.encode_types(&mut module);
-        self.encode_imports(&mut module);
-        self.encode_funcs(&mut module);
-        self.encode_tables(&mut module);
-        self.encode_memories(&mut module);
-        self.encode_tags(&mut module);
-        self.encode_globals(&mut module);
-        self.encode_exports(&mut module);
-        self.encode_start(&mut module);
-        self.encode_elems(&mut module);
-        self.encode_data_count(&mut module);
-        self.encode_code(&mut module);
-        self.encode_data(&mut module);
-
-        module
+    fn encode_all(&self, module: &mut ModuleBytes) {
+        self.encode_types(module);
+        self.encode_imports(module);
+        self.encode_funcs(module);
+        self.encode_tables(module);
+        self.encode_memories(module);
+        self.encode_tags(module);
+        self.encode_globals(module);
+        self.encode_exports(module);
+        self.encode_start(module);
+        self.encode_elems(module);
+        self.encode_data_count(module);
+        self.encode_code(module);
+        self.encode_data(module);
+        self.encode_target_features(module);
     }
 
-    fn encode_types(&self, module: &mut wasm_encoder::Module) {
+    /// Returns an upper bound on the length of [`Module::to_bytes`].
+    ///
+    /// This is computed from the module's structure without encoding it, so
+    /// it's cheap enough to use for sizing buffers ahead of time. The
+    /// estimate is never smaller than the actual encoded length but may be
+    /// considerably larger, particularly for the code section.
+    pub fn estimated_encoded_len(&self) -> usize {
+        fn name_len(name: &str) -> usize {
+            MAX_LEB_U32 + name.len()
+        }
+
+        let mut scratch = Vec::new();
+        let mut const_expr_len = |expr: &ConstExpr| {
+            scratch.clear();
+            expr.encode(&mut scratch);
+            scratch.len()
+        };
+
+        // The module header, plus the id, size, and item count of every
+        // section that could be emitted.
+        let mut len = 8 + 13 * (1 + 2 * MAX_LEB_U32);
+
+        len += self.rec_groups.len() * (1 + MAX_LEB_U32);
+        for ty in &self.types {
+            len += 3 + 2 * MAX_LEB_U32;
+            len += match &ty.composite_type.inner {
+                CompositeInnerType::Array(_) => MAX_VAL_TYPE + 1,
+                CompositeInnerType::Func(f) => {
+                    2 * MAX_LEB_U32 + (f.params.len() + f.results.len()) * MAX_VAL_TYPE
+                }
+                CompositeInnerType::Struct(s) => MAX_LEB_U32 + s.fields.len() * (MAX_VAL_TYPE + 1),
+            };
+        }
+
+        for import in &self.imports {
+            len += name_len(&import.module) + name_len(&import.field) + MAX_ENTITY_TYPE;
+        }
+
+        len += self.num_defined_funcs * MAX_LEB_U32;
+        for init in &self.defined_tables {
+            len += 2 + MAX_ENTITY_TYPE + init.as_ref().map_or(0, &mut const_expr_len);
+        }
+        len += self.num_defined_memories * MAX_ENTITY_TYPE;
+        len += self.num_defined_tags * (1 + MAX_LEB_U32);
+        for (_, expr) in &self.defined_globals {
+            len += MAX_VAL_TYPE + 1 + const_expr_len(expr);
+        }
+        for (name, _, _) in &self.exports {
+            len += name_len(name) + 1 + MAX_LEB_U32;
+        }
+
+        for elem in &self.elems {
+            len += 2 * MAX_LEB_U32 + MAX_VAL_TYPE;
+            len += match &elem.kind {
+                ElementKind::Active {
+                    offset: Offset::Expr(e),
+                    ..
+                } => const_expr_len(e),
+                _ => MAX_OFFSET_EXPR,
+            };
+            len += match &elem.items {
+                Elements::Functions(fs) => fs.len() * MAX_LEB_U32,
+                Elements::Expressions(es) => es.iter().map(&mut const_expr_len).sum(),
+            };
+        }
+
+        for code in &self.code {
+            len += 2 * MAX_LEB_U32 + code.locals.len() * (MAX_LEB_U32 + MAX_VAL_TYPE);
+            len += match &code.instructions {
+                Instructions::Generated(instrs) => {
+                    1 + instrs.iter().map(instruction_len_bound).sum::<usize>()
+                }
+                Instructions::Arbitrary(body) => body.len(),
+            };
+        }
+
+        for seg in &self.data {
+            len += 3 * MAX_LEB_U32 + seg.init.len();
+            len += match &seg.kind {
+                DataSegmentKind::Active {
+                    offset: Offset::Expr(e),
+                    ..
+                } => const_expr_len(e),
+                _ => MAX_OFFSET_EXPR,
+            };
+        }
+
+        if self.config.generate_target_features_section {
+            len += 1 + 2 * MAX_LEB_U32 + name_len("target_features");
+            for (name, _) in TARGET_FEATURES {
+                len += 1 + name_len(name);
+            }
+        }
+
+        len
+    }
+
+    fn encode_types(&self, module: &mut ModuleBytes<'_>) {
         if !self.should_encode_types {
             return;
         }
 
         let mut section = wasm_encoder::TypeSection::new();
 
+        // Explicit rec groups are only valid with GC, so the flag to force them
+        // is ignored otherwise.
+        let force_explicit = self.config.force_explicit_rec_groups && self.config.gc_enabled;
+
         for group in &self.rec_groups {
-            if group.end - group.start == 1 {
+            if group.end - group.start == 1 && !force_explicit {
                 let ty = &self.types[group.start];
                 section.ty().subtype(&wasm_encoder::SubType {
                     is_final: ty.is_final,
@@ -55,7 +222,7 @@ impl Module {
         module.section(&section);
     }
 
-    fn encode_imports(&self, module: &mut wasm_encoder::Module) {
+    fn encode_imports(&self, module: &mut ModuleBytes<'_>) {
         if !self.should_encode_imports {
             return;
         }
@@ -71,7 +238,7 @@ impl Module {
         module.section(&section);
     }
 
-    fn encode_tags(&self, module: &mut wasm_encoder::Module) {
+    fn encode_tags(&self, module: &mut ModuleBytes<'_>) {
         if self.num_defined_tags == 0 {
             return;
         }
@@ -85,7 +252,7 @@ impl Module {
         module.section(&tags);
     }
 
-    fn encode_funcs(&self, module: &mut wasm_encoder::Module) {
+    fn encode_funcs(&self, module: &mut ModuleBytes<'_>) {
         if self.num_defined_funcs == 0 {
             return;
         }
@@ -96,7 +263,7 @@ impl Module {
         module.section(&funcs);
     }
 
-    fn encode_tables(&self, module: &mut wasm_encoder::Module) {
+    fn encode_tables(&self, module: &mut ModuleBytes<'_>) {
         if self.defined_tables.is_empty() {
             return;
         }
@@ -117,7 +284,7 @@ impl Module {
         module.section(&tables);
     }
 
-    fn encode_memories(&self, module: &mut wasm_encoder::Module) {
+    fn encode_memories(&self, module: &mut ModuleBytes<'_>) {
         if self.num_defined_memories == 0 {
             return;
         }
@@ -128,8 +295,8 @@ impl Module {
         module.section(&mems);
     }
 
-    fn encode_globals(&self, module: &mut wasm_encoder::Module) {
-        if self.globals.is_empty() {
+    fn encode_globals(&self, module: &mut ModuleBytes<'_>) {
+        if self.defined_globals.is_empty() {
             return;
         }
         let mut globals = wasm_encoder::GlobalSection::new();
@@ -140,24 +307,36 @@ impl Module {
         module.section(&globals);
     }
 
-    fn encode_exports(&self, module: &mut wasm_encoder::Module) {
+    fn encode_exports(&self, module: &mut ModuleBytes<'_>) {
         if self.exports.is_empty() {
             return;
         }
+        let mut entries = self.exports.iter().collect::<Vec<_>>();
+        match self.config.export_ordering {
+            ExportOrdering::Generation => {}
+            ExportOrdering::ByName => entries.sort_by(|a, b| a.0.cmp(&b.0)),
+            ExportOrdering::ByKind => entries.sort_by_key(|(_, kind, _)| match kind {
+                ExportKind::Func => 0,
+                ExportKind::Table => 1,
+                ExportKind::Memory => 2,
+                ExportKind::Global => 3,
+                ExportKind::Tag => 4,
+            }),
+        }
         let mut exports = wasm_encoder::ExportSection::new();
-        for (name, kind, idx) in &self.exports {
+        for (name, kind, idx) in entries {
             exports.export(name, *kind, *idx);
         }
         module.section(&exports);
     }
 
-    fn encode_start(&self, module: &mut wasm_encoder::Module) {
+    fn encode_start(&self, module: &mut ModuleBytes<'_>) {
         if let Some(f) = self.start {
             module.section(&wasm_encoder::StartSection { function_index: f });
         }
     }
 
-    fn encode_elems(&self, module: &mut wasm_encoder::Module) {
+    fn encode_elems(&self, module: &mut ModuleBytes<'_>) {
         if self.elems.is_empty() {
             return;
         }
@@ -172,10 +351,11 @@ impl Module {
             };
             match &el.kind {
                 ElementKind::Active { table, offset } => {
-                    let offset = match *offset {
-                        Offset::Const32(n) => ConstExpr::i32_const(n),
-                        Offset::Const64(n) => ConstExpr::i64_const(n),
-                        Offset::Global(g) => ConstExpr::global_get(g),
+                    let offset = match offset {
+                        Offset::Const32(n) => ConstExpr::i32_const(*n),
+                        Offset::Const64(n) => ConstExpr::i64_const(*n),
+                        Offset::Global(g) => ConstExpr::global_get(*g),
+                        Offset::Expr(e) => e.clone(),
                     };
                     elems.active(*table, &offset, elements);
                 }
@@ -190,13 +370,29 @@ impl Module {
         module.section(&elems);
     }
 
-    fn encode_data_count(&self, module: &mut wasm_encoder::Module) {
-        // Without bulk memory there's no need for a data count section,
-        if !self.config.bulk_memory_enabled {
-            return;
-        }
-        // ... and also if there's no data no need for a data count section.
-        if self.data.is_empty() {
+    fn encode_data_count(&self, module: &mut ModuleBytes<'_>) {
+        let emit = match self.config.emit_data_count_section {
+            Some(emit) => emit,
+            // Without bulk memory there's no need for a data count section,
+            None if !self.config.bulk_memory_enabled => false,
+            // ... and also if there's no data no need for a data count section,
+            None if self.data.is_empty() => false,
+            // ... otherwise it's only needed when code refers to data segments.
+            None => self.code.iter().any(|c| match &c.instructions {
+                Instructions::Generated(instrs) => instrs.iter().any(|i| {
+                    matches!(
+                        i,
+                        Instruction::MemoryInit { .. }
+                            | Instruction::DataDrop(_)
+                            | Instruction::ArrayNewData { .. }
+                            | Instruction::ArrayInitData { .. }
+                    )
+                }),
+                // Arbitrary bodies might refer to anything.
+                Instructions::Arbitrary(_) => true,
+            }),
+        };
+        if !emit {
             return;
         }
         module.section(&wasm_encoder::DataCountSection {
@@ -204,15 +400,15 @@ impl Module {
         });
     }
 
-    fn encode_code(&self, module: &mut wasm_encoder::Module) {
+    fn encode_code(&self, module: &mut ModuleBytes<'_>) {
         if self.code.is_empty() {
             return;
         }
         let mut code = wasm_encoder::CodeSection::new();
         for c in &self.code {
-            // Skip the run-length encoding because it is a little
-            // annoying to compute; use a length of one for every local.
-            let mut func = wasm_encoder::Function::new(c.locals.iter().map(|l| (1, *l)));
+            // Consecutive locals of the same type are grouped into a single
+            // run-length encoded entry.
+            let mut func = wasm_encoder::Function::new_with_locals_types(c.locals.iter().copied());
             match &c.instructions {
                 Instructions::Generated(instrs) => {
                     for instr in instrs {
@@ -229,7 +425,7 @@ impl Module {
         module.section(&code);
     }
 
-    fn encode_data(&self, module: &mut wasm_encoder::Module) {
+    fn encode_data(&self, module: &mut ModuleBytes<'_>) {
         if self.data.is_empty() {
             return;
         }
@@ -240,10 +436,11 @@ impl Module {
                     memory_index,
                     offset,
                 } => {
-                    let offset = match *offset {
-                        Offset::Const32(n) => ConstExpr::i32_const(n),
-                        Offset::Const64(n) => ConstExpr::i64_const(n),
-                        Offset::Global(g) => ConstExpr::global_get(g),
+                    let offset = match offset {
+                        Offset::Const32(n) => ConstExpr::i32_const(*n),
+                        Offset::Const64(n) => ConstExpr::i64_const(*n),
+                        Offset::Global(g) => ConstExpr::global_get(*g),
+                        Offset::Expr(e) => e.clone(),
                     };
                     data.active(*memory_index, &offset, seg.init.iter().copied());
                 }
@@ -254,6 +451,43 @@ impl Module {
         }
         module.section(&data);
     }
+
+    fn encode_target_features(&self, module: &mut ModuleBytes<'_>) {
+        if !self.config.generate_target_features_section {
+            return;
+        }
+        let coverage = self.feature_coverage();
+        let features = TARGET_FEATURES
+            .iter()
+            .filter(|(_, covered)| covered(&coverage))
+            .collect::<Vec<_>>();
+        let mut data = Vec::new();
+        features.len().encode(&mut data);
+        for (name, _) in features {
+            data.push(b'+');
+            name.encode(&mut data);
+        }
+        module.section(&wasm_encoder::CustomSection {
+            name: "target_features".into(),
+            data: data.into(),
+        });
+    }
+}
+
+/// An upper bound on the encoded length of `instr`.
+fn instruction_len_bound(instr: &Instruction) -> usize {
+    match instr {
+        Instruction::BrTable(targets, _) => 1 + (targets.len() + 2) * MAX_LEB_U32,
+        Instruction::TryTable(_, catches) => {
+            1 + MAX_BLOCK_TYPE + MAX_LEB_U32 + catches.len() * (1 + 2 * MAX_LEB_U32)
+        }
+        Instruction::TypedSelectMulti(tys) => 1 + MAX_LEB_U32 + tys.len() * MAX_VAL_TYPE,
+        Instruction::Resume { resume_table, .. }
+        | Instruction::ResumeThrow { resume_table, .. } => {
+            MAX_INSTRUCTION + MAX_LEB_U32 + resume_table.len() * (1 + 2 * MAX_LEB_U32)
+        }
+        _ => MAX_INSTRUCTION,
+    }
 }
 
 pub(crate) fn translate_entity_type(ty: &EntityType) -> wasm_encoder::EntityType {
@@ -60,17 +60,58 @@ mod component;
 mod config;
 mod core;
 
-pub use crate::core::{InstructionKind, InstructionKinds, Module};
+pub use crate::core::{FeatureCoverage, InstructionKind, InstructionKinds, Module};
 use arbitrary::{Result, Unstructured};
 #[cfg(feature = "component-model")]
 pub use component::Component;
-pub use config::{Config, MemoryOffsetChoices};
+pub use config::{
+    Config, DataContentKind, ElementDensity, ExportOrdering, MemoryOffsetChoices, PathologyKind,
+    StringAlphabet, SwarmWeights,
+};
 use std::{collections::HashSet, fmt::Write, str};
 use wasm_encoder::MemoryType;
 
 #[doc(hidden)]
 pub use config::InternalOptionalConfig;
 
+/// Generate a corpus of `count` encoded Wasm modules with the given `config`.
+///
+/// The `rng` callback is used as the source of randomness: it is given a
+/// buffer to fill with random bytes for each module, for example
+/// `|buf| rng.fill_bytes(buf)` for a `rand` RNG. The same buffer is reused for
+/// every module that is generated.
+///
+/// Attempts that fail to generate a module, for example because the random
+/// bytes were exhausted before the config's minimums were satisfied, are
+/// retried. After 1000 consecutive failed attempts the config is assumed to be
+/// unsatisfiable and the iterator ends early, yielding fewer than `count`
+/// modules.
+pub fn generate_corpus(
+    config: Config,
+    count: usize,
+    mut rng: impl FnMut(&mut [u8]),
+) -> impl Iterator<Item = Vec<u8>> {
+    const BUFFER_SIZE: usize = 2048;
+    const MAX_CONSECUTIVE_FAILURES: usize = 1000;
+    let mut buf = vec![0; BUFFER_SIZE];
+    let mut remaining = count;
+    std::iter::from_fn(move || {
+        if remaining == 0 {
+            return None;
+        }
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            rng(&mut buf);
+            let mut u = Unstructured::new(&buf);
+            if let Ok(module) = Module::new(config.clone(), &mut u) {
+                remaining -= 1;
+                return Some(module.to_bytes());
+            }
+        }
+        remaining = 0;
+        None
+    })
+}
+
 pub(crate) fn page_size(mem: &MemoryType) -> u32 {
     const DEFAULT_WASM_PAGE_SIZE_LOG2: u32 = 16;
     1 << mem.page_size_log2.unwrap_or(DEFAULT_WASM_PAGE_SIZE_LOG2)
@@ -105,6 +146,17 @@ pub(crate) fn arbitrary_loop<'a>(
     Ok(())
 }
 
+/// Return `true` with probability `ratio`, which is clamped to `0.0..=1.0` and
+/// rounded down to a whole percentage.
+///
+/// A ratio that rounds down to zero returns `false` without consuming any
+/// input, so options whose ratio is disabled don't affect the rest of the
+/// generated module.
+pub(crate) fn arbitrary_ratio(u: &mut Unstructured, ratio: f64) -> Result<bool> {
+    let pct = (ratio.clamp(0.0, 1.0) * 100.0) as u8;
+    Ok(pct > 0 && u.ratio(pct, 100)?)
+}
+
 // Mirror what happens in `Arbitrary for String`, but do so with a clamped size.
 pub(crate) fn limited_str<'a>(max_size: usize, u: &mut Unstructured<'a>) -> Result<&'a str> {
     let size = u.arbitrary_len::<u8>()?;
@@ -123,16 +175,40 @@ pub(crate) fn limited_str<'a>(max_size: usize, u: &mut Unstructured<'a>) -> Resu
     }
 }
 
-pub(crate) fn limited_string(max_size: usize, u: &mut Unstructured) -> Result<String> {
-    Ok(limited_str(max_size, u)?.into())
+pub(crate) fn limited_string(
+    max_size: usize,
+    alphabet: Option<StringAlphabet>,
+    u: &mut Unstructured,
+) -> Result<String> {
+    const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+    let alphabet = alphabet.unwrap_or_default();
+    if alphabet == StringAlphabet::Utf8 {
+        return Ok(limited_str(max_size, u)?.into());
+    }
+    let size = std::cmp::min(u.arbitrary_len::<u8>()?, max_size);
+    let mut name = String::with_capacity(size);
+    for _ in 0..size {
+        name.push(match alphabet {
+            StringAlphabet::Ascii => u.int_in_range(0x20..=0x7e)?,
+            StringAlphabet::Alphanumeric => *u.choose(ALPHANUMERIC)?,
+            StringAlphabet::Utf8 => unreachable!(),
+        } as char);
+    }
+    Ok(name)
 }
 
+/// Generates a name that isn't already in `names` and adds it.
+///
+/// Collisions are resolved by appending digits, which are part of every
+/// `StringAlphabet`.
 pub(crate) fn unique_string(
     max_size: usize,
+    alphabet: Option<StringAlphabet>,
     names: &mut HashSet<String>,
     u: &mut Unstructured,
 ) -> Result<String> {
-    let mut name = limited_string(max_size, u)?;
+    let mut name = limited_string(max_size, alphabet, u)?;
     while names.contains(&name) {
         write!(&mut name, "{}", names.len()).unwrap();
     }
@@ -140,7 +216,6 @@ pub(crate) fn unique_string(
     Ok(name)
 }
 
-#[cfg(feature = "component-model")]
 pub(crate) fn unique_kebab_string(
     max_size: usize,
     names: &mut HashSet<String>,
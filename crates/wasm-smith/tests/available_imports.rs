@@ -8,7 +8,7 @@ use wasmparser::Validator;
 use wasmparser::{Parser, TypeRef, ValType};
 
 mod common;
-use common::validate;
+use common::{for_each_module, validate};
 
 #[test]
 fn smoke_test_imports_config() {
@@ -191,3 +191,157 @@ fn import_config(
     );
     (config, available)
 }
+
+#[test]
+fn calls_target_imported_functions() {
+    fn count_calls(prefer_calling_imports: bool) -> (usize, usize) {
+        let (mut imported_calls, mut defined_calls) = (0, 0);
+        for_each_module(
+            256,
+            |_| Config {
+                prefer_calling_imports,
+                available_imports: Some(
+                    wat::parse_str(
+                        r#"
+                        (module
+                            (import "env" "f" (func))
+                            (import "env" "pi" (func (param i32)))
+                            (import "env" "po" (func (result i32)))
+                        )
+                        "#,
+                    )
+                    .unwrap(),
+                ),
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                let mut num_imported_funcs = 0;
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    match payload.unwrap() {
+                        wasmparser::Payload::ImportSection(rdr) => {
+                            for import in rdr {
+                                if let TypeRef::Func(_) = import.unwrap().ty {
+                                    num_imported_funcs += 1;
+                                }
+                            }
+                        }
+                        wasmparser::Payload::CodeSectionEntry(body) => {
+                            for op in body.get_operators_reader().unwrap() {
+                                if let wasmparser::Operator::Call { function_index } = op.unwrap() {
+                                    if function_index < num_imported_funcs {
+                                        imported_calls += 1;
+                                    } else {
+                                        defined_calls += 1;
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            },
+        );
+        (imported_calls, defined_calls)
+    }
+
+    let (imported, defined) = count_calls(false);
+    assert!(imported > 0);
+    assert!(defined > 0);
+    let (preferred_imported, preferred_defined) = count_calls(true);
+    assert!(preferred_imported > imported);
+    assert!(preferred_defined < defined);
+}
+
+#[test]
+fn imported_externrefs_round_trip_through_any() {
+    use wasmparser::Operator;
+
+    let mut round_trips = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            available_imports: Some(
+                wat::parse_str(
+                    r#"
+                    (module
+                        (import "env" "host" (global externref))
+                        (import "env" "f" (func (param externref) (result externref)))
+                    )
+                    "#,
+                )
+                .unwrap(),
+            ),
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let wasmparser::Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                let ops = body
+                    .get_operators_reader()
+                    .unwrap()
+                    .into_iter()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                for window in ops.windows(5) {
+                    if let [
+                        Operator::AnyConvertExtern,
+                        Operator::LocalTee { .. },
+                        Operator::RefTestNullable { .. } | Operator::RefTestNonNull { .. },
+                        Operator::LocalGet { .. },
+                        Operator::ExternConvertAny,
+                    ] = window
+                    {
+                        round_trips += 1;
+                    }
+                }
+            }
+        },
+    );
+    assert!(round_trips > 0);
+}
+
+#[test]
+fn imported_tables_are_mutated() {
+    use wasmparser::Operator;
+
+    let (mut grows, mut sets, mut fills) = (0, 0, 0);
+    for_each_module(
+        256,
+        |_| Config {
+            available_imports: Some(
+                wat::parse_str(
+                    r#"
+                    (module
+                        (import "env" "table" (table 1 10 funcref))
+                    )
+                    "#,
+                )
+                .unwrap(),
+            ),
+            min_funcs: 2,
+            ..Config::default()
+        },
+        |module, wasm_bytes| {
+            let num_imported_tables = u32::try_from(module.num_imported_tables()).unwrap();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let wasmparser::Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                for op in body.get_operators_reader().unwrap() {
+                    match op.unwrap() {
+                        Operator::TableGrow { table } if table < num_imported_tables => grows += 1,
+                        Operator::TableSet { table } if table < num_imported_tables => sets += 1,
+                        Operator::TableFill { table } if table < num_imported_tables => fills += 1,
+                        _ => {}
+                    }
+                }
+            }
+        },
+    );
+    // Growing is bounded by the imported table's maximum, but doesn't trap.
+    assert!(grows > 0);
+    assert!(sets + fills > 0);
+}
@@ -13,3 +13,34 @@ pub fn validate(validator: &mut Validator, bytes: &[u8]) -> Types {
     }
     panic!("wasm failed to validate: {err}");
 }
+
+/// Generate up to `count` modules, each from a fresh buffer of random bytes
+/// with a fixed seed, and call `check` with each module and its encoding once
+/// it has been validated.
+///
+/// `config` creates each module's configuration, possibly from the start of
+/// its random bytes. Modules which fail to generate are skipped.
+#[cfg(feature = "wasmparser")]
+#[allow(dead_code)]
+pub fn for_each_module(
+    count: usize,
+    mut config: impl FnMut(&mut arbitrary::Unstructured) -> wasm_smith::Config,
+    mut check: impl FnMut(&wasm_smith::Module, &[u8]),
+) {
+    use rand::{RngCore, SeedableRng, rngs::SmallRng};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..count {
+        rng.fill_bytes(&mut buf);
+        let mut u = arbitrary::Unstructured::new(&buf);
+        let config = config(&mut u);
+        let features = config.features();
+        let Ok(module) = wasm_smith::Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        validate(&mut Validator::new_with_features(features), &wasm_bytes);
+        check(&module, &wasm_bytes);
+    }
+}
@@ -1,9 +1,14 @@
 use arbitrary::{Arbitrary, Unstructured};
 use rand::{RngCore, SeedableRng, rngs::SmallRng};
-use wasm_smith::{Config, Module};
+use wasm_smith::{
+    Config, DataContentKind, FeatureCoverage, InstructionKind, InstructionKinds, Module,
+    PathologyKind, StringAlphabet, generate_corpus,
+};
 use wasmparser::{Validator, WasmFeatures};
 
 mod common;
+#[cfg(feature = "wasmparser")]
+use common::for_each_module;
 use common::validate;
 
 #[test]
@@ -22,6 +27,36 @@ fn smoke_test_module() {
     }
 }
 
+#[test]
+fn smoke_test_generate_corpus() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let corpus = generate_corpus(Config::default(), 100, |buf| rng.fill_bytes(buf));
+    let mut count = 0;
+    for wasm_bytes in corpus {
+        let mut validator = Validator::new_with_features(WasmFeatures::all());
+        validate(&mut validator, &wasm_bytes);
+        count += 1;
+    }
+    assert_eq!(count, 100);
+}
+
+#[test]
+fn generate_corpus_ends_for_unsatisfiable_config() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut config = Config::default();
+    // There's nothing to export, so the minimum number of exports can never
+    // be met.
+    config.min_exports = 1;
+    config.max_imports = 0;
+    config.max_funcs = 0;
+    config.max_tables = 0;
+    config.max_memories = 0;
+    config.max_globals = 0;
+    config.max_tags = 0;
+    let corpus = generate_corpus(config, 10, |buf| rng.fill_bytes(buf));
+    assert_eq!(corpus.count(), 0);
+}
+
 #[test]
 fn smoke_test_ensure_termination() {
     let mut rng = SmallRng::seed_from_u64(0);
@@ -57,6 +92,161 @@ fn smoke_test_swarm_config() {
     }
 }
 
+#[test]
+fn swarm_config_with_weights() {
+    use wasm_smith::SwarmWeights;
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let weights = SwarmWeights {
+        gc: Some(0.0),
+        simd: Some(1.0),
+        ..SwarmWeights::default()
+    };
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        if let Ok(config) = Config::arbitrary_with_weights(&mut u, &weights) {
+            assert!(!config.gc_enabled);
+            assert!(config.simd_enabled);
+            if let Ok(module) = Module::new(config, &mut u) {
+                let wasm_bytes = module.to_bytes();
+                let mut validator = Validator::new_with_features(WasmFeatures::all());
+                validate(&mut validator, &wasm_bytes);
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn swarm_config_stays_within_features() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        if let Ok(config) = Config::arbitrary(&mut u) {
+            if let Ok(module) = Module::new(config, &mut u) {
+                module.assert_features_subset();
+            }
+        }
+    }
+}
+
+#[test]
+fn estimated_encoded_len_is_upper_bound() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for i in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = match i % 3 {
+            0 => Config::default(),
+            // Extended constant expressions for data offsets can be longer
+            // than a single constant.
+            1 => Config {
+                extended_const_enabled: true,
+                extended_const_data_offset_ratio: 1.0,
+                min_memories: 1,
+                min_data_segments: 50,
+                max_data_segments: 100,
+                ..Config::default()
+            },
+            _ => match Config::arbitrary(&mut u) {
+                Ok(config) => config,
+                Err(_) => continue,
+            },
+        };
+        if let Ok(module) = Module::new(config, &mut u) {
+            let actual = module.to_bytes().len();
+            let estimate = module.estimated_encoded_len();
+            assert!(estimate >= actual, "estimate {estimate} < actual {actual}");
+        }
+    }
+}
+
+#[test]
+fn encode_into_reuses_buffer() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut encoded = Vec::new();
+    for i in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = if i % 2 == 0 {
+            Config::default()
+        } else if let Ok(config) = Config::arbitrary(&mut u) {
+            config
+        } else {
+            continue;
+        };
+        if let Ok(module) = Module::new(config, &mut u) {
+            // The same buffer is used for every module, so any bytes left
+            // over from the previous one would show up here.
+            module.encode_into(&mut encoded);
+            assert_eq!(encoded, module.to_bytes());
+        }
+    }
+}
+
+#[test]
+fn encode_sections_matches_to_bytes() {
+    use wasm_encoder::Encode;
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for i in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = if i % 2 == 0 {
+            Config::default()
+        } else if let Ok(config) = Config::arbitrary(&mut u) {
+            config
+        } else {
+            continue;
+        };
+        if let Ok(module) = Module::new(config, &mut u) {
+            let mut bytes = wasm_encoder::Module::HEADER.to_vec();
+            for (id, contents) in module.encode_sections() {
+                bytes.push(id);
+                contents.len().encode(&mut bytes);
+                bytes.extend_from_slice(&contents);
+            }
+            assert_eq!(bytes, module.to_bytes());
+        }
+    }
+}
+
+#[test]
+fn config_value_types() {
+    use wasm_encoder::ValType;
+
+    let config = Config::default();
+    let types = config.value_types();
+    assert!(types.contains(&ValType::F32));
+    assert!(types.contains(&ValType::F64));
+    assert!(types.contains(&ValType::V128));
+
+    let no_simd = Config {
+        simd_enabled: false,
+        ..config.clone()
+    }
+    .value_types();
+    assert!(!no_simd.contains(&ValType::V128));
+    assert!(no_simd.contains(&ValType::F32));
+
+    let no_floats = Config {
+        allow_floats: false,
+        ..config.clone()
+    }
+    .value_types();
+    assert!(!no_floats.contains(&ValType::F32));
+    assert!(!no_floats.contains(&ValType::F64));
+    assert!(no_floats.contains(&ValType::V128));
+    assert_eq!(no_floats.len(), types.len() - 2);
+}
+
 #[test]
 fn multi_value_disabled() {
     let mut rng = SmallRng::seed_from_u64(42);
@@ -187,20 +377,4706 @@ fn smoke_test_wasm_gc() {
 }
 
 #[test]
-fn smoke_test_wasm_custom_page_sizes() {
+#[cfg(feature = "wasmparser")]
+fn struct_get_cast_chains() {
+    use wasmparser::{HeapType, Operator, Parser, Payload};
+
+    let mut chains = 0;
+    for_each_module(
+        256,
+        |_| {
+            // Struct references rarely stay on the stack long enough to be read
+            // from when every kind of instruction is available.
+            Config {
+                gc_enabled: true,
+                reference_types_enabled: true,
+                allowed_instructions: InstructionKinds::new(&[
+                    InstructionKind::Aggregate,
+                    InstructionKind::Reference,
+                ]),
+                ..Config::default()
+            }
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                let ops = body
+                    .get_operators_reader()
+                    .unwrap()
+                    .into_iter()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                for window in ops.windows(3) {
+                    let [Operator::StructGet { .. }, cast, get] = window else {
+                        continue;
+                    };
+                    let cast_ty = match cast {
+                        Operator::RefCastNonNull {
+                            hty: HeapType::Concrete(ty),
+                        }
+                        | Operator::RefCastNullable {
+                            hty: HeapType::Concrete(ty),
+                        } => ty.as_module_index().unwrap(),
+                        _ => continue,
+                    };
+                    match get {
+                        Operator::StructGet {
+                            struct_type_index, ..
+                        }
+                        | Operator::StructGetS {
+                            struct_type_index, ..
+                        }
+                        | Operator::StructGetU {
+                            struct_type_index, ..
+                        } if *struct_type_index == cast_ty => chains += 1,
+                        _ => {}
+                    }
+                }
+            }
+        },
+    );
+    assert!(chains > 0);
+}
+
+#[test]
+fn new_matching_struct_type() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 16 * 1024];
+    rng.fill_bytes(&mut buf);
+    let mut u = Unstructured::new(&buf);
+    let config = Config {
+        gc_enabled: true,
+        reference_types_enabled: true,
+        ..Config::default()
+    };
+    let module =
+        Module::new_matching(config.clone(), &mut u, |m| m.num_struct_types() > 0, 100).unwrap();
+    assert!(module.num_struct_types() > 0);
+    let wasm_bytes = module.to_bytes();
+    let mut validator = Validator::new_with_features(WasmFeatures::all());
+    validate(&mut validator, &wasm_bytes);
+
+    // Running out of attempts is reported as an incorrect format.
+    let mut u = Unstructured::new(&buf);
+    let err = Module::new_matching(config, &mut u, |_| false, 3).unwrap_err();
+    assert!(matches!(err, arbitrary::Error::IncorrectFormat), "{err}");
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_gc_passive_element_expressions() {
+    use wasmparser::{ElementItems, ElementKind, Operator, Parser, Payload};
+
+    let mut gc_exprs = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            reference_types_enabled: true,
+            bulk_memory_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::ElementSection(reader) = payload.unwrap() else {
+                    continue;
+                };
+                for elem in reader {
+                    let elem = elem.unwrap();
+                    if !matches!(elem.kind, ElementKind::Passive | ElementKind::Declared) {
+                        continue;
+                    }
+                    let ElementItems::Expressions(_, exprs) = elem.items else {
+                        continue;
+                    };
+                    for expr in exprs {
+                        let allocates =
+                            expr.unwrap().get_operators_reader().into_iter().any(|op| {
+                                matches!(
+                                    op.unwrap(),
+                                    Operator::StructNew { .. }
+                                        | Operator::StructNewDefault { .. }
+                                        | Operator::ArrayNew { .. }
+                                        | Operator::ArrayNewDefault { .. }
+                                        | Operator::ArrayNewFixed { .. }
+                                )
+                            });
+                        if allocates {
+                            gc_exprs += 1;
+                        }
+                    }
+                }
+            }
+        },
+    );
+    assert!(gc_exprs > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn concrete_func_ref_table_elements() {
+    use wasmparser::{ElementItems, ElementKind, HeapType, Operator, Parser, Payload, TypeRef};
+
+    let mut checked_tables = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            reference_types_enabled: true,
+            bulk_memory_enabled: true,
+            concrete_func_table_element_ratio: 0.25,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut func_types = Vec::new();
+            let mut table_types = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            match import.unwrap().ty {
+                                TypeRef::Func(ty) => func_types.push(ty),
+                                TypeRef::Table(ty) => table_types.push(ty.element_type),
+                                _ => {}
+                            }
+                        }
+                    }
+                    Payload::FunctionSection(reader) => {
+                        func_types.extend(reader.into_iter().map(|t| t.unwrap()));
+                    }
+                    Payload::TableSection(reader) => {
+                        for table in reader {
+                            table_types.push(table.unwrap().ty.element_type);
+                        }
+                    }
+                    Payload::ElementSection(reader) => {
+                        for elem in reader {
+                            let elem = elem.unwrap();
+                            let ElementKind::Active { table_index, .. } = elem.kind else {
+                                continue;
+                            };
+                            let table_ty = table_types[table_index.unwrap_or(0) as usize];
+                            let HeapType::Concrete(table_ty) = table_ty.heap_type() else {
+                                continue;
+                            };
+                            let table_ty = table_ty.as_module_index().unwrap();
+                            let ElementItems::Expressions(elem_ty, exprs) = elem.items else {
+                                panic!("function indices in a segment for a concrete table");
+                            };
+                            let HeapType::Concrete(elem_ty) = elem_ty.heap_type() else {
+                                continue;
+                            };
+                            let elem_ty = elem_ty.as_module_index().unwrap();
+                            if elem_ty == table_ty {
+                                checked_tables += 1;
+                            }
+                            for expr in exprs {
+                                for op in expr.unwrap().get_operators_reader() {
+                                    if let Operator::RefFunc { function_index } = op.unwrap() {
+                                        assert_eq!(func_types[function_index as usize], elem_ty);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(checked_tables > 0);
+}
+
+#[test]
+fn new_tracked_reports_consumed_bytes() {
     let mut rng = SmallRng::seed_from_u64(0);
     let mut buf = vec![0; 2048];
-    for _ in 0..1024 {
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config::arbitrary(&mut u).unwrap();
+        let before = u.len();
+        let Ok((module, consumed)) = Module::new_tracked(config, &mut u) else {
+            continue;
+        };
+        assert_eq!(consumed, before - u.len());
+        module.to_bytes();
+    }
+}
+
+#[test]
+fn overlapping_memory_copies() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut overlapping = 0;
+    for i in 0..256 {
         rng.fill_bytes(&mut buf);
         let mut u = Unstructured::new(&buf);
         let config = Config {
-            custom_page_sizes_enabled: true,
+            min_memories: 1,
+            overlapping_copy_ratio: 1.0,
+            disallow_traps: i % 2 == 0,
             ..Config::default()
         };
-        if let Ok(module) = Module::new(config, &mut u) {
-            let wasm_bytes = module.to_bytes();
-            let mut validator = Validator::new_with_features(WasmFeatures::all());
-            validate(&mut validator, &wasm_bytes);
+        let disallow_traps = config.disallow_traps;
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                continue;
+            };
+            let ops = body
+                .get_operators_reader()
+                .unwrap()
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            for (j, op) in ops.iter().enumerate() {
+                let Operator::MemoryCopy { dst_mem, src_mem } = op else {
+                    continue;
+                };
+                let consts = ops[..j]
+                    .iter()
+                    .rev()
+                    .take(3)
+                    .filter_map(|op| match op {
+                        Operator::I32Const { value } => Some(u64::from(*value as u32)),
+                        Operator::I64Const { value } => Some(*value as u64),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let [len, src, dst] = consts[..] else {
+                    assert!(!disallow_traps, "non-constant copy without traps");
+                    continue;
+                };
+                if dst_mem == src_mem && dst.abs_diff(src) < len {
+                    overlapping += 1;
+                } else {
+                    assert!(!disallow_traps, "non-overlapping copy without traps");
+                }
+            }
+        }
+    }
+    assert!(overlapping > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn import_field_dictionary() {
+    use wasmparser::{Parser, Payload};
+
+    let dictionary = ["fd_write", "proc_exit", "args_get"];
+    let mut imports = 0;
+    for_each_module(
+        256,
+        |u| {
+            let mut config = Config::arbitrary(u).unwrap();
+            config.import_field_dictionary =
+                Some(dictionary.iter().map(|s| s.to_string()).collect());
+            config
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::ImportSection(reader) = payload.unwrap() else {
+                    continue;
+                };
+                for import in reader {
+                    let name = import.unwrap().name;
+                    let word = name.trim_end_matches(|c: char| c.is_ascii_digit());
+                    assert!(
+                        dictionary.contains(&word),
+                        "unexpected import name {name:?}"
+                    );
+                    imports += 1;
+                }
+            }
+        },
+    );
+    assert!(imports > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn data_content() {
+    use wasmparser::{Parser, Payload};
+
+    fn is_repeated_pattern(data: &[u8]) -> bool {
+        (1..=8).any(|n| data.iter().enumerate().all(|(i, b)| *b == data[i % n]))
+    }
+
+    for kind in [
+        DataContentKind::Random,
+        DataContentKind::Zeros,
+        DataContentKind::Ascii,
+        DataContentKind::RepeatedPattern,
+    ] {
+        let mut nonempty = 0;
+        let mut random_distinct = false;
+        for_each_module(
+            256,
+            |_| Config {
+                data_content: kind,
+                min_data_segments: 1,
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    let Payload::DataSection(reader) = payload.unwrap() else {
+                        continue;
+                    };
+                    for data in reader {
+                        let data = data.unwrap().data;
+                        if data.is_empty() {
+                            continue;
+                        }
+                        nonempty += 1;
+                        match kind {
+                            DataContentKind::Random => {
+                                random_distinct |= !is_repeated_pattern(data)
+                            }
+                            DataContentKind::Zeros => assert!(data.iter().all(|b| *b == 0)),
+                            DataContentKind::Ascii => {
+                                assert!(data.iter().all(|b| (0x20..=0x7e).contains(b)))
+                            }
+                            DataContentKind::RepeatedPattern => assert!(is_repeated_pattern(data)),
+                        }
+                    }
+                }
+            },
+        );
+        assert!(
+            nonempty > 100,
+            "{kind:?}: only {nonempty} non-empty segments"
+        );
+        if kind == DataContentKind::Random {
+            assert!(random_distinct);
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn used_func_types() {
+    use std::collections::HashSet;
+    use wasmparser::{CompositeInnerType, Parser, Payload, TypeRef};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.allow_invalid_funcs = false;
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let used = module.used_func_types();
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+
+        let mut func_types = HashSet::new();
+        let mut function_types = HashSet::new();
+        let mut type_index = 0;
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::TypeSection(reader) => {
+                    for rec_group in reader {
+                        for ty in rec_group.unwrap().into_types() {
+                            if let CompositeInnerType::Func(_) = ty.composite_type.inner {
+                                func_types.insert(type_index);
+                            }
+                            type_index += 1;
+                        }
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        if let TypeRef::Func(ty) = import.unwrap().ty {
+                            function_types.insert(ty);
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    for ty in reader {
+                        function_types.insert(ty.unwrap());
+                    }
+                }
+                _ => {}
+            }
+        }
+        assert!(used.is_subset(&func_types));
+        assert!(function_types.is_subset(&used));
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn trapping_indirect_calls() {
+    use wasmparser::{Operator, Parser, Payload, TypeRef};
+
+    let mut trapping = [0; 2];
+    for disallow_traps in [false, true] {
+        for_each_module(
+            1024,
+            |u| {
+                let mut config = Config::arbitrary(u).unwrap();
+                config.trapping_indirect_call_ratio = 1.0;
+                config.disallow_traps = disallow_traps;
+                config.allow_invalid_funcs = false;
+                config
+            },
+            |_, wasm_bytes| {
+                let mut table_maximums = Vec::new();
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    match payload.unwrap() {
+                        Payload::ImportSection(reader) => {
+                            for import in reader {
+                                if let TypeRef::Table(ty) = import.unwrap().ty {
+                                    table_maximums.push(ty.maximum);
+                                }
+                            }
+                        }
+                        Payload::TableSection(reader) => {
+                            for table in reader {
+                                table_maximums.push(table.unwrap().ty.maximum);
+                            }
+                        }
+                        Payload::CodeSectionEntry(body) => {
+                            let ops = body
+                                .get_operators_reader()
+                                .unwrap()
+                                .into_iter()
+                                .collect::<Result<Vec<_>, _>>()
+                                .unwrap();
+                            for (i, op) in ops.iter().enumerate() {
+                                let table = match op {
+                                    Operator::CallIndirect { table_index, .. }
+                                    | Operator::ReturnCallIndirect { table_index, .. } => {
+                                        *table_index
+                                    }
+                                    _ => continue,
+                                };
+                                assert!(!disallow_traps);
+                                let max = table_maximums[table as usize];
+                                let index = match ops[i - 1] {
+                                    Operator::TableGrow { table: grown } => {
+                                        assert_eq!(grown, table);
+                                        trapping[0] += 1;
+                                        continue;
+                                    }
+                                    Operator::I32Const { value } => u64::from(value as u32),
+                                    Operator::I64Const { value } => value as u64,
+                                    ref op => panic!("unexpected index operand: {op:?}"),
+                                };
+                                assert!(index >= max.unwrap_or(u64::from(u32::MAX)));
+                                trapping[1] += 1;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            },
+        );
+    }
+    assert!(trapping.iter().all(|&n| n > 0), "{trapping:?}");
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn all_sections_present() {
+    use wasmparser::{Parser, Payload};
+
+    let mut generated = 0;
+    for_each_module(
+        256,
+        |_| Config::all_sections_present(),
+        |_, wasm_bytes| {
+            generated += 1;
+
+            // Whether the type, import, function, table, memory, global, export,
+            // start, element, data, and code sections were each present.
+            let mut sections = [false; 11];
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let section = match payload.unwrap() {
+                    Payload::TypeSection(_) => 0,
+                    Payload::ImportSection(_) => 1,
+                    Payload::FunctionSection(_) => 2,
+                    Payload::TableSection(_) => 3,
+                    Payload::MemorySection(_) => 4,
+                    Payload::GlobalSection(_) => 5,
+                    Payload::ExportSection(_) => 6,
+                    Payload::StartSection { .. } => 7,
+                    Payload::ElementSection(_) => 8,
+                    Payload::DataSection(_) => 9,
+                    Payload::CodeSectionStart { .. } => 10,
+                    _ => continue,
+                };
+                sections[section] = true;
+            }
+            assert_eq!(
+                sections,
+                [true; 11],
+                "{}",
+                wasmprinter::print_bytes(wasm_bytes).unwrap()
+            );
+        },
+    );
+    assert!(generated > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn call_chain() {
+    use wasmparser::{ExternalKind, Operator, Parser, Payload, TypeRef};
+
+    const DEPTH: usize = 5;
+
+    let mut generated = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            call_chain_depth: Some(DEPTH),
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut num_imported_funcs = 0;
+            let mut exported_funcs = Vec::new();
+            let mut bodies = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Func(_) = import.unwrap().ty {
+                                num_imported_funcs += 1;
+                            }
+                        }
+                    }
+                    Payload::ExportSection(reader) => {
+                        for export in reader {
+                            let export = export.unwrap();
+                            if export.kind == ExternalKind::Func {
+                                exported_funcs.push(export.index);
+                            }
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        let ops = body
+                            .get_operators_reader()
+                            .unwrap()
+                            .into_iter()
+                            .collect::<Result<Vec<_>, _>>()
+                            .unwrap();
+                        bodies.push(ops);
+                    }
+                    _ => {}
+                }
+            }
+
+            // The chain is made up of the last `DEPTH` defined functions.
+            if bodies.len() < DEPTH {
+                return;
+            }
+            generated += 1;
+            let start = bodies.len() - DEPTH;
+            let head = num_imported_funcs + u32::try_from(start).unwrap();
+            assert!(exported_funcs.contains(&head));
+            for (i, ops) in bodies[start..].iter().enumerate() {
+                let next = head + u32::try_from(i).unwrap() + 1;
+                if i + 1 < DEPTH {
+                    assert!(
+                        matches!(
+                            &ops[..],
+                            [Operator::Call { function_index }, Operator::End]
+                                if *function_index == next
+                        ),
+                        "{ops:?}"
+                    );
+                } else {
+                    assert!(matches!(&ops[..], [Operator::End]), "{ops:?}");
+                }
+            }
+        },
+    );
+    assert!(generated > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn dead_code_after_control_transfer() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut dead_code = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            dead_code_ratio: 1.0,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                let ops = body
+                    .get_operators_reader()
+                    .unwrap()
+                    .into_iter()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                for pair in ops.windows(2) {
+                    if matches!(
+                        pair[0],
+                        Operator::Br { .. } | Operator::Return | Operator::Unreachable
+                    ) && !matches!(
+                        pair[1],
+                        Operator::End
+                            | Operator::Else
+                            | Operator::Catch { .. }
+                            | Operator::CatchAll
+                    ) {
+                        dead_code += 1;
+                    }
+                }
+            }
+        },
+    );
+    assert!(dead_code > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn generate_cabi_realloc() {
+    use wasmparser::{ExternalKind, FuncType, Operator, Parser, Payload, TypeRef, ValType};
+
+    /// Evaluates a call to the `cabi_realloc` body, which only uses a few
+    /// straight-line instructions and `if`s, returning `None` if it traps.
+    fn call(
+        ops: &[Operator<'_>],
+        args: [u32; 4],
+        globals: &mut [u64],
+        pages: &mut u64,
+        max_pages: u64,
+    ) -> Option<u32> {
+        let mut locals = vec![
+            args[0].into(),
+            args[1].into(),
+            args[2].into(),
+            args[3].into(),
+        ];
+        locals.extend([0, 0]);
+        let mut stack: Vec<u64> = Vec::new();
+        let mut pc = 0;
+        while pc < ops.len() {
+            let pop = |stack: &mut Vec<u64>| stack.pop().unwrap();
+            match &ops[pc] {
+                Operator::LocalGet { local_index } => stack.push(locals[*local_index as usize]),
+                Operator::LocalSet { local_index } => {
+                    locals[*local_index as usize] = pop(&mut stack)
+                }
+                Operator::LocalTee { local_index } => {
+                    locals[*local_index as usize] = *stack.last().unwrap()
+                }
+                Operator::GlobalGet { global_index } => stack.push(globals[*global_index as usize]),
+                Operator::GlobalSet { global_index } => {
+                    globals[*global_index as usize] = pop(&mut stack)
+                }
+                Operator::I32Const { value } => stack.push(u64::from(*value as u32)),
+                Operator::I64Const { value } => stack.push(*value as u64),
+                Operator::I32Eqz => {
+                    let a = pop(&mut stack);
+                    stack.push((a == 0).into());
+                }
+                Operator::I64ExtendI32U | Operator::I32WrapI64 => {
+                    let a = pop(&mut stack);
+                    stack.push(a & u64::from(u32::MAX));
+                }
+                Operator::MemorySize { .. } => stack.push(*pages),
+                Operator::MemoryGrow { .. } => {
+                    let delta = pop(&mut stack);
+                    if *pages + delta > max_pages {
+                        stack.push(u64::from(u32::MAX));
+                    } else {
+                        stack.push(*pages);
+                        *pages += delta;
+                    }
+                }
+                Operator::MemoryCopy { .. } => {
+                    let n = pop(&mut stack);
+                    let src = pop(&mut stack);
+                    let dst = pop(&mut stack);
+                    if src + n > *pages << 16 || dst + n > *pages << 16 {
+                        return None;
+                    }
+                }
+                Operator::Select => {
+                    let c = pop(&mut stack);
+                    let b = pop(&mut stack);
+                    let a = pop(&mut stack);
+                    stack.push(if c != 0 { a } else { b });
+                }
+                Operator::If { .. } => {
+                    if pop(&mut stack) == 0 {
+                        let mut depth = 0;
+                        loop {
+                            pc += 1;
+                            match ops[pc] {
+                                Operator::If { .. } => depth += 1,
+                                Operator::End if depth == 0 => break,
+                                Operator::End => depth -= 1,
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                Operator::End => {}
+                Operator::Return => break,
+                Operator::Unreachable => return None,
+                op => {
+                    let b = pop(&mut stack);
+                    let a = pop(&mut stack);
+                    let (a32, b32) = (a as u32, b as u32);
+                    stack.push(match op {
+                        Operator::I32Or => (a32 | b32).into(),
+                        Operator::I32LtU => (a32 < b32).into(),
+                        Operator::I32Eq => (a32 == b32).into(),
+                        Operator::I64Add => a.wrapping_add(b),
+                        Operator::I64Sub => a.wrapping_sub(b),
+                        Operator::I64And => a & b,
+                        Operator::I64Shl => a << b,
+                        Operator::I64ShrU => a >> b,
+                        Operator::I64GtU => (a > b).into(),
+                        Operator::I64LeU => (a <= b).into(),
+                        op => panic!("unexpected operator in `cabi_realloc`: {op:?}"),
+                    });
+                }
+            }
+            pc += 1;
+        }
+        Some(stack.pop().unwrap() as u32)
+    }
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut allocated = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let disallow_traps = u.arbitrary().unwrap();
+        let config = Config {
+            generate_cabi_realloc: true,
+            disallow_traps,
+            ..Config::default()
+        };
+        let features = config.features();
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        let mut validator = Validator::new_with_features(features);
+        let types = validate(&mut validator, &wasm_bytes);
+
+        let mut memories = Vec::new();
+        let mut globals = Vec::new();
+        let mut num_imported_funcs = 0;
+        let mut realloc = None;
+        let mut bodies = Vec::new();
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        match import.unwrap().ty {
+                            TypeRef::Func(_) => num_imported_funcs += 1,
+                            TypeRef::Memory(ty) => memories.push(ty),
+                            TypeRef::Global(_) => globals.push(0),
+                            _ => {}
+                        }
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    memories.extend(reader.into_iter().map(|m| m.unwrap()));
+                }
+                Payload::GlobalSection(reader) => {
+                    for global in reader {
+                        let mut init = global.unwrap().init_expr.get_operators_reader();
+                        globals.push(match init.read().unwrap() {
+                            Operator::I32Const { value } => u64::from(value as u32),
+                            _ => 0,
+                        });
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export.unwrap();
+                        if export.name == "cabi_realloc" {
+                            assert_eq!(export.kind, ExternalKind::Func);
+                            realloc = Some(export.index);
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => bodies.push(body),
+                _ => {}
+            }
+        }
+        // The function isn't added if there's no room for its type.
+        let Some(realloc) = realloc else {
+            continue;
+        };
+        let types = types.as_ref();
+        let func_type = types.core_function_at(realloc);
+        let expected = FuncType::new([ValType::I32; 4], [ValType::I32]);
+        assert_eq!(types[func_type].unwrap_func(), &expected);
+
+        let body = &bodies[(realloc - num_imported_funcs) as usize];
+        let ops = body
+            .get_operators_reader()
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let memory = ops
+            .iter()
+            .find_map(|op| match op {
+                Operator::MemorySize { mem } => Some(memories[*mem as usize]),
+                _ => None,
+            })
+            .unwrap();
+        let heap = ops
+            .iter()
+            .find_map(|op| match op {
+                Operator::GlobalGet { global_index } => Some(*global_index as usize),
+                _ => None,
+            })
+            .unwrap();
+        let mut pages = memory.initial;
+        let max_pages = memory.maximum.unwrap_or(65536).min(65536);
+
+        // Allocations should be bumped from the aligned heap pointer, and only
+        // fail when they don't fit in the memory.
+        for (align, size) in [(1, 3), (8, 16), (4, 1), (16, 100_000), (2, 0), (8, 8)] {
+            let expected = (globals[heap] + u64::from(align) - 1) & !(u64::from(align) - 1);
+            let end = expected + u64::from(size);
+            let fits = end <= u64::from(u32::MAX) && end <= max_pages << 16;
+            match call(
+                &ops,
+                [0, 0, align, size],
+                &mut globals,
+                &mut pages,
+                max_pages,
+            ) {
+                Some(ptr) if fits => {
+                    assert_ne!(ptr, 0);
+                    assert_eq!(u64::from(ptr), expected);
+                    assert_eq!(globals[heap], end);
+                    assert!(end <= pages << 16);
+                    allocated += 1;
+                }
+                None => {
+                    assert!(!fits && !disallow_traps);
+                    break;
+                }
+                Some(ptr) => {
+                    assert!(!fits && disallow_traps);
+                    assert_eq!(ptr, 0);
+                    break;
+                }
+            }
         }
     }
+    assert!(allocated > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn new_default_only_for_defaultable_types() {
+    use wasmparser::{CompositeInnerType, Operator, Parser, Payload, StorageType};
+
+    let is_defaultable = |ty: StorageType| match ty {
+        StorageType::I8 | StorageType::I16 => true,
+        StorageType::Val(ty) => ty.is_defaultable(),
+    };
+
+    // Generated struct and array types only have nullable reference fields,
+    // so import some types that aren't defaultable.
+    let available_imports = wat::parse_str(
+        r#"
+        (module
+            (type $d (struct (field i32)))
+            (type $s (struct (field (ref i31)) (field i64) (field (ref $d))))
+            (type $a (array (mut (ref $d))))
+            (import "env" "f" (func (param (ref $s) (ref $a))))
+        )
+        "#,
+    )
+    .unwrap();
+
+    let mut new_default = 0;
+    let mut explicit_new = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            reference_types_enabled: true,
+            available_imports: Some(available_imports.clone()),
+            allowed_instructions: InstructionKinds::new(&[
+                InstructionKind::Aggregate,
+                InstructionKind::Reference,
+            ]),
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            // Whether each type in the type section is a struct or array that can
+            // be created with `*.new_default`.
+            let mut defaultable = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::TypeSection(reader) => {
+                        for rec_group in reader {
+                            for ty in rec_group.unwrap().into_types() {
+                                defaultable.push(match ty.composite_type.inner {
+                                    CompositeInnerType::Struct(s) => {
+                                        s.fields.iter().all(|f| is_defaultable(f.element_type))
+                                    }
+                                    CompositeInnerType::Array(a) => {
+                                        is_defaultable(a.0.element_type)
+                                    }
+                                    _ => false,
+                                });
+                            }
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        for op in body.get_operators_reader().unwrap() {
+                            match op.unwrap() {
+                                Operator::StructNewDefault {
+                                    struct_type_index: ty,
+                                }
+                                | Operator::ArrayNewDefault {
+                                    array_type_index: ty,
+                                } => {
+                                    assert!(defaultable[ty as usize]);
+                                    new_default += 1;
+                                }
+                                Operator::StructNew {
+                                    struct_type_index: ty,
+                                }
+                                | Operator::ArrayNew {
+                                    array_type_index: ty,
+                                } if !defaultable[ty as usize] => {
+                                    explicit_new += 1;
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(new_default > 0);
+    assert!(explicit_new > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn concrete_table_element_types() {
+    use wasmparser::{CompositeInnerType, HeapType, Parser, Payload, TableInit};
+
+    let mut concrete_tables = 0;
+    let mut non_nullable_tables = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            reference_types_enabled: true,
+            concrete_table_element_ratio: 1.0,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            // Whether each type in the type section is a struct or array.
+            let mut is_aggregate = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::TypeSection(reader) => {
+                        for rec_group in reader {
+                            for ty in rec_group.unwrap().into_types() {
+                                is_aggregate.push(matches!(
+                                    ty.composite_type.inner,
+                                    CompositeInnerType::Struct(_) | CompositeInnerType::Array(_)
+                                ));
+                            }
+                        }
+                    }
+                    Payload::TableSection(reader) => {
+                        for table in reader {
+                            let table = table.unwrap();
+                            let element_type = table.ty.element_type;
+                            let HeapType::Concrete(idx) = element_type.heap_type() else {
+                                continue;
+                            };
+                            if !is_aggregate[idx.as_module_index().unwrap() as usize] {
+                                continue;
+                            }
+                            concrete_tables += 1;
+                            if !element_type.is_nullable() {
+                                assert!(matches!(table.init, TableInit::Expr(_)));
+                                non_nullable_tables += 1;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(concrete_tables > 0);
+    assert!(non_nullable_tables > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn partial_memory_init_ranges() {
+    use wasmparser::{DataKind, Operator, Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut partial = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let disallow_traps = u.arbitrary().unwrap();
+        let config = Config {
+            bulk_memory_enabled: true,
+            partial_memory_init_ratio: 1.0,
+            disallow_traps,
+            ..Config::default()
+        };
+        let features = config.features();
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        validate(&mut Validator::new_with_features(features), &wasm_bytes);
+
+        // The length of each data segment, if it's passive, and the function
+        // bodies, which come before the data section.
+        let mut passive_lens = Vec::new();
+        let mut bodies = Vec::new();
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::DataSection(reader) => {
+                    for data in reader {
+                        let data = data.unwrap();
+                        passive_lens.push(match data.kind {
+                            DataKind::Passive => Some(data.data.len() as u64),
+                            DataKind::Active { .. } => None,
+                        });
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let ops = body
+                        .get_operators_reader()
+                        .unwrap()
+                        .into_iter()
+                        .collect::<Result<Vec<_>, _>>()
+                        .unwrap();
+                    bodies.push(ops);
+                }
+                _ => {}
+            }
+        }
+
+        for ops in bodies {
+            if disallow_traps {
+                assert!(!ops.iter().any(|op| matches!(op, Operator::DataDrop { .. })));
+            }
+            for window in ops.windows(3) {
+                let [
+                    Operator::I32Const { value: src },
+                    Operator::I32Const { value: len },
+                    Operator::MemoryInit { data_index, .. },
+                ] = window
+                else {
+                    continue;
+                };
+                let Some(segment_len) = passive_lens[*data_index as usize] else {
+                    continue;
+                };
+                let (src, len) = (u64::from(*src as u32), u64::from(*len as u32));
+                if src + len <= segment_len && (src > 0 || len < segment_len) {
+                    partial += 1;
+                }
+            }
+        }
+    }
+    assert!(partial > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn canonical_duplicate_rec_groups() {
+    use wasmparser::{CompositeInnerType, HeapType, Operator, Parser, Payload, TypeRef, ValType};
+
+    let concrete = |ty: &ValType| match ty.as_reference_type()?.heap_type() {
+        HeapType::Concrete(i) => i.as_module_index(),
+        _ => None,
+    };
+
+    let mut interchanged = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            reference_types_enabled: true,
+            emit_canonical_duplicate_rec_groups: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            // The rec group that each type is in, as its start and length, and
+            // for function types taking and returning one concrete reference, the
+            // two types referenced.
+            let mut rec_groups = Vec::new();
+            let mut ref_pairs = Vec::new();
+            let mut func_types = Vec::new();
+            let mut num_imported_funcs = 0;
+            let mut bodies = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::TypeSection(reader) => {
+                        for rec_group in reader {
+                            let start = ref_pairs.len();
+                            let types = rec_group.unwrap().into_types();
+                            let len = types.len();
+                            for ty in types {
+                                rec_groups.push((start, len));
+                                ref_pairs.push(match &ty.composite_type.inner {
+                                    CompositeInnerType::Func(f) => {
+                                        match (f.params(), f.results()) {
+                                            ([param], [result]) => {
+                                                concrete(param).zip(concrete(result))
+                                            }
+                                            _ => None,
+                                        }
+                                    }
+                                    _ => None,
+                                });
+                            }
+                        }
+                    }
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Func(ty) = import.unwrap().ty {
+                                func_types.push(ty);
+                                num_imported_funcs += 1;
+                            }
+                        }
+                    }
+                    Payload::FunctionSection(reader) => {
+                        func_types.extend(reader.into_iter().map(|ty| ty.unwrap()));
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        let ops = body
+                            .get_operators_reader()
+                            .unwrap()
+                            .into_iter()
+                            .collect::<Result<Vec<_>, _>>()
+                            .unwrap();
+                        bodies.push(ops);
+                    }
+                    _ => {}
+                }
+            }
+
+            // A function returning its argument as the same type in another rec
+            // group is only valid if the two rec groups are canonically equal.
+            for (i, ops) in bodies.iter().enumerate() {
+                let ty = func_types[num_imported_funcs + i];
+                let Some((param, result)) = ref_pairs[ty as usize] else {
+                    continue;
+                };
+                if !matches!(
+                    &ops[..],
+                    [Operator::LocalGet { local_index: 0 }, Operator::End]
+                ) {
+                    continue;
+                }
+                let (param_start, param_len) = rec_groups[param as usize];
+                let (result_start, result_len) = rec_groups[result as usize];
+                if param_start != result_start
+                    && param_len == result_len
+                    && param as usize - param_start == result as usize - result_start
+                {
+                    interchanged += 1;
+                }
+            }
+        },
+    );
+    assert!(interchanged > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn imported_globals_with_concrete_reference_types() {
+    use wasmparser::{HeapType, Operator, Parser, Payload, TypeRef};
+
+    let mut concrete_imports = 0;
+    let mut concrete_reads = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            reference_types_enabled: true,
+            min_types: 1,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            // Whether each imported global has a concrete reference type.
+            let mut is_concrete = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            let TypeRef::Global(ty) = import.unwrap().ty else {
+                                continue;
+                            };
+                            let concrete = ty
+                                .content_type
+                                .as_reference_type()
+                                .is_some_and(|r| matches!(r.heap_type(), HeapType::Concrete(_)));
+                            concrete_imports += usize::from(concrete);
+                            is_concrete.push(concrete);
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        for op in body.get_operators_reader().unwrap() {
+                            if let Operator::GlobalGet { global_index } = op.unwrap() {
+                                if is_concrete.get(global_index as usize) == Some(&true) {
+                                    concrete_reads += 1;
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(concrete_imports > 0);
+    assert!(concrete_reads > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn no_refs_in_signatures() {
+    use wasmparser::{CompositeInnerType, Parser, Payload, TypeRef};
+
+    let mut ref_tables = 0;
+    let mut ref_globals = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            reference_types_enabled: true,
+            gc_enabled: true,
+            refs_in_signatures: false,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::TypeSection(reader) => {
+                        for rec_group in reader {
+                            for ty in rec_group.unwrap().into_types() {
+                                if let CompositeInnerType::Func(f) = ty.composite_type.inner {
+                                    assert!(f.params().iter().all(|t| !t.is_reference_type()));
+                                    assert!(f.results().iter().all(|t| !t.is_reference_type()));
+                                }
+                            }
+                        }
+                    }
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            match import.unwrap().ty {
+                                TypeRef::Table(_) => ref_tables += 1,
+                                TypeRef::Global(g) if g.content_type.is_reference_type() => {
+                                    ref_globals += 1
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    Payload::TableSection(reader) => ref_tables += reader.count(),
+                    Payload::GlobalSection(reader) => {
+                        for global in reader {
+                            if global.unwrap().ty.content_type.is_reference_type() {
+                                ref_globals += 1;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(ref_tables > 0);
+    assert!(ref_globals > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn parameterized_loops() {
+    use wasmparser::{BlockType, CompositeInnerType, Operator, Parser, Payload};
+
+    let mut param_loops = 0;
+    let mut back_branches = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            multi_value_enabled: true,
+            parameterized_loop_ratio: 1.0,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut param_counts = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::TypeSection(reader) => {
+                        for rec_group in reader {
+                            for ty in rec_group.unwrap().into_types() {
+                                param_counts.push(match ty.composite_type.inner {
+                                    CompositeInnerType::Func(f) => f.params().len(),
+                                    _ => 0,
+                                });
+                            }
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        // Whether each enclosing label is a loop with parameters.
+                        let mut labels = vec![false];
+                        for op in body.get_operators_reader().unwrap() {
+                            match op.unwrap() {
+                                Operator::Loop {
+                                    blockty: BlockType::FuncType(ty),
+                                } if param_counts[ty as usize] > 0 => {
+                                    param_loops += 1;
+                                    labels.push(true);
+                                }
+                                Operator::Block { .. }
+                                | Operator::Loop { .. }
+                                | Operator::If { .. }
+                                | Operator::Try { .. }
+                                | Operator::TryTable { .. } => labels.push(false),
+                                Operator::End => {
+                                    labels.pop();
+                                }
+                                Operator::Br { relative_depth }
+                                | Operator::BrIf { relative_depth } => {
+                                    if labels[labels.len() - 1 - relative_depth as usize] {
+                                        back_branches += 1;
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(param_loops > 0);
+    assert!(back_branches > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn legacy_delegate_targets() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let (mut to_try, mut to_function) = (0, 0);
+    for_each_module(
+        256,
+        |_| Config {
+            exceptions_enabled: true,
+            legacy_exceptions: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                // Whether each enclosing frame is the body of a `try` block. The
+                // first frame is the function's own.
+                let mut frames = vec![false];
+                for op in body.get_operators_reader().unwrap() {
+                    match op.unwrap() {
+                        Operator::Try { .. } => frames.push(true),
+                        Operator::Block { .. }
+                        | Operator::Loop { .. }
+                        | Operator::If { .. }
+                        | Operator::TryTable { .. } => frames.push(false),
+                        Operator::Catch { .. } | Operator::CatchAll => {
+                            *frames.last_mut().unwrap() = false;
+                        }
+                        Operator::End => {
+                            frames.pop();
+                        }
+                        Operator::Delegate { relative_depth } => {
+                            frames.pop();
+                            let target = frames.len() - 1 - relative_depth as usize;
+                            if target == 0 {
+                                to_function += 1;
+                            } else {
+                                assert!(frames[target], "delegate to a non-`try` frame");
+                                to_try += 1;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        },
+    );
+    assert!(to_try > 0);
+    assert!(to_function > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn min_data_bytes() {
+    use wasmparser::{Parser, Payload};
+
+    for disallow_traps in [false, true] {
+        for_each_module(
+            256,
+            |_| {
+                // Bulk memory is enabled, so passive segments can always hold the
+                // floor.
+                Config {
+                    min_data_bytes: Some(100_000),
+                    bulk_memory_enabled: true,
+                    disallow_traps,
+                    ..Config::default()
+                }
+            },
+            |_, wasm_bytes| {
+                let mut total = 0;
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    if let Payload::DataSection(reader) = payload.unwrap() {
+                        for data in reader {
+                            total += data.unwrap().data.len();
+                        }
+                    }
+                }
+                assert!(total >= 100_000, "only {total} bytes of data");
+            },
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn table_get_set_on_concrete_tables() {
+    use wasmparser::{HeapType, Operator, Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let (mut gets, mut sets) = (0, 0);
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            gc_enabled: true,
+            reference_types_enabled: true,
+            concrete_table_element_ratio: 1.0,
+            ..Config::default()
+        };
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        let types = validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+        let types = types.as_ref();
+        let is_concrete = |table: u32| {
+            matches!(
+                types.table_at(table).element_type.heap_type(),
+                HeapType::Concrete(_)
+            )
+        };
+
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                continue;
+            };
+            for op in body.get_operators_reader().unwrap() {
+                match op.unwrap() {
+                    Operator::TableGet { table } if is_concrete(table) => gets += 1,
+                    Operator::TableSet { table } if is_concrete(table) => sets += 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+    assert!(gets > 0);
+    assert!(sets > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn shared_memory_without_atomics() {
+    use wasmparser::{Parser, Payload, TypeRef};
+
+    let mut shared_memories = 0;
+    let mut accesses = 0;
+    for_each_module(
+        256,
+        |_| {
+            let config = Config {
+                threads_enabled: true,
+                shared_everything_threads_enabled: true,
+                allow_atomics: false,
+                min_memories: 1,
+                ..Config::default()
+            };
+            let features = config.features();
+            assert!(features.contains(WasmFeatures::THREADS));
+            config
+        },
+        |_, wasm_bytes| {
+            let mut shared = false;
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Memory(ty) = import.unwrap().ty {
+                                shared |= ty.shared;
+                            }
+                        }
+                    }
+                    Payload::MemorySection(reader) => {
+                        for ty in reader {
+                            shared |= ty.unwrap().shared;
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        for op in body.get_operators_reader().unwrap() {
+                            let op = op.unwrap();
+                            let name = format!("{op:?}");
+                            assert!(!name.contains("Atomic"), "unexpected {op:?}");
+                            accesses += usize::from(
+                                shared && (name.contains("Load") || name.contains("Store")),
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            shared_memories += usize::from(shared);
+        },
+    );
+    assert!(shared_memories > 0);
+    assert!(accesses > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn extended_const_data_offsets() {
+    use wasmparser::{DataKind, Operator, Parser, Payload, TypeRef};
+
+    for disallow_traps in [false, true] {
+        let mut extended = 0;
+        for_each_module(
+            256,
+            |_| Config {
+                extended_const_enabled: true,
+                extended_const_data_offset_ratio: 1.0,
+                disallow_traps,
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                let mut memory_sizes = Vec::new();
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    match payload.unwrap() {
+                        Payload::ImportSection(reader) => {
+                            for import in reader {
+                                if let TypeRef::Memory(ty) = import.unwrap().ty {
+                                    memory_sizes
+                                        .push(ty.initial << ty.page_size_log2.unwrap_or(16));
+                                }
+                            }
+                        }
+                        Payload::MemorySection(reader) => {
+                            for ty in reader {
+                                let ty = ty.unwrap();
+                                memory_sizes.push(ty.initial << ty.page_size_log2.unwrap_or(16));
+                            }
+                        }
+                        Payload::DataSection(reader) => {
+                            for data in reader {
+                                let data = data.unwrap();
+                                let DataKind::Active {
+                                    memory_index,
+                                    offset_expr,
+                                } = data.kind
+                                else {
+                                    continue;
+                                };
+                                let ops = offset_expr
+                                    .get_operators_reader()
+                                    .into_iter()
+                                    .collect::<Result<Vec<_>, _>>()
+                                    .unwrap();
+                                // Extended offsets are more than a single
+                                // instruction and the trailing `end`.
+                                if ops.len() > 2 {
+                                    extended += 1;
+                                }
+                                if !disallow_traps {
+                                    continue;
+                                }
+
+                                // Evaluate the offset, which is only made of
+                                // constants when traps are disallowed.
+                                let mut stack: Vec<u64> = Vec::new();
+                                for op in ops {
+                                    match op {
+                                        Operator::I32Const { value } => {
+                                            stack.push(value as u32 as u64)
+                                        }
+                                        Operator::I64Const { value } => stack.push(value as u64),
+                                        Operator::I32Add | Operator::I32Sub => {
+                                            let b = stack.pop().unwrap() as u32;
+                                            let a = stack.pop().unwrap() as u32;
+                                            stack.push(u64::from(if op == Operator::I32Add {
+                                                a.wrapping_add(b)
+                                            } else {
+                                                a.wrapping_sub(b)
+                                            }));
+                                        }
+                                        Operator::I64Add => {
+                                            let b = stack.pop().unwrap();
+                                            let a = stack.pop().unwrap();
+                                            stack.push(a.wrapping_add(b));
+                                        }
+                                        Operator::I64Sub => {
+                                            let b = stack.pop().unwrap();
+                                            let a = stack.pop().unwrap();
+                                            stack.push(a.wrapping_sub(b));
+                                        }
+                                        Operator::End => {}
+                                        op => panic!("unexpected {op:?}"),
+                                    }
+                                }
+                                let offset = stack.pop().unwrap();
+                                let end = offset + data.data.len() as u64;
+                                assert!(end <= memory_sizes[memory_index as usize]);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            },
+        );
+        assert!(extended > 0);
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn all_valtypes_in_locals() {
+    use wasmparser::{Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut checked = 0;
+    for gc_enabled in [false, true] {
+        for _ in 0..512 {
+            rng.fill_bytes(&mut buf);
+            let mut u = Unstructured::new(&buf);
+            let config = Config {
+                all_valtypes_in_locals: true,
+                gc_enabled,
+                min_funcs: 1,
+                ..Config::default()
+            };
+            let expected = config.value_types();
+            let Ok(module) = Module::new(config, &mut u) else {
+                continue;
+            };
+            let wasm_bytes = module.to_bytes();
+            validate(
+                &mut Validator::new_with_features(WasmFeatures::all()),
+                &wasm_bytes,
+            );
+
+            let body =
+                Parser::new(0)
+                    .parse_all(&wasm_bytes)
+                    .find_map(|payload| match payload.unwrap() {
+                        Payload::CodeSectionEntry(body) => Some(body),
+                        _ => None,
+                    });
+            let Some(body) = body else {
+                continue;
+            };
+            let mut locals = Vec::new();
+            for local in body.get_locals_reader().unwrap() {
+                let (_, ty) = local.unwrap();
+                locals.push(wasm_encoder::ValType::try_from(ty).unwrap());
+            }
+            for ty in &expected {
+                assert!(locals.contains(ty), "missing local of type {ty:?}");
+            }
+            checked += 1;
+        }
+    }
+    assert!(checked > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn v128_load_splats() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut seen = [false; 4];
+    for_each_module(
+        256,
+        |_| Config {
+            simd_enabled: true,
+            min_memories: 1,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                for op in body.get_operators_reader().unwrap() {
+                    match op.unwrap() {
+                        Operator::V128Load8Splat { .. } => seen[0] = true,
+                        Operator::V128Load16Splat { .. } => seen[1] = true,
+                        Operator::V128Load32Splat { .. } => seen[2] = true,
+                        Operator::V128Load64Splat { .. } => seen[3] = true,
+                        _ => {}
+                    }
+                }
+            }
+        },
+    );
+    assert_eq!(seen, [true; 4]);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn atomic_alignment() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut modules = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        // Generation panics if an atomic memory access is misaligned.
+        let config = Config {
+            threads_enabled: true,
+            assert_atomic_alignment: true,
+            min_memories: 1,
+            ..Config::default()
+        };
+        let features = config.features();
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        // The validator also rejects atomics without their natural alignment.
+        validate(&mut Validator::new_with_features(features), &wasm_bytes);
+        modules += 1;
+    }
+    assert!(modules > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn export_all_kinds() {
+    use wasmparser::{ExternalKind, Parser, Payload, TypeRef};
+
+    let mut all_five = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            export_all_kinds: true,
+            exceptions_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            // The number of items of each kind, and whether each kind is exported,
+            // indexed by `ExternalKind`.
+            let index = |kind| match kind {
+                ExternalKind::Func => 0,
+                ExternalKind::Table => 1,
+                ExternalKind::Memory => 2,
+                ExternalKind::Global => 3,
+                ExternalKind::Tag => 4,
+            };
+            let mut counts = [0; 5];
+            let mut exported = [false; 5];
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            counts[match import.unwrap().ty {
+                                TypeRef::Func(_) => 0,
+                                TypeRef::Table(_) => 1,
+                                TypeRef::Memory(_) => 2,
+                                TypeRef::Global(_) => 3,
+                                TypeRef::Tag(_) => 4,
+                            }] += 1;
+                        }
+                    }
+                    Payload::FunctionSection(reader) => counts[0] += reader.count(),
+                    Payload::TableSection(reader) => counts[1] += reader.count(),
+                    Payload::MemorySection(reader) => counts[2] += reader.count(),
+                    Payload::GlobalSection(reader) => counts[3] += reader.count(),
+                    Payload::TagSection(reader) => counts[4] += reader.count(),
+                    Payload::ExportSection(reader) => {
+                        for export in reader {
+                            exported[index(export.unwrap().kind)] = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            for (count, exported) in counts.iter().zip(exported) {
+                assert_eq!(*count > 0, exported);
+            }
+            if counts.iter().all(|c| *c > 0) {
+                all_five += 1;
+            }
+        },
+    );
+    assert!(all_five > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn string_alphabet() {
+    use wasmparser::{Parser, Payload};
+
+    for alphabet in [
+        StringAlphabet::Ascii,
+        StringAlphabet::Alphanumeric,
+        StringAlphabet::Utf8,
+    ] {
+        let allowed = |c: char| match alphabet {
+            StringAlphabet::Ascii => (' '..='~').contains(&c),
+            StringAlphabet::Alphanumeric => c.is_ascii_alphanumeric(),
+            StringAlphabet::Utf8 => true,
+        };
+        let mut chars = 0;
+        for_each_module(
+            256,
+            |_| Config {
+                string_alphabet: Some(alphabet),
+                min_imports: 1,
+                min_exports: 1,
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                let mut names = Vec::new();
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    match payload.unwrap() {
+                        Payload::ImportSection(reader) => {
+                            for import in reader {
+                                let import = import.unwrap();
+                                names.push(import.module);
+                                names.push(import.name);
+                            }
+                        }
+                        Payload::ExportSection(reader) => {
+                            for export in reader {
+                                names.push(export.unwrap().name);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                for name in names {
+                    assert!(name.chars().all(allowed), "{alphabet:?}: {name:?}");
+                    chars += name.len();
+                }
+            },
+        );
+        assert!(chars > 0);
+    }
+}
+
+#[test]
+fn smoke_test_wasm_custom_page_sizes() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..1024 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            custom_page_sizes_enabled: true,
+            ..Config::default()
+        };
+        if let Ok(module) = Module::new(config, &mut u) {
+            let wasm_bytes = module.to_bytes();
+            let mut validator = Validator::new_with_features(WasmFeatures::all());
+            validate(&mut validator, &wasm_bytes);
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn disallow_traps_prefers_saturating_truncation() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    fn count_truncations(wasm: &[u8]) -> (usize, usize) {
+        let (mut trapping, mut saturating) = (0, 0);
+        for payload in Parser::new(0).parse_all(wasm) {
+            let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                continue;
+            };
+            for op in body.get_operators_reader().unwrap() {
+                match op.unwrap() {
+                    Operator::I32TruncF32S
+                    | Operator::I32TruncF32U
+                    | Operator::I32TruncF64S
+                    | Operator::I32TruncF64U
+                    | Operator::I64TruncF32S
+                    | Operator::I64TruncF32U
+                    | Operator::I64TruncF64S
+                    | Operator::I64TruncF64U => trapping += 1,
+                    Operator::I32TruncSatF32S
+                    | Operator::I32TruncSatF32U
+                    | Operator::I32TruncSatF64S
+                    | Operator::I32TruncSatF64U
+                    | Operator::I64TruncSatF32S
+                    | Operator::I64TruncSatF32U
+                    | Operator::I64TruncSatF64S
+                    | Operator::I64TruncSatF64U => saturating += 1,
+                    _ => {}
+                }
+            }
+        }
+        (trapping, saturating)
+    }
+
+    for disallow_traps in [true, false] {
+        let (mut trapping, mut saturating) = (0, 0);
+        for_each_module(
+            256,
+            |_| Config {
+                disallow_traps,
+                saturating_float_to_int_enabled: true,
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                let (t, s) = count_truncations(wasm_bytes);
+                trapping += t;
+                saturating += s;
+            },
+        );
+        assert!(saturating > 0);
+        if disallow_traps {
+            assert_eq!(trapping, 0);
+        } else {
+            assert!(trapping > 0);
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_deep_branches() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut deep_branches = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            deep_branch_ratio: 1.0,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                for op in body.get_operators_reader().unwrap() {
+                    match op.unwrap() {
+                        Operator::Br { relative_depth } | Operator::BrIf { relative_depth }
+                            if relative_depth > 1 =>
+                        {
+                            deep_branches += 1
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        },
+    );
+    assert!(deep_branches > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_atomic_fence_without_memory() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut fences = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            threads_enabled: true,
+            max_memories: 0,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::MemorySection(_) => panic!("unexpected memory section"),
+                    Payload::CodeSectionEntry(body) => {
+                        for op in body.get_operators_reader().unwrap() {
+                            if let Operator::AtomicFence = op.unwrap() {
+                                fences += 1;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(fences > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_legacy_exceptions() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let (mut catches, mut delegates, mut rethrows) = (0, 0, 0);
+    for_each_module(
+        256,
+        |_| Config {
+            exceptions_enabled: true,
+            legacy_exceptions: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                for op in body.get_operators_reader().unwrap() {
+                    match op.unwrap() {
+                        Operator::Catch { .. } | Operator::CatchAll => catches += 1,
+                        Operator::Delegate { .. } => delegates += 1,
+                        Operator::Rethrow { .. } => rethrows += 1,
+                        op @ (Operator::TryTable { .. } | Operator::ThrowRef) => {
+                            panic!("unexpected non-legacy instruction: {op:?}")
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        },
+    );
+    assert!(catches > 0);
+    assert!(delegates > 0);
+    assert!(rethrows > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_typed_select_v128() {
+    use wasmparser::{Operator, Parser, Payload, ValType};
+
+    let mut selects = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            simd_enabled: true,
+            reference_types_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                for op in body.get_operators_reader().unwrap() {
+                    if let Operator::TypedSelect { ty: ValType::V128 } = op.unwrap() {
+                        selects += 1;
+                    }
+                }
+            }
+        },
+    );
+    assert!(selects > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_definitions_not_allowed() {
+    use wasmparser::{Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut imports = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let Ok(mut config) = Config::arbitrary(&mut u) else {
+            continue;
+        };
+        config.definitions_allowed = false;
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        let mut validator = Validator::new_with_features(WasmFeatures::all());
+        validate(&mut validator, &wasm_bytes);
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::ImportSection(reader) => imports += reader.count(),
+                Payload::FunctionSection(_)
+                | Payload::TableSection(_)
+                | Payload::MemorySection(_)
+                | Payload::GlobalSection(_)
+                | Payload::TagSection(_)
+                | Payload::ElementSection(_)
+                | Payload::DataSection(_)
+                | Payload::CodeSectionStart { .. } => panic!("unexpected definitions"),
+                _ => {}
+            }
+        }
+    }
+    assert!(imports > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn emit_data_count_section() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    // Returns whether the module has a data count section and whether any of
+    // its functions refer to a data segment.
+    fn inspect(wasm_bytes: &[u8]) -> (bool, bool) {
+        let (mut has_data_count, mut uses_data) = (false, false);
+        for payload in Parser::new(0).parse_all(wasm_bytes) {
+            match payload.unwrap() {
+                Payload::DataCountSection { .. } => has_data_count = true,
+                Payload::CodeSectionEntry(body) => {
+                    for op in body.get_operators_reader().unwrap() {
+                        if let Operator::MemoryInit { .. } | Operator::DataDrop { .. } = op.unwrap()
+                        {
+                            uses_data = true;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        (has_data_count, uses_data)
+    }
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut invalid_without_data_count = 0;
+    for _ in 0..512 {
+        rng.fill_bytes(&mut buf);
+        let generate = |emit_data_count_section| {
+            let mut u = Unstructured::new(&buf);
+            let mut config = Config::arbitrary(&mut u).unwrap();
+            config.bulk_memory_enabled = true;
+            config.emit_data_count_section = emit_data_count_section;
+            Module::new(config, &mut u).unwrap().to_bytes()
+        };
+
+        let wasm_bytes = generate(None);
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+        let (has_data_count, uses_data) = inspect(&wasm_bytes);
+        assert_eq!(has_data_count, uses_data);
+
+        let (has_data_count, _) = inspect(&generate(Some(true)));
+        assert!(has_data_count);
+
+        let wasm_bytes = generate(Some(false));
+        let (has_data_count, _) = inspect(&wasm_bytes);
+        assert!(!has_data_count);
+        let valid = Validator::new_with_features(WasmFeatures::all())
+            .validate_all(&wasm_bytes)
+            .is_ok();
+        assert_eq!(valid, !uses_data);
+        if !valid {
+            invalid_without_data_count += 1;
+        }
+    }
+    assert!(invalid_without_data_count > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_use_all_locals() {
+    use wasmparser::{CompositeInnerType, Operator, Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut checked_locals = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.use_all_locals = true;
+        let module = Module::new(config, &mut u).unwrap();
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+
+        let mut param_counts = Vec::new();
+        let mut func_types = Vec::new();
+        let mut defined_funcs = 0;
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::TypeSection(reader) => {
+                    for group in reader {
+                        for ty in group.unwrap().into_types() {
+                            param_counts.push(match &ty.composite_type.inner {
+                                CompositeInnerType::Func(f) => f.params().len(),
+                                _ => 0,
+                            });
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    func_types = reader.into_iter().map(|t| t.unwrap()).collect();
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let ty = func_types[defined_funcs] as usize;
+                    defined_funcs += 1;
+                    let mut n = param_counts[ty];
+                    for local in body.get_locals_reader().unwrap() {
+                        n += local.unwrap().0 as usize;
+                    }
+                    let mut read = vec![false; n];
+                    let mut written = vec![false; n];
+                    for op in body.get_operators_reader().unwrap() {
+                        match op.unwrap() {
+                            Operator::LocalGet { local_index } => {
+                                read[local_index as usize] = true;
+                            }
+                            Operator::LocalSet { local_index }
+                            | Operator::LocalTee { local_index } => {
+                                written[local_index as usize] = true;
+                            }
+                            _ => {}
+                        }
+                    }
+                    assert!(read.iter().all(|r| *r), "unread local");
+                    assert!(written.iter().all(|w| *w), "unwritten local");
+                    checked_locals += n;
+                }
+                _ => {}
+            }
+        }
+    }
+    assert!(checked_locals > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn max_type_size_counts_nested_ref_types() {
+    use std::collections::HashSet;
+    use wasmparser::types::{CoreTypeId, Types};
+    use wasmparser::{
+        CompositeInnerType, ExternalKind, HeapType, Parser, Payload, TypeRef, UnpackedIndex,
+        ValType,
+    };
+
+    // Mirrors wasm-smith's accounting of the structural size of the concrete
+    // type that `ty` refers to.
+    fn nested(types: &Types, ty: ValType, seen: &mut HashSet<CoreTypeId>) -> u32 {
+        let Some(HeapType::Concrete(UnpackedIndex::Id(id))) =
+            ty.as_reference_type().map(|r| r.heap_type())
+        else {
+            return 0;
+        };
+        if !seen.insert(id) {
+            return 0;
+        }
+        let tys: Vec<ValType> = match &types[id].composite_type.inner {
+            CompositeInnerType::Func(f) => f.params().iter().chain(f.results()).copied().collect(),
+            CompositeInnerType::Struct(s) => {
+                s.fields.iter().map(|f| f.element_type.unpack()).collect()
+            }
+            CompositeInnerType::Array(a) => vec![a.0.element_type.unpack()],
+            CompositeInnerType::Cont(_) => Vec::new(),
+        };
+        1 + tys.len() as u32 + tys.into_iter().map(|t| nested(types, t, seen)).sum::<u32>()
+    }
+
+    fn entity_size(types: &Types, kind: ExternalKind, index: u32) -> u32 {
+        let mut seen = HashSet::new();
+        match kind {
+            ExternalKind::Func => {
+                let f = types[types.as_ref().core_function_at(index)].unwrap_func();
+                let tys = f.params().iter().chain(f.results());
+                1 + tys.clone().count() as u32
+                    + tys.map(|t| nested(types, *t, &mut seen)).sum::<u32>()
+            }
+            ExternalKind::Tag => {
+                let f = types[types.as_ref().tag_at(index)].unwrap_func();
+                1 + f.params().len() as u32
+                    + f.params()
+                        .iter()
+                        .map(|t| nested(types, *t, &mut seen))
+                        .sum::<u32>()
+            }
+            ExternalKind::Global => {
+                1 + nested(
+                    types,
+                    types.as_ref().global_at(index).content_type,
+                    &mut seen,
+                )
+            }
+            ExternalKind::Table => {
+                let ty = ValType::Ref(types.as_ref().table_at(index).element_type);
+                1 + nested(types, ty, &mut seen)
+            }
+            _ => 1,
+        }
+    }
+
+    let max_type_size = 30;
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut saw_nested = false;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.reference_types_enabled = true;
+        config.gc_enabled = true;
+        config.max_type_size = max_type_size;
+        config.concrete_heap_type_ratio = Some((1, 1));
+        config.available_imports = None;
+        config.exports = None;
+        config.module_shape = None;
+        config.export_everything = false;
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        let types = validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+
+        let mut total = 0;
+        let mut counts = [0; 5];
+        let mut count_entity = |kind: ExternalKind, index: u32| {
+            let size = entity_size(&types, kind, index);
+            let flat = match kind {
+                ExternalKind::Func => {
+                    let f = types[types.as_ref().core_function_at(index)].unwrap_func();
+                    1 + (f.params().len() + f.results().len()) as u32
+                }
+                _ => 1,
+            };
+            saw_nested |= size > flat;
+            total += 1 + size;
+        };
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        let (kind, slot) = match import.unwrap().ty {
+                            TypeRef::Func(_) => (ExternalKind::Func, 0),
+                            TypeRef::Table(_) => (ExternalKind::Table, 1),
+                            TypeRef::Memory(_) => (ExternalKind::Memory, 2),
+                            TypeRef::Global(_) => (ExternalKind::Global, 3),
+                            TypeRef::Tag(_) => (ExternalKind::Tag, 4),
+                        };
+                        count_entity(kind, counts[slot]);
+                        counts[slot] += 1;
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export.unwrap();
+                        count_entity(export.kind, export.index);
+                    }
+                }
+                _ => {}
+            }
+        }
+        assert!(
+            total <= max_type_size,
+            "type size {total} exceeds {max_type_size}"
+        );
+    }
+    assert!(saw_nested);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn config_serde_round_trip() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config::arbitrary(&mut u).unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        let round_tripped: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(format!("{config:?}"), format!("{round_tripped:?}"));
+    }
+
+    // Deserialized configurations are sanitized.
+    let config = Config {
+        reference_types_enabled: false,
+        gc_enabled: true,
+        ..Config::default()
+    };
+    let json = serde_json::to_string(&config).unwrap();
+    let config: Config = serde_json::from_str(&json).unwrap();
+    assert!(!config.gc_enabled);
+
+    // Example modules can't be serialized, rather than being silently dropped.
+    let config = Config {
+        module_shape: Some(wat::parse_str("(module)").unwrap()),
+        ..Config::default()
+    };
+    let err = serde_json::to_string(&config).unwrap_err();
+    assert!(err.to_string().contains("module_shape"), "{err}");
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn config_for_feature_set() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    // Without `FLOATS` no float instructions are allowed either.
+    let config = Config::for_feature_set(WasmFeatures::SIMD | WasmFeatures::BULK_MEMORY);
+    assert!(config.simd_enabled && config.bulk_memory_enabled);
+    assert!(!config.allow_floats && !config.reference_types_enabled);
+
+    let features = WasmFeatures::WASM1 | WasmFeatures::SIMD | WasmFeatures::BULK_MEMORY;
+    let config = Config::for_feature_set(features);
+    assert_eq!(config.features(), features);
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let module = Module::new(config.clone(), &mut u).unwrap();
+        let wasm_bytes = module.to_bytes();
+        validate(&mut Validator::new_with_features(features), &wasm_bytes);
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                for op in body.get_operators_reader().unwrap() {
+                    match op.unwrap() {
+                        Operator::RefNull { .. }
+                        | Operator::RefIsNull
+                        | Operator::RefFunc { .. }
+                        | Operator::TypedSelect { .. }
+                        | Operator::TableGet { .. }
+                        | Operator::TableSet { .. }
+                        | Operator::TableGrow { .. }
+                        | Operator::TableSize { .. }
+                        | Operator::TableFill { .. } => panic!("unexpected reference-types op"),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_extended_const_global_get() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut saw_mixed = false;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.extended_const_enabled = true;
+        let module = Module::new(config, &mut u).unwrap();
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            if let Payload::GlobalSection(reader) = payload.unwrap() {
+                for global in reader {
+                    let (mut global_get, mut arith) = (false, false);
+                    for op in global.unwrap().init_expr.get_operators_reader() {
+                        match op.unwrap() {
+                            Operator::GlobalGet { .. } => global_get = true,
+                            Operator::I32Add
+                            | Operator::I32Sub
+                            | Operator::I32Mul
+                            | Operator::I64Add
+                            | Operator::I64Sub
+                            | Operator::I64Mul => arith = true,
+                            _ => {}
+                        }
+                    }
+                    saw_mixed |= global_get && arith;
+                }
+            }
+        }
+    }
+    assert!(saw_mixed);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn simd_opcode_coverage() {
+    use std::collections::BTreeSet;
+    use wasmparser::{Parser, Payload};
+
+    // Collect the names of every operator in the `simd` proposal that the
+    // validator knows about.
+    macro_rules! simd_opcodes {
+        ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident ($($ann:tt)*) )*) => {{
+            let mut ops = BTreeSet::new();
+            $(
+                if stringify!($proposal) == "simd" {
+                    ops.insert(stringify!($op));
+                }
+            )*
+            ops
+        }};
+    }
+    let known = wasmparser::for_each_visit_simd_operator!(simd_opcodes);
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 4096];
+    let mut emitted = BTreeSet::new();
+    for _ in 0..2048 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.simd_enabled = true;
+        let module = Module::new(config, &mut u).unwrap();
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                for op in body.get_operators_reader().unwrap() {
+                    let op = format!("{:?}", op.unwrap());
+                    let name = op.split([' ', '{']).next().unwrap().to_string();
+                    emitted.insert(name);
+                }
+            }
+        }
+    }
+
+    // Require at least 90% coverage so that generation can't silently lag
+    // behind the validator's SIMD support.
+    let missing = known
+        .iter()
+        .filter(|op| !emitted.contains(**op))
+        .collect::<Vec<_>>();
+    assert!(
+        missing.len() * 10 <= known.len(),
+        "only {} of {} SIMD opcodes were generated, missing: {missing:?}",
+        known.len() - missing.len(),
+        known.len(),
+    );
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn simd_lane_loads_and_stores() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 4096];
+    // Whether `v128.{load,store}{8,16,32,64}_lane` were each generated.
+    let mut loads = [false; 4];
+    let mut stores = [false; 4];
+    for _ in 0..1024 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            simd_enabled: true,
+            min_memories: 1,
+            ..Config::default()
+        };
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                continue;
+            };
+            for op in body.get_operators_reader().unwrap() {
+                let (seen, width, lane) = match op.unwrap() {
+                    Operator::V128Load8Lane { lane, .. } => (&mut loads, 0, lane),
+                    Operator::V128Load16Lane { lane, .. } => (&mut loads, 1, lane),
+                    Operator::V128Load32Lane { lane, .. } => (&mut loads, 2, lane),
+                    Operator::V128Load64Lane { lane, .. } => (&mut loads, 3, lane),
+                    Operator::V128Store8Lane { lane, .. } => (&mut stores, 0, lane),
+                    Operator::V128Store16Lane { lane, .. } => (&mut stores, 1, lane),
+                    Operator::V128Store32Lane { lane, .. } => (&mut stores, 2, lane),
+                    Operator::V128Store64Lane { lane, .. } => (&mut stores, 3, lane),
+                    _ => continue,
+                };
+                // There are 16 8-bit lanes, 8 16-bit lanes, and so on.
+                assert!(lane < 16 >> width);
+                seen[width] = true;
+            }
+        }
+    }
+    assert_eq!(loads, [true; 4]);
+    assert_eq!(stores, [true; 4]);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn relaxed_simd_dot_products() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 4096];
+    // Whether `i16x8.relaxed_dot_i8x16_i7x16_s` and the ternary
+    // `i32x4.relaxed_dot_i8x16_i7x16_add_s` were each generated.
+    let mut seen = [false; 2];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            simd_enabled: true,
+            relaxed_simd_enabled: true,
+            ..Config::default()
+        };
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                continue;
+            };
+            for op in body.get_operators_reader().unwrap() {
+                match op.unwrap() {
+                    Operator::I16x8RelaxedDotI8x16I7x16S => seen[0] = true,
+                    Operator::I32x4RelaxedDotI8x16I7x16AddS => seen[1] = true,
+                    _ => {}
+                }
+            }
+        }
+    }
+    assert_eq!(seen, [true; 2]);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn max_total_instructions() {
+    use wasmparser::{CompositeInnerType, Parser, Payload, ValType};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        let cap = u.int_in_range(0..=200).unwrap();
+        config.max_total_instructions = Some(cap);
+        config.allow_invalid_funcs = false;
+        let disallow_traps = config.disallow_traps;
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+
+        let mut results = Vec::new();
+        let mut func_results = Vec::new();
+        let mut total = 0;
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::TypeSection(reader) => {
+                    for group in reader {
+                        for ty in group.unwrap().into_types() {
+                            results.push(match &ty.composite_type.inner {
+                                CompositeInnerType::Func(f) => f.results().to_vec(),
+                                _ => Vec::new(),
+                            });
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    for ty in reader {
+                        func_results.push(results[ty.unwrap() as usize].clone());
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    // Don't count the function's final `end`.
+                    let ops = body.get_operators_reader().unwrap();
+                    total += ops.into_iter().count() - 1;
+                }
+                _ => {}
+            }
+        }
+
+        // Without traps, a non-nullable reference result may have no value
+        // that can be allocated without operands, in which case its function
+        // keeps its generated body and the cap isn't honored.
+        let non_nullable = |ty: &ValType| ty.as_reference_type().is_some_and(|r| !r.is_nullable());
+        if disallow_traps && func_results.iter().flatten().any(non_nullable) {
+            continue;
+        }
+
+        // Each function needs room for its smallest terminating body, so the
+        // cap can only be honored when it leaves room for those: a single
+        // `unreachable` for each function with results, or a constant for
+        // each result when traps are disallowed.
+        let min_bodies = func_results
+            .iter()
+            .map(|results| match (disallow_traps, results.len()) {
+                (true, n) => n,
+                (false, n) => usize::from(n > 0),
+            })
+            .sum::<usize>();
+        assert!(
+            total <= cap.max(min_bodies),
+            "{total} instructions generated with a cap of {cap}"
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_max_locals() {
+    use wasmparser::{Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.max_locals = 5000;
+        let module = Module::new(config, &mut u).unwrap();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &module.to_bytes(),
+        );
+    }
+
+    // A stream of `1` bytes keeps every loop going, so with nothing else to
+    // generate the single function gets as many locals as allowed.
+    let buf = vec![1; 1 << 16];
+    let mut u = Unstructured::new(&buf);
+    let config = Config {
+        max_locals: 5000,
+        min_funcs: 1,
+        max_funcs: 1,
+        max_imports: 0,
+        max_exports: 0,
+        max_globals: 0,
+        max_tables: 0,
+        max_memories: 0,
+        max_data_segments: 0,
+        max_element_segments: 0,
+        max_tags: 0,
+        ..Config::default()
+    };
+    let module = Module::new(config, &mut u).unwrap();
+    let wasm_bytes = module.to_bytes();
+    validate(
+        &mut Validator::new_with_features(WasmFeatures::all()),
+        &wasm_bytes,
+    );
+    let mut checked = false;
+    for payload in Parser::new(0).parse_all(&wasm_bytes) {
+        if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+            let mut entries = 0;
+            let mut locals = 0;
+            for local in body.get_locals_reader().unwrap() {
+                entries += 1;
+                locals += local.unwrap().0;
+            }
+            assert!(locals >= 4000, "only {locals} locals generated");
+            // Runs of locals with the same type share an entry.
+            assert!(entries < locals);
+            checked = true;
+        }
+    }
+    assert!(checked);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn pathological_deep_block_nesting() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    const DEPTH: usize = 10_000;
+
+    let mut checked_bodies = 0;
+    for_each_module(
+        16,
+        |u| {
+            let mut config = Config::arbitrary(u).unwrap();
+            config.pathological = Some(PathologyKind::DeepBlockNesting(DEPTH));
+            config.min_funcs = 1;
+            config.max_funcs = config.max_funcs.clamp(1, 4);
+            config.max_total_instructions = None;
+            config
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                let Ok(ops) = body.get_operators_reader() else {
+                    continue;
+                };
+                let (mut depth, mut max_depth) = (0usize, 0);
+                for op in ops {
+                    match op.unwrap() {
+                        Operator::Block { .. }
+                        | Operator::Loop { .. }
+                        | Operator::If { .. }
+                        | Operator::TryTable { .. }
+                        | Operator::Try { .. } => {
+                            depth += 1;
+                            max_depth = max_depth.max(depth);
+                        }
+                        Operator::End => depth = depth.saturating_sub(1),
+                        _ => {}
+                    }
+                }
+                assert!(max_depth >= DEPTH);
+                checked_bodies += 1;
+            }
+        },
+    );
+    assert!(checked_bodies > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn pathological_long_rec_group() {
+    use wasmparser::{Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..64 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            gc_enabled: true,
+            reference_types_enabled: true,
+            max_types: 1000,
+            pathological: Some(PathologyKind::LongRecGroup),
+            ..Config::default()
+        };
+        let module = Module::new(config, &mut u).unwrap();
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+
+        let mut groups = Vec::new();
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            if let Payload::TypeSection(reader) = payload.unwrap() {
+                for group in reader {
+                    groups.push(group.unwrap().types().len());
+                }
+            }
+        }
+        assert_eq!(groups, [1000]);
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn meaningful_start() {
+    use wasmparser::{Operator, Parser, Payload, TypeRef};
+
+    let mut starts = 0;
+    for_each_module(
+        256,
+        |u| {
+            let mut config = Config::arbitrary(u).unwrap();
+            config.meaningful_start = true;
+            config.allow_invalid_funcs = false;
+            config
+        },
+        |_, wasm_bytes| {
+            let mut start = None;
+            let mut func_index = 0;
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Func(_) = import.unwrap().ty {
+                                func_index += 1;
+                            }
+                        }
+                    }
+                    Payload::StartSection { func, .. } => start = Some(func),
+                    Payload::CodeSectionEntry(body) => {
+                        let is_start = start == Some(func_index);
+                        func_index += 1;
+                        if !is_start {
+                            continue;
+                        }
+                        let mutates = body.get_operators_reader().unwrap().into_iter().any(|op| {
+                            matches!(
+                                op.unwrap(),
+                                Operator::GlobalSet { .. }
+                                    | Operator::MemoryGrow { .. }
+                                    | Operator::TableFill { .. }
+                            )
+                        });
+                        assert!(mutates, "start function has no side effect");
+                        starts += 1;
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(starts > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn target_features_section() {
+    use wasmparser::{BinaryReader, Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut listed = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.generate_target_features_section = true;
+        let features = config.features();
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        validate(&mut Validator::new_with_features(features), &wasm_bytes);
+
+        let mut sections = 0;
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            let Payload::CustomSection(section) = payload.unwrap() else {
+                continue;
+            };
+            if section.name() != "target_features" {
+                continue;
+            }
+            sections += 1;
+            let mut reader = BinaryReader::new(section.data(), section.data_offset());
+            for _ in 0..reader.read_var_u32().unwrap() {
+                assert_eq!(reader.read_u8().unwrap(), b'+');
+                let feature = match reader.read_string().unwrap() {
+                    "atomics" => WasmFeatures::THREADS,
+                    "bulk-memory" => WasmFeatures::BULK_MEMORY,
+                    "custom-page-sizes" => WasmFeatures::CUSTOM_PAGE_SIZES,
+                    "exception-handling" => WasmFeatures::EXCEPTIONS,
+                    "gc" => WasmFeatures::GC,
+                    "memory64" => WasmFeatures::MEMORY64,
+                    "multimemory" => WasmFeatures::MULTI_MEMORY,
+                    "multivalue" => WasmFeatures::MULTI_VALUE,
+                    "nontrapping-fptoint" => WasmFeatures::SATURATING_FLOAT_TO_INT,
+                    "reference-types" => WasmFeatures::REFERENCE_TYPES,
+                    "relaxed-simd" => WasmFeatures::RELAXED_SIMD,
+                    "sign-ext" => WasmFeatures::SIGN_EXTENSION,
+                    "simd128" => WasmFeatures::SIMD,
+                    "tail-call" => WasmFeatures::TAIL_CALL,
+                    "wide-arithmetic" => WasmFeatures::WIDE_ARITHMETIC,
+                    name => panic!("unknown target feature {name:?}"),
+                };
+                assert!(features.contains(feature));
+                listed += 1;
+            }
+            assert!(reader.eof());
+        }
+        assert_eq!(sections, 1);
+    }
+    assert!(listed > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn exercise_func_sharing() {
+    use std::collections::HashSet;
+    use wasmparser::{
+        CompositeInnerType, ElementItems, ElementKind, ExternalKind, Operator, Parser, Payload,
+        RefType, TableType, TypeRef,
+    };
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut shared = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.exercise_func_sharing = true;
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+
+        // Whether each type can be shared: an unshared function type whose
+        // parameters all have a default value.
+        let mut shareable_types = Vec::new();
+        let mut func_types = Vec::new();
+        // Each table and whether it's imported.
+        let mut tables: Vec<(TableType, bool)> = Vec::new();
+        let mut exported = HashSet::new();
+        let mut in_table = HashSet::new();
+        let mut called = HashSet::new();
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::TypeSection(reader) => {
+                    for group in reader {
+                        for ty in group.unwrap().into_types() {
+                            shareable_types.push(match &ty.composite_type.inner {
+                                CompositeInnerType::Func(f) => {
+                                    !ty.composite_type.shared
+                                        && f.params().iter().all(|p| {
+                                            p.as_reference_type().is_none_or(|r| r.is_nullable())
+                                        })
+                                }
+                                _ => false,
+                            });
+                        }
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        match import.unwrap().ty {
+                            TypeRef::Func(ty) => func_types.push(ty),
+                            TypeRef::Table(ty) => tables.push((ty, true)),
+                            _ => {}
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    func_types.extend(reader.into_iter().map(|t| t.unwrap()));
+                }
+                Payload::TableSection(reader) => {
+                    for table in reader {
+                        tables.push((table.unwrap().ty, false));
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export.unwrap();
+                        if export.kind == ExternalKind::Func {
+                            exported.insert(export.index);
+                        }
+                    }
+                }
+                Payload::ElementSection(reader) => {
+                    for elem in reader {
+                        let elem = elem.unwrap();
+                        if !matches!(elem.kind, ElementKind::Active { .. }) {
+                            continue;
+                        }
+                        if let ElementItems::Functions(funcs) = elem.items {
+                            in_table.extend(funcs.into_iter().map(|f| f.unwrap()));
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    for op in body.get_operators_reader().unwrap() {
+                        if let Operator::Call { function_index } = op.unwrap() {
+                            called.insert(function_index);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if exported
+            .iter()
+            .any(|f| in_table.contains(f) && called.contains(f))
+        {
+            shared += 1;
+            continue;
+        }
+
+        // A function must be shared unless one of the documented exceptions
+        // holds: there's no defined function to call from, no other function
+        // that can be shared, or no table to hold it.
+        let config = module.config();
+        let caller = module.num_imported_functions() as u32;
+        let has_caller = module.num_defined_functions() > 0;
+        let has_callee = func_types
+            .iter()
+            .enumerate()
+            .any(|(i, ty)| i as u32 != caller && shareable_types[*ty as usize]);
+        let has_table = tables.iter().enumerate().any(|(i, (t, imported))| {
+            t.element_type == RefType::FUNCREF
+                && (i == 0 || config.reference_types_enabled)
+                && (t.initial > 0 || (!imported && t.maximum != Some(0)))
+        }) || (tables.len() < config.max_tables
+            && (tables.is_empty() || config.reference_types_enabled));
+        assert!(
+            !has_caller || !has_callee || !has_table,
+            "a function could have been shared but wasn't"
+        );
+    }
+    assert!(shared > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn clamp_offsets_to_32bit() {
+    use wasmparser::{DataKind, Operator, Parser, Payload};
+
+    let mut memarg_offsets = 0;
+    let mut data_offsets = 0;
+    for_each_module(
+        256,
+        |u| {
+            let mut config = Config::arbitrary(u).unwrap();
+            config.memory64_enabled = true;
+            config.clamp_offsets_to_32bit = true;
+            config
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::DataSection(reader) => {
+                        for data in reader {
+                            let DataKind::Active { offset_expr, .. } = data.unwrap().kind else {
+                                continue;
+                            };
+                            for op in offset_expr.get_operators_reader() {
+                                match op.unwrap() {
+                                    Operator::I64Const { value } => {
+                                        assert!(value as u64 <= u64::from(u32::MAX));
+                                        data_offsets += 1;
+                                    }
+                                    Operator::GlobalGet { .. } => {
+                                        // Only 32-bit memories may use globals.
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        let Ok(ops) = body.get_operators_reader() else {
+                            continue;
+                        };
+                        for op in ops {
+                            let Ok(op) = op else { break };
+                            // Every memory instruction's immediate prints its
+                            // `MemArg`, which is the only place `offset` appears.
+                            let op = format!("{op:?}");
+                            for (i, _) in op.match_indices("offset: ") {
+                                let digits = op[i + "offset: ".len()..]
+                                    .split(|c: char| !c.is_ascii_digit())
+                                    .next()
+                                    .unwrap();
+                                let offset: u64 = digits.parse().unwrap();
+                                assert!(offset <= u64::from(u32::MAX), "{op}");
+                                memarg_offsets += 1;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(memarg_offsets > 0);
+    assert!(data_offsets > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn shared_functions_only_access_shared_globals() {
+    use wasmparser::{Operator, Parser, Payload, TypeRef};
+
+    let mut unshared_accesses_of_shared = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            threads_enabled: true,
+            shared_everything_threads_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut shared_types = Vec::new();
+            let mut shared_globals = Vec::new();
+            let mut defined_funcs = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::TypeSection(reader) => {
+                        for group in reader {
+                            for ty in group.unwrap().into_types() {
+                                shared_types.push(ty.composite_type.shared);
+                            }
+                        }
+                    }
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            match import.unwrap().ty {
+                                TypeRef::Global(ty) => shared_globals.push(ty.shared),
+                                _ => {}
+                            }
+                        }
+                    }
+                    Payload::FunctionSection(reader) => {
+                        for ty in reader {
+                            defined_funcs.push(shared_types[ty.unwrap() as usize]);
+                        }
+                    }
+                    Payload::GlobalSection(reader) => {
+                        for global in reader {
+                            shared_globals.push(global.unwrap().ty.shared);
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        let shared = defined_funcs.remove(0);
+                        for op in body.get_operators_reader().unwrap() {
+                            match op.unwrap() {
+                                Operator::GlobalGet { global_index }
+                                | Operator::GlobalSet { global_index } => {
+                                    let global_shared = shared_globals[global_index as usize];
+                                    // Shared functions may only access shared
+                                    // globals, but unshared functions may access
+                                    // either.
+                                    assert!(!shared || global_shared);
+                                    if !shared && global_shared {
+                                        unshared_accesses_of_shared += 1;
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(unshared_accesses_of_shared > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_table_atomics() {
+    use wasmparser::{Operator, Parser, Payload, TypeRef};
+
+    let mut table_atomics = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            threads_enabled: true,
+            shared_everything_threads_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut shared_tables = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Table(ty) = import.unwrap().ty {
+                                shared_tables.push(ty.shared);
+                            }
+                        }
+                    }
+                    Payload::TableSection(reader) => {
+                        for table in reader {
+                            shared_tables.push(table.unwrap().ty.shared);
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        for op in body.get_operators_reader().unwrap() {
+                            match op.unwrap() {
+                                Operator::TableAtomicGet { table_index, .. }
+                                | Operator::TableAtomicSet { table_index, .. }
+                                | Operator::TableAtomicRmwXchg { table_index, .. }
+                                | Operator::TableAtomicRmwCmpxchg { table_index, .. } => {
+                                    // Atomic accesses are only generated for
+                                    // shared tables.
+                                    assert!(shared_tables[table_index as usize]);
+                                    table_atomics += 1;
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(table_atomics > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn single_large_function() {
+    use wasmparser::{Parser, Payload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 64 * 1024];
+    let mut largest_body = 0;
+    for _ in 0..16 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let module = Module::new(Config::single_large_function(), &mut u).unwrap();
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+
+        let mut bodies = 0;
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::ImportSection(reader) => assert_eq!(reader.count(), 0),
+                Payload::CodeSectionEntry(body) => {
+                    bodies += 1;
+                    let ops = body.get_operators_reader().unwrap().into_iter().count();
+                    largest_body = largest_body.max(ops);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(bodies, 1);
+    }
+    // Bodies can grow well past the default per-function instruction cap.
+    assert!(largest_body > 10 * Config::default().max_instructions);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn ref_null_covers_abstract_heap_types() {
+    use std::collections::HashSet;
+    use wasmparser::{AbstractHeapType::*, HeapType, Operator, Parser, Payload};
+
+    let mut seen = HashSet::new();
+    for_each_module(
+        256,
+        |_| Config {
+            reference_types_enabled: true,
+            gc_enabled: true,
+            exceptions_enabled: true,
+            legacy_exceptions: false,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                for op in body.get_operators_reader().unwrap() {
+                    if let Operator::RefNull {
+                        hty: HeapType::Abstract { shared: false, ty },
+                    } = op.unwrap()
+                    {
+                        seen.insert(ty);
+                    }
+                }
+            }
+        },
+    );
+
+    let expected = HashSet::from([
+        Func, Extern, Any, Eq, Struct, Array, I31, None, NoFunc, NoExtern, Exn, NoExn,
+    ]);
+    assert_eq!(seen, expected);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_many_memories() {
+    use std::collections::BTreeSet;
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut used = BTreeSet::new();
+    for_each_module(
+        256,
+        |_| Config {
+            min_memories: 5,
+            max_memories: 8,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut memories = 0;
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let wasmparser::TypeRef::Memory(_) = import.unwrap().ty {
+                                memories += 1;
+                            }
+                        }
+                    }
+                    Payload::MemorySection(reader) => memories += reader.count(),
+                    Payload::CodeSectionEntry(body) => {
+                        for op in body.get_operators_reader().unwrap() {
+                            match op.unwrap() {
+                                Operator::I32Load { memarg }
+                                | Operator::I64Load { memarg }
+                                | Operator::I32Store { memarg }
+                                | Operator::I64Store { memarg } => {
+                                    used.insert(memarg.memory);
+                                }
+                                Operator::MemorySize { mem }
+                                | Operator::MemoryGrow { mem }
+                                | Operator::MemoryFill { mem } => {
+                                    used.insert(mem);
+                                }
+                                Operator::MemoryCopy { dst_mem, src_mem } => {
+                                    used.insert(dst_mem);
+                                    used.insert(src_mem);
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            assert!((5..=8).contains(&memories));
+        },
+    );
+    assert!(used.len() >= 5, "only memories {used:?} were used");
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn imported_and_defined_counts() {
+    use wasmparser::{Parser, Payload, TypeRef};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let Ok(config) = Config::arbitrary(&mut u) else {
+            continue;
+        };
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        let mut imported = [0; 5];
+        let mut defined = [0; 5];
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            match payload.unwrap() {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        let i = match import.unwrap().ty {
+                            TypeRef::Func(_) => 0,
+                            TypeRef::Global(_) => 1,
+                            TypeRef::Table(_) => 2,
+                            TypeRef::Memory(_) => 3,
+                            TypeRef::Tag(_) => 4,
+                        };
+                        imported[i] += 1;
+                    }
+                }
+                Payload::FunctionSection(reader) => defined[0] = reader.count(),
+                Payload::GlobalSection(reader) => defined[1] = reader.count(),
+                Payload::TableSection(reader) => defined[2] = reader.count(),
+                Payload::MemorySection(reader) => defined[3] = reader.count(),
+                Payload::TagSection(reader) => defined[4] = reader.count(),
+                _ => {}
+            }
+        }
+        let expected_imported = [
+            module.num_imported_functions(),
+            module.num_imported_globals(),
+            module.num_imported_tables(),
+            module.num_imported_memories(),
+            module.num_imported_tags(),
+        ];
+        let expected_defined = [
+            module.num_defined_functions(),
+            module.num_defined_globals(),
+            module.num_defined_tables(),
+            module.num_defined_memories(),
+            module.num_defined_tags(),
+        ];
+        assert_eq!(imported.map(|n| n as usize), expected_imported);
+        assert_eq!(defined.map(|n| n as usize), expected_defined);
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn uniform_memory_index_type() {
+    use wasmparser::{Parser, Payload, TypeRef};
+
+    for memory64 in [false, true] {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut buf = vec![0; 2048];
+        let mut memories = 0;
+        for _ in 0..256 {
+            rng.fill_bytes(&mut buf);
+            let mut u = Unstructured::new(&buf);
+            let mut config = Config::arbitrary(&mut u).unwrap();
+            config.memory64_enabled = true;
+            config.uniform_memory_index_type = Some(memory64);
+            let module = Module::new(config, &mut u).unwrap();
+            let wasm_bytes = module.to_bytes();
+            validate(
+                &mut Validator::new_with_features(WasmFeatures::all()),
+                &wasm_bytes,
+            );
+            for payload in Parser::new(0).parse_all(&wasm_bytes) {
+                let mut check = |ty: wasmparser::MemoryType| {
+                    assert_eq!(ty.memory64, memory64);
+                    memories += 1;
+                };
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Memory(ty) = import.unwrap().ty {
+                                check(ty);
+                            }
+                        }
+                    }
+                    Payload::MemorySection(reader) => {
+                        for ty in reader {
+                            check(ty.unwrap());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        assert!(memories > 0);
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_i8x16_shuffle_patterns() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let identity: [u8; 16] = std::array::from_fn(|i| i as u8);
+    let reverse: [u8; 16] = std::array::from_fn(|i| 15 - i as u8);
+    let interleave_low: [u8; 16] = std::array::from_fn(|i| (i / 2 + i % 2 * 16) as u8);
+    let interleave_high = interleave_low.map(|l| l + 8);
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let (mut reversed, mut random) = (0, 0);
+    for _ in 0..1024 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let mut config = Config::arbitrary(&mut u).unwrap();
+        config.simd_enabled = true;
+        let module = Module::new(config, &mut u).unwrap();
+        let wasm_bytes = module.to_bytes();
+        validate(
+            &mut Validator::new_with_features(WasmFeatures::all()),
+            &wasm_bytes,
+        );
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                for op in body.get_operators_reader().unwrap() {
+                    if let Operator::I8x16Shuffle { lanes } = op.unwrap() {
+                        // Identity and reverse may select from either
+                        // operand.
+                        let relative = lanes.map(|l| l % 16);
+                        if relative == reverse {
+                            reversed += 1;
+                        } else if relative != identity
+                            && lanes != interleave_low
+                            && lanes != interleave_high
+                            && lanes.iter().any(|l| *l != lanes[0])
+                        {
+                            random += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    assert!(reversed > 0);
+    assert!(random > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_memory_grow_to_maximum() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut near_maximum = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            min_memories: 1,
+            max_memories: 1,
+            memory64_enabled: false,
+            memory_max_size_required: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut boundary = None;
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let wasmparser::TypeRef::Memory(ty) = import.unwrap().ty {
+                                boundary = Some(ty.maximum.unwrap() - ty.initial);
+                            }
+                        }
+                    }
+                    Payload::MemorySection(reader) => {
+                        for ty in reader {
+                            let ty = ty.unwrap();
+                            boundary = Some(ty.maximum.unwrap() - ty.initial);
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        let boundary = boundary.unwrap() as i64;
+                        let mut prev = None;
+                        for op in body.get_operators_reader().unwrap() {
+                            let op = op.unwrap();
+                            if let (
+                                Some(Operator::I32Const { value }),
+                                Operator::MemoryGrow { .. },
+                            ) = (&prev, &op)
+                            {
+                                if (i64::from(*value) - boundary).abs() <= 1 {
+                                    near_maximum += 1;
+                                }
+                            }
+                            prev = Some(op);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(near_maximum > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_deduplicate_types() {
+    use std::collections::HashSet;
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    for i in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            deduplicate_types: true,
+            // Without GC every rec group is a single function type, which are
+            // much more likely to collide.
+            gc_enabled: i % 2 == 0,
+            min_types: 5,
+            ..Config::default()
+        };
+        if let Ok(module) = Module::new(config, &mut u) {
+            let wasm_bytes = module.to_bytes();
+            let mut validator = Validator::new_with_features(WasmFeatures::all());
+            let types = validate(&mut validator, &wasm_bytes);
+            let types = types.as_ref();
+
+            // Structurally-identical types are canonicalized to the same id
+            // during validation.
+            let mut ids = HashSet::new();
+            for i in 0..types.core_type_count_in_module() {
+                assert!(
+                    ids.insert(types.core_type_at_in_module(i)),
+                    "type {i} is a duplicate"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_exercise_memory_lifecycle() {
+    use wasmparser::{DataKind, Operator, Parser, Payload};
+
+    let mut exercised = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            exercise_memory_lifecycle: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut active_memories = Vec::new();
+            let mut passive = false;
+            let mut has_code = false;
+            let mut init_memories = Vec::new();
+            let mut copy_memories = Vec::new();
+            let mut drop = false;
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::DataSection(reader) => {
+                        for data in reader {
+                            match data.unwrap().kind {
+                                DataKind::Active { memory_index, .. } => {
+                                    active_memories.push(memory_index)
+                                }
+                                DataKind::Passive => passive = true,
+                            }
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        has_code = true;
+                        for op in body.get_operators_reader().unwrap() {
+                            match op.unwrap() {
+                                Operator::MemoryInit { mem, .. } => init_memories.push(mem),
+                                Operator::DataDrop { .. } => drop = true,
+                                Operator::MemoryCopy { dst_mem, src_mem } if dst_mem == src_mem => {
+                                    copy_memories.push(dst_mem)
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if has_code {
+                // The code section precedes the data section, so only check that
+                // the active segments and bulk memory instructions share a memory
+                // once everything has been parsed.
+                assert!(passive && drop);
+                assert!(
+                    active_memories
+                        .iter()
+                        .any(|m| init_memories.contains(m) && copy_memories.contains(m))
+                );
+                exercised += 1;
+            }
+        },
+    );
+    assert!(exercised > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_array_new_fixed() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut non_empty = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                for op in body.get_operators_reader().unwrap() {
+                    if let Operator::ArrayNewFixed { array_size, .. } = op.unwrap() {
+                        assert!(array_size <= 16);
+                        if array_size > 0 {
+                            non_empty += 1;
+                        }
+                    }
+                }
+            }
+        },
+    );
+    assert!(non_empty > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_min_zero_memory_ratio() {
+    use wasmparser::{Parser, Payload};
+
+    let mut memories = 0;
+    let mut zero_minimum = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            min_zero_memory_ratio: 1.0,
+            min_memories: 1,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                if let Payload::MemorySection(reader) = payload.unwrap() {
+                    for ty in reader {
+                        memories += 1;
+                        if ty.unwrap().initial == 0 {
+                            zero_minimum += 1;
+                        }
+                    }
+                }
+            }
+        },
+    );
+    assert!(memories > 0);
+    assert_eq!(zero_minimum, memories);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn disallow_traps_call_ref_operands_are_non_null() {
+    use wasmparser::{Operator, Parser, ValType, ValidPayload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut checked = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            disallow_traps: true,
+            gc_enabled: true,
+            ..Config::default()
+        };
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        let mut validator = Validator::new_with_features(WasmFeatures::all());
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            let payload = payload.unwrap();
+            let ValidPayload::Func(func, body) = validator.payload(&payload).unwrap() else {
+                continue;
+            };
+            let mut func = func.into_validator(Default::default());
+            for local in body.get_locals_reader().unwrap() {
+                let (count, ty) = local.unwrap();
+                func.define_locals(0, count, ty).unwrap();
+            }
+            let mut ops = body.get_operators_reader().unwrap();
+            while !ops.eof() {
+                let (op, offset) = ops.read_with_offset().unwrap();
+                if let Operator::CallRef { .. }
+                | Operator::ReturnCallRef { .. }
+                | Operator::RefAsNonNull = op
+                {
+                    // The reference operand must be statically non-null,
+                    // unless this is unreachable code that is never executed.
+                    if !func.get_control_frame(0).unwrap().unreachable {
+                        let Some(Some(ValType::Ref(r))) = func.get_operand_type(0) else {
+                            panic!("expected a reference operand for {op:?}");
+                        };
+                        assert!(!r.is_nullable(), "{op:?} on nullable {r:?}");
+                        checked += 1;
+                    }
+                }
+                func.op(offset, &op).unwrap();
+            }
+        }
+    }
+    assert!(checked > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn select_between_differing_reference_types() {
+    use wasmparser::{Operator, Parser, ValType, ValidPayload};
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut differing = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            gc_enabled: true,
+            ..Config::default()
+        };
+        let Ok(module) = Module::new(config, &mut u) else {
+            continue;
+        };
+        let wasm_bytes = module.to_bytes();
+        let mut validator = Validator::new_with_features(WasmFeatures::all());
+        for payload in Parser::new(0).parse_all(&wasm_bytes) {
+            let payload = payload.unwrap();
+            let ValidPayload::Func(func, body) = validator.payload(&payload).unwrap() else {
+                continue;
+            };
+            let mut func = func.into_validator(Default::default());
+            for local in body.get_locals_reader().unwrap() {
+                let (count, ty) = local.unwrap();
+                func.define_locals(0, count, ty).unwrap();
+            }
+            let mut ops = body.get_operators_reader().unwrap();
+            while !ops.eof() {
+                let (op, offset) = ops.read_with_offset().unwrap();
+                if let Operator::TypedSelect {
+                    ty: ValType::Ref(_),
+                } = op
+                {
+                    if let (Some(Some(ValType::Ref(a))), Some(Some(ValType::Ref(b)))) =
+                        (func.get_operand_type(1), func.get_operand_type(2))
+                    {
+                        if a != b {
+                            differing += 1;
+                        }
+                    }
+                }
+                // Validating the `select` checks that its annotation is a
+                // supertype of both operands.
+                func.op(offset, &op).unwrap();
+            }
+        }
+    }
+    assert!(differing > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_ref_as_non_null_then_call_ref() {
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut pairs = 0;
+    for_each_module(
+        1024,
+        |_| Config {
+            gc_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                let mut prev = None;
+                for op in body.get_operators_reader().unwrap() {
+                    let op = op.unwrap();
+                    if let (Some(Operator::RefAsNonNull), Operator::CallRef { .. }) = (&prev, &op) {
+                        pairs += 1;
+                    }
+                    prev = Some(op);
+                }
+            }
+        },
+    );
+    assert!(pairs > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_subtype_global_get() {
+    use wasmparser::{Operator, Parser, Payload, TypeRef};
+
+    let mut subtype_inits = 0;
+    for_each_module(
+        1024,
+        |_| Config {
+            gc_enabled: true,
+            subtype_global_get_ratio: 1.0,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut imported_globals = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Global(ty) = import.unwrap().ty {
+                                imported_globals.push(ty.content_type);
+                            }
+                        }
+                    }
+                    Payload::GlobalSection(reader) => {
+                        for global in reader {
+                            let global = global.unwrap();
+                            let op = global.init_expr.get_operators_reader().read().unwrap();
+                            if let Operator::GlobalGet { global_index } = op {
+                                if imported_globals
+                                    .get(global_index as usize)
+                                    .is_some_and(|ty| *ty != global.ty.content_type)
+                                {
+                                    subtype_inits += 1;
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert!(subtype_inits > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_struct_field_indices() {
+    use std::collections::BTreeSet;
+    use wasmparser::{Operator, Parser, Payload};
+
+    let mut gets = BTreeSet::new();
+    let mut sets = BTreeSet::new();
+    for_each_module(
+        256,
+        |_| Config {
+            gc_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::CodeSectionEntry(body) = payload.unwrap() else {
+                    continue;
+                };
+                for op in body.get_operators_reader().unwrap() {
+                    match op.unwrap() {
+                        Operator::StructGet { field_index, .. }
+                        | Operator::StructGetS { field_index, .. }
+                        | Operator::StructGetU { field_index, .. } => {
+                            gets.insert(field_index);
+                        }
+                        Operator::StructSet { field_index, .. } => {
+                            sets.insert(field_index);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        },
+    );
+    assert!(gets.iter().any(|i| *i > 0), "struct.get fields: {gets:?}");
+    assert!(sets.iter().any(|i| *i > 0), "struct.set fields: {sets:?}");
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn concrete_heap_type_ratio() {
+    use wasmparser::{HeapType, Parser, Payload, ValType};
+
+    // Returns the number of concrete and abstract references among the
+    // locals and globals of the generated modules.
+    let count = |ratio: Option<(u32, u32)>| {
+        let (mut concrete, mut abstract_) = (0, 0);
+        let mut tally = |ty: ValType| {
+            if let ValType::Ref(r) = ty {
+                match r.heap_type() {
+                    HeapType::Concrete(_) => concrete += 1,
+                    _ => abstract_ += 1,
+                }
+            }
+        };
+        for_each_module(
+            256,
+            |_| Config {
+                gc_enabled: true,
+                concrete_heap_type_ratio: ratio,
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    match payload.unwrap() {
+                        Payload::GlobalSection(reader) => {
+                            for global in reader {
+                                tally(global.unwrap().ty.content_type);
+                            }
+                        }
+                        Payload::CodeSectionEntry(body) => {
+                            for local in body.get_locals_reader().unwrap() {
+                                tally(local.unwrap().1);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            },
+        );
+        (concrete, abstract_)
+    };
+
+    let (concrete, abstract_) = count(Some((19, 20)));
+    assert!(
+        concrete > abstract_,
+        "{concrete} concrete, {abstract_} abstract"
+    );
+    let (concrete, _) = count(Some((0, 1)));
+    assert_eq!(concrete, 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn force_explicit_rec_groups() {
+    fn saw_rec_groups(force_explicit_rec_groups: bool) -> (bool, bool) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut buf = vec![0; 2048];
+        let (mut saw_implicit, mut saw_explicit_single) = (false, false);
+        for _ in 0..256 {
+            rng.fill_bytes(&mut buf);
+            let mut u = Unstructured::new(&buf);
+            let mut config = Config::arbitrary(&mut u).unwrap();
+            config.reference_types_enabled = true;
+            config.gc_enabled = true;
+            config.force_explicit_rec_groups = force_explicit_rec_groups;
+            let module = Module::new(config, &mut u).unwrap();
+            let wasm_bytes = module.to_bytes();
+            validate(
+                &mut Validator::new_with_features(WasmFeatures::all()),
+                &wasm_bytes,
+            );
+            for payload in wasmparser::Parser::new(0).parse_all(&wasm_bytes) {
+                if let wasmparser::Payload::TypeSection(reader) = payload.unwrap() {
+                    for group in reader {
+                        let group = group.unwrap();
+                        if !group.is_explicit_rec_group() {
+                            saw_implicit = true;
+                        } else if group.types().len() == 1 {
+                            saw_explicit_single = true;
+                        }
+                    }
+                }
+            }
+        }
+        (saw_implicit, saw_explicit_single)
+    }
+
+    assert_eq!(saw_rec_groups(true), (false, true));
+    assert_eq!(saw_rec_groups(false), (true, false));
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_cross_rec_group_refs() {
+    use wasmparser::{Parser, Payload};
+
+    let mut cross_group_supertypes = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            cross_rec_group_refs: true,
+            gc_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                let Payload::TypeSection(reader) = payload.unwrap() else {
+                    continue;
+                };
+                let mut rec_group_start = 0;
+                for rec_group in reader {
+                    let rec_group = rec_group.unwrap();
+                    let len = rec_group.types().len() as u32;
+                    for ty in rec_group.into_types() {
+                        let supertype = ty.supertype_idx.and_then(|i| i.as_module_index());
+                        if supertype.is_some_and(|i| i < rec_group_start) {
+                            cross_group_supertypes += 1;
+                        }
+                    }
+                    rec_group_start += len;
+                }
+            }
+        },
+    );
+    assert!(cross_group_supertypes > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_recursive_type_bias() {
+    use wasmparser::{CompositeInnerType, HeapType, Parser, Payload, StorageType, ValType};
+
+    // Counts the struct and array types that have a field referring to
+    // themselves or to a later type in their rec group, which are only
+    // generated through `recursive_type_bias`.
+    let recursive_types = |recursive_type_bias: f64| {
+        let mut recursive = 0;
+        for_each_module(
+            256,
+            |_| Config {
+                gc_enabled: true,
+                recursive_type_bias,
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    let Payload::TypeSection(reader) = payload.unwrap() else {
+                        continue;
+                    };
+                    let mut index = 0;
+                    for rec_group in reader {
+                        let rec_group = rec_group.unwrap();
+                        let rec_group_start = index;
+                        for ty in rec_group.into_types() {
+                            let fields = match &ty.composite_type.inner {
+                                CompositeInnerType::Struct(s) => s.fields.to_vec(),
+                                CompositeInnerType::Array(a) => vec![a.0],
+                                _ => Vec::new(),
+                            };
+                            let refers_to_self_or_later = fields.iter().any(|f| {
+                                let StorageType::Val(ValType::Ref(r)) = f.element_type else {
+                                    return false;
+                                };
+                                let HeapType::Concrete(i) = r.heap_type() else {
+                                    return false;
+                                };
+                                let i = match i.as_module_index() {
+                                    Some(i) => i,
+                                    None => rec_group_start + i.as_rec_group_index().unwrap(),
+                                };
+                                i >= index
+                            });
+                            if refers_to_self_or_later {
+                                recursive += 1;
+                            }
+                            index += 1;
+                        }
+                    }
+                }
+            },
+        );
+        recursive
+    };
+
+    assert_eq!(recursive_types(0.0), 0);
+    assert!(recursive_types(0.5) > 1_000);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_tags_with_reference_params() {
+    use wasmparser::{CompositeInnerType, Operator, Parser, Payload, TypeRef};
+
+    let mut ref_tags = 0;
+    let mut ref_throws = 0;
+    for_each_module(
+        1024,
+        |_| Config {
+            exceptions_enabled: true,
+            reference_types_enabled: true,
+            gc_enabled: true,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            // Whether each type is a function type with a reference-typed param,
+            // and then the same for each tag.
+            let mut types = Vec::new();
+            let mut tags = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::TypeSection(reader) => {
+                        for rec_group in reader {
+                            for ty in rec_group.unwrap().into_types() {
+                                types.push(match &ty.composite_type.inner {
+                                    CompositeInnerType::Func(f) => {
+                                        f.params().iter().any(|p| p.is_reference_type())
+                                    }
+                                    _ => false,
+                                });
+                            }
+                        }
+                    }
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Tag(tag) = import.unwrap().ty {
+                                tags.push(types[tag.func_type_idx as usize]);
+                            }
+                        }
+                    }
+                    Payload::TagSection(reader) => {
+                        for tag in reader {
+                            tags.push(types[tag.unwrap().func_type_idx as usize]);
+                        }
+                    }
+                    Payload::CodeSectionEntry(body) => {
+                        for op in body.get_operators_reader().unwrap() {
+                            if let Operator::Throw { tag_index } = op.unwrap() {
+                                if tags[tag_index as usize] {
+                                    ref_throws += 1;
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            ref_tags += tags.iter().filter(|t| **t).count();
+        },
+    );
+    assert!(ref_tags > 0);
+    assert!(ref_throws > 0);
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_element_density() {
+    use wasm_smith::ElementDensity;
+    use wasmparser::{ElementItems, ElementKind, Operator, Parser, Payload, TypeRef};
+
+    for density in [ElementDensity::Dense, ElementDensity::Sparse] {
+        let mut non_empty = 0;
+        for_each_module(
+            256,
+            |_| Config {
+                element_density: density,
+                max_table_elements: 50,
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                let mut table_minimums = Vec::new();
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    match payload.unwrap() {
+                        Payload::ImportSection(reader) => {
+                            for import in reader {
+                                if let TypeRef::Table(ty) = import.unwrap().ty {
+                                    table_minimums.push(ty.initial);
+                                }
+                            }
+                        }
+                        Payload::TableSection(reader) => {
+                            for table in reader {
+                                table_minimums.push(table.unwrap().ty.initial);
+                            }
+                        }
+                        Payload::ElementSection(reader) => {
+                            for elem in reader {
+                                let elem = elem.unwrap();
+                                let ElementKind::Active {
+                                    table_index,
+                                    offset_expr,
+                                } = elem.kind
+                                else {
+                                    continue;
+                                };
+                                let offset =
+                                    match offset_expr.get_operators_reader().read().unwrap() {
+                                        Operator::I32Const { value } => u64::from(value as u32),
+                                        Operator::I64Const { value } => value as u64,
+                                        op => panic!("non-constant offset: {op:?}"),
+                                    };
+                                let len = u64::from(match elem.items {
+                                    ElementItems::Functions(r) => r.count(),
+                                    ElementItems::Expressions(_, r) => r.count(),
+                                });
+                                let minimum = table_minimums[table_index.unwrap_or(0) as usize];
+                                match density {
+                                    ElementDensity::Dense => {
+                                        assert_eq!(offset, 0);
+                                        // Function lists may be empty when no
+                                        // function has a matching type.
+                                        assert!(len == minimum || len == 0);
+                                    }
+                                    ElementDensity::Sparse => {
+                                        assert!(len <= 4);
+                                        assert!(offset + len <= minimum);
+                                    }
+                                    ElementDensity::Arbitrary => unreachable!(),
+                                }
+                                if len > 0 {
+                                    non_empty += 1;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            },
+        );
+        assert!(non_empty > 0);
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_fully_initialize_tables() {
+    use wasmparser::{ElementItems, ElementKind, Operator, Parser, Payload, TypeRef};
+
+    let mut filled_tables = 0;
+    let mut non_null_entries = 0;
+    for_each_module(
+        256,
+        |u| {
+            let mut config = Config::arbitrary(u).unwrap();
+            config.fully_initialize_tables = true;
+            config.bulk_memory_enabled = true;
+            config.reference_types_enabled = true;
+            config.max_table_elements = 1000;
+            config
+        },
+        |_, wasm_bytes| {
+            // Mark which slots of each defined table are written by an active
+            // segment at instantiation.
+            let mut num_imported_tables = 0;
+            let mut initialized: Vec<Vec<bool>> = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(reader) => {
+                        for import in reader {
+                            if let TypeRef::Table(_) = import.unwrap().ty {
+                                num_imported_tables += 1;
+                                initialized.push(Vec::new());
+                            }
+                        }
+                    }
+                    Payload::TableSection(reader) => {
+                        for table in reader {
+                            initialized.push(vec![false; table.unwrap().ty.initial as usize]);
+                        }
+                    }
+                    Payload::ElementSection(reader) => {
+                        for elem in reader {
+                            let elem = elem.unwrap();
+                            let ElementKind::Active {
+                                table_index,
+                                offset_expr,
+                            } = elem.kind
+                            else {
+                                continue;
+                            };
+                            let offset = match offset_expr.get_operators_reader().read().unwrap() {
+                                Operator::I32Const { value } => u64::from(value as u32),
+                                Operator::I64Const { value } => value as u64,
+                                _ => continue,
+                            };
+                            let len = match elem.items {
+                                ElementItems::Functions(r) => u64::from(r.count()),
+                                ElementItems::Expressions(_, r) => {
+                                    let mut len = 0;
+                                    for expr in r {
+                                        let mut ops = expr.unwrap().get_operators_reader();
+                                        if !matches!(ops.read().unwrap(), Operator::RefNull { .. })
+                                        {
+                                            non_null_entries += 1;
+                                        }
+                                        len += 1;
+                                    }
+                                    len
+                                }
+                            };
+                            let slots = &mut initialized[table_index.unwrap_or(0) as usize];
+                            for slot in offset..offset.saturating_add(len) {
+                                if let Some(slot) = slots.get_mut(slot as usize) {
+                                    *slot = true;
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            for slots in &initialized[num_imported_tables..] {
+                assert!(slots.iter().all(|s| *s), "table has an uninitialized slot");
+                if !slots.is_empty() {
+                    filled_tables += 1;
+                }
+            }
+        },
+    );
+    assert!(filled_tables > 0);
+    assert!(non_null_entries > 0);
+}
+
+#[test]
+fn smoke_test_feature_coverage() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buf = vec![0; 2048];
+    let mut structs = 0;
+    let mut arrays = 0;
+    for _ in 0..256 {
+        rng.fill_bytes(&mut buf);
+        let mut u = Unstructured::new(&buf);
+        let config = Config {
+            gc_enabled: true,
+            ..Config::default()
+        };
+        if let Ok(module) = Module::new(config, &mut u) {
+            let coverage = module.feature_coverage();
+            structs += usize::from(coverage.gc_struct_types);
+            arrays += usize::from(coverage.gc_array_types);
+        }
+    }
+    assert!(structs > 0);
+    assert!(arrays > 0);
+
+    // Nothing is exercised by a module built with every proposal disabled.
+    let mut u = Unstructured::new(&buf);
+    let config = Config {
+        bulk_memory_enabled: false,
+        exceptions_enabled: false,
+        gc_enabled: false,
+        memory64_enabled: false,
+        max_memories: 1,
+        multi_value_enabled: false,
+        reference_types_enabled: false,
+        saturating_float_to_int_enabled: false,
+        sign_extension_ops_enabled: false,
+        simd_enabled: false,
+        relaxed_simd_enabled: false,
+        tail_call_enabled: false,
+        threads_enabled: false,
+        ..Config::default()
+    };
+    if let Ok(module) = Module::new(config, &mut u) {
+        assert_eq!(module.feature_coverage(), FeatureCoverage::default());
+    }
+}
+
+#[test]
+#[cfg(feature = "wasmparser")]
+fn smoke_test_instantiable() {
+    use wasmparser::{
+        DataKind, ElementItems, ElementKind, Operator, Parser, Payload, TableInit, TypeRef,
+    };
+
+    fn const_offset(expr: &wasmparser::ConstExpr) -> u64 {
+        let mut ops = expr.get_operators_reader();
+        let offset = match ops.read().unwrap() {
+            Operator::I32Const { value } => u64::from(value as u32),
+            Operator::I64Const { value } => value as u64,
+            op => panic!("non-constant offset in instantiable module: {op:?}"),
+        };
+        assert!(matches!(ops.read().unwrap(), Operator::End));
+        offset
+    }
+
+    for_each_module(
+        256,
+        |u| {
+            let mut config = Config::arbitrary(u).unwrap();
+            config.instantiable = true;
+            config.custom_page_sizes_enabled = u.arbitrary().unwrap();
+            config
+        },
+        |_, wasm_bytes| {
+            // Check everything that could trap during instantiation against the
+            // minimum sizes of the module's memories and tables.
+            let mut memory_bytes = Vec::new();
+            let mut table_elems = Vec::new();
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    Payload::ImportSection(rdr) => {
+                        for import in rdr {
+                            match import.unwrap().ty {
+                                TypeRef::Memory(ty) => {
+                                    memory_bytes.push(ty.initial << ty.page_size_log2.unwrap_or(16))
+                                }
+                                TypeRef::Table(ty) => table_elems.push(ty.initial),
+                                _ => {}
+                            }
+                        }
+                    }
+                    Payload::MemorySection(rdr) => {
+                        for ty in rdr {
+                            let ty = ty.unwrap();
+                            memory_bytes.push(ty.initial << ty.page_size_log2.unwrap_or(16));
+                        }
+                    }
+                    Payload::TableSection(rdr) => {
+                        for table in rdr {
+                            let table = table.unwrap();
+                            assert!(matches!(
+                                table.init,
+                                TableInit::RefNull | TableInit::Expr(_)
+                            ));
+                            table_elems.push(table.ty.initial);
+                        }
+                    }
+                    Payload::StartSection { .. } => {
+                        panic!("instantiable module has a start function")
+                    }
+                    Payload::ElementSection(rdr) => {
+                        for elem in rdr {
+                            let elem = elem.unwrap();
+                            let ElementKind::Active {
+                                table_index,
+                                offset_expr,
+                            } = elem.kind
+                            else {
+                                continue;
+                            };
+                            let len = match elem.items {
+                                ElementItems::Functions(r) => r.count(),
+                                ElementItems::Expressions(_, r) => r.count(),
+                            };
+                            let table = table_elems[table_index.unwrap_or(0) as usize];
+                            assert!(const_offset(&offset_expr) + u64::from(len) <= table);
+                        }
+                    }
+                    Payload::DataSection(rdr) => {
+                        for data in rdr {
+                            let data = data.unwrap();
+                            let DataKind::Active {
+                                memory_index,
+                                offset_expr,
+                            } = data.kind
+                            else {
+                                continue;
+                            };
+                            let memory = memory_bytes[memory_index as usize];
+                            let end = const_offset(&offset_expr) + data.data.len() as u64;
+                            assert!(end <= memory);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
 }
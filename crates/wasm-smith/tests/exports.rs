@@ -2,14 +2,14 @@
 
 use arbitrary::Unstructured;
 use rand::{RngCore, SeedableRng, rngs::SmallRng};
-use wasm_smith::{Config, Module};
+use wasm_smith::{Config, ExportOrdering, Module};
 use wasmparser::{
     CompositeType, FuncType, GlobalType, MemoryType, Parser, TableType, Validator, WasmFeatures,
     types::EntityType,
 };
 
 mod common;
-use common::validate;
+use common::{for_each_module, validate};
 
 #[derive(Debug, PartialEq)]
 enum ExportType {
@@ -122,6 +122,232 @@ fn smoke_test_all_exports() {
     smoke_test_exports(test, 23);
 }
 
+#[test]
+fn smoke_test_reexport_imports() {
+    let available_imports = wat::parse_str(
+        r#"
+        (module
+            (import "env" "f" (func (param i32)))
+            (import "env" "t" (table 1 funcref))
+            (import "env" "m" (memory 1))
+            (import "env" "g" (global i32))
+        )
+        "#,
+    )
+    .unwrap();
+    let mut reexported = [false; 4];
+
+    for_each_module(
+        256,
+        |_| Config {
+            available_imports: Some(available_imports.clone()),
+            reexport_imports_ratio: 1.0,
+            min_exports: 1,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            let mut num_imported = [0; 4];
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    wasmparser::Payload::ImportSection(rdr) => {
+                        for import in rdr {
+                            let kind = match import.unwrap().ty {
+                                wasmparser::TypeRef::Func(_) => 0,
+                                wasmparser::TypeRef::Table(_) => 1,
+                                wasmparser::TypeRef::Memory(_) => 2,
+                                wasmparser::TypeRef::Global(_) => 3,
+                                _ => continue,
+                            };
+                            num_imported[kind] += 1;
+                        }
+                    }
+                    wasmparser::Payload::ExportSection(rdr) => {
+                        for export in rdr {
+                            let export = export.unwrap();
+                            let kind = match export.kind {
+                                wasmparser::ExternalKind::Func => 0,
+                                wasmparser::ExternalKind::Table => 1,
+                                wasmparser::ExternalKind::Memory => 2,
+                                wasmparser::ExternalKind::Global => 3,
+                                _ => continue,
+                            };
+                            if export.index < num_imported[kind] {
+                                reexported[kind] = true;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert_eq!(reexported, [true; 4]);
+}
+
+#[test]
+fn smoke_test_export_ordering() {
+    fn kind_rank(kind: wasmparser::ExternalKind) -> u32 {
+        match kind {
+            wasmparser::ExternalKind::Func => 0,
+            wasmparser::ExternalKind::Table => 1,
+            wasmparser::ExternalKind::Memory => 2,
+            wasmparser::ExternalKind::Global => 3,
+            wasmparser::ExternalKind::Tag => 4,
+        }
+    }
+
+    for ordering in [ExportOrdering::ByName, ExportOrdering::ByKind] {
+        let mut checked = 0;
+        for_each_module(
+            256,
+            |_| Config {
+                export_everything: true,
+                export_ordering: ordering,
+                ..Config::default()
+            },
+            |_, wasm_bytes| {
+                for payload in Parser::new(0).parse_all(wasm_bytes) {
+                    if let wasmparser::Payload::ExportSection(rdr) = payload.unwrap() {
+                        let exports = rdr.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+                        if exports.len() > 1 {
+                            checked += 1;
+                        }
+                        match ordering {
+                            ExportOrdering::ByName => {
+                                assert!(exports.windows(2).all(|w| w[0].name <= w[1].name));
+                            }
+                            ExportOrdering::ByKind => {
+                                assert!(
+                                    exports
+                                        .windows(2)
+                                        .all(|w| kind_rank(w[0].kind) <= kind_rank(w[1].kind))
+                                );
+                            }
+                            ExportOrdering::Generation => unreachable!(),
+                        }
+                    }
+                }
+            },
+        );
+        assert!(checked > 0, "{ordering:?}");
+    }
+}
+
+#[test]
+fn smoke_test_kebab_case_export_names() {
+    fn is_kebab_case(name: &str) -> bool {
+        name.split('-').all(|word| {
+            word.starts_with(|c: char| c.is_ascii_lowercase())
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+    }
+
+    for_each_module(
+        256,
+        |_| Config {
+            kebab_case_export_names: true,
+            min_exports: 5,
+            ..Config::default()
+        },
+        |_, wasm_bytes| {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                if let wasmparser::Payload::ExportSection(rdr) = payload.unwrap() {
+                    for export in rdr {
+                        let name = export.unwrap().name;
+                        assert!(is_kebab_case(name), "{name:?} is not kebab-case");
+                    }
+                }
+            }
+        },
+    );
+}
+
+#[test]
+fn smoke_test_export_imported_and_defined_entities() {
+    let imports = r#"
+        (import "env" "f" (func (param i32)))
+        (import "env" "t" (table 1 funcref))
+        (import "env" "m" (memory 1))
+        (import "env" "g" (global i32))
+        (import "env" "e" (tag (param f32)))
+    "#;
+    let available_imports = wat::parse_str(format!("(module {imports})")).unwrap();
+    // The type each kind of imported entity has when it's re-exported.
+    let reexported_types = get_exports(
+        WasmFeatures::default(),
+        &wat::parse_str(format!(
+            r#"(module {imports}
+                (export "f" (func 0))
+                (export "t" (table 0))
+                (export "m" (memory 0))
+                (export "g" (global 0))
+                (export "e" (tag 0)))"#
+        ))
+        .unwrap(),
+    );
+    let mut exported_imported = [false; 5];
+    let mut exported_defined = [false; 5];
+
+    for_each_module(
+        256,
+        |_| Config {
+            available_imports: Some(available_imports.clone()),
+            exceptions_enabled: true,
+            export_everything: true,
+            // `get_exports` expects final function types without supertypes.
+            gc_enabled: false,
+            ..Config::default()
+        },
+        |module, wasm_bytes| {
+            let mut exports = get_exports(module.config().features(), wasm_bytes).into_iter();
+
+            let mut num_imported = [0; 5];
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                match payload.unwrap() {
+                    wasmparser::Payload::ImportSection(rdr) => {
+                        for import in rdr {
+                            let kind = match import.unwrap().ty {
+                                wasmparser::TypeRef::Func(_) => 0,
+                                wasmparser::TypeRef::Table(_) => 1,
+                                wasmparser::TypeRef::Memory(_) => 2,
+                                wasmparser::TypeRef::Global(_) => 3,
+                                wasmparser::TypeRef::Tag(_) => 4,
+                            };
+                            num_imported[kind] += 1;
+                        }
+                    }
+                    wasmparser::Payload::ExportSection(rdr) => {
+                        for export in rdr {
+                            let export = export.unwrap();
+                            let kind = match export.kind {
+                                wasmparser::ExternalKind::Func => 0,
+                                wasmparser::ExternalKind::Table => 1,
+                                wasmparser::ExternalKind::Memory => 2,
+                                wasmparser::ExternalKind::Global => 3,
+                                wasmparser::ExternalKind::Tag => 4,
+                            };
+                            let (_, ty) = exports.next().unwrap();
+                            // Exports of imported entities must resolve to the
+                            // import's type rather than to a defined entity.
+                            if export.index < num_imported[kind] {
+                                assert_eq!(reexported_types[kind].1, ty);
+                                exported_imported[kind] = true;
+                            } else {
+                                exported_defined[kind] = true;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        },
+    );
+    assert_eq!(exported_imported, [true; 5]);
+    assert_eq!(exported_defined, [true; 5]);
+}
+
 fn get_exports(features: WasmFeatures, module: &[u8]) -> Vec<(String, ExportType)> {
     let mut validator = Validator::new_with_features(features);
     let types = validate(&mut validator, module);
@@ -6,7 +6,7 @@ use wasm_smith::{Config, Module};
 use wasmparser::{Parser, Validator, WasmFeatures, types::EntityType};
 
 mod common;
-use common::validate;
+use common::{for_each_module, validate};
 
 #[derive(Debug, PartialEq)]
 struct WasmExport(String, EntityType);
@@ -64,6 +64,60 @@ fn smoke_test_module_shape_without_gc_types() {
     smoke_test_imports_exports(test, 43);
 }
 
+#[test]
+fn smoke_test_module_shape_full_interface() {
+    let test = r#"
+        (module
+            (rec
+                (type $s (sub (struct (field (mut i32)))))
+                (type $f (func (param (ref null $s)) (result i32)))
+            )
+            (import "env" "g" (global $g i64))
+            (import "env" "s" (global (ref null $s)))
+            (import "env" "f" (func (type $f)))
+            (global (export "counter") (mut i32) i32.const 0)
+            (global (export "g") i64 global.get $g)
+            (export "imported" (global 1))
+            (func (export "f") (type $f) unreachable)
+        )
+        "#;
+    let wasm = wat::parse_str(test).unwrap();
+    let expected_types = get_types(&wasm);
+    let (expected_imports, expected_exports) = get_imports_exports(WasmFeatures::default(), &wasm);
+
+    let mut generated = 0;
+    for_each_module(
+        256,
+        |_| Config {
+            module_shape: Some(wasm.clone()),
+            ..Config::default()
+        },
+        |module, wasm_bytes| {
+            let generated_types = get_types(wasm_bytes);
+            assert_eq!(expected_types, generated_types[..expected_types.len()]);
+            let (generated_imports, generated_exports) =
+                get_imports_exports(module.config().features(), wasm_bytes);
+            assert_eq!(expected_imports, generated_imports);
+            assert_eq!(expected_exports, generated_exports);
+            generated += 1;
+        },
+    );
+    // Generating a module with a given shape never fails.
+    assert_eq!(generated, 256);
+}
+
+fn get_types(module: &[u8]) -> Vec<Vec<wasmparser::SubType>> {
+    let mut types = vec![];
+    for payload in Parser::new(0).parse_all(module) {
+        if let wasmparser::Payload::TypeSection(rdr) = payload.unwrap() {
+            for recgrp in rdr {
+                types.push(recgrp.unwrap().into_types().collect());
+            }
+        }
+    }
+    types
+}
+
 fn get_imports_exports(
     features: WasmFeatures,
     module: &[u8],
@@ -370,6 +370,51 @@ impl WasmFeatures {
         .union(WasmFeatures::CALL_INDIRECT_OVERLONG);
 }
 
+#[cfg(all(feature = "features", feature = "validate"))]
+impl WasmFeatures {
+    /// Returns a minimal set of features with which `wasm`, a core module or
+    /// component, validates.
+    ///
+    /// Features are removed one at a time from [`WasmFeatures::all`] as long
+    /// as `wasm` still validates without them, so no single feature can be
+    /// removed from the returned set. Note that this includes the
+    /// wasmparser-specific features such as [`WasmFeatures::FLOATS`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `wasm` doesn't validate even with all features
+    /// enabled.
+    pub fn minimal_for(wasm: &[u8]) -> crate::Result<WasmFeatures> {
+        crate::Validator::new_with_features(WasmFeatures::all()).validate_all(wasm)?;
+        let mut features = WasmFeatures::all();
+        for feature in WasmFeatures::all().iter() {
+            let without = features.difference(feature);
+            if crate::Validator::new_with_features(without)
+                .validate_all(wasm)
+                .is_ok()
+            {
+                features = without;
+            }
+        }
+        Ok(features)
+    }
+
+    /// Returns the minimal features, as computed by
+    /// [`WasmFeatures::minimal_for`], of both `a` and `b`.
+    ///
+    /// This can be used to check how a transformation from `a` to `b` changed
+    /// the features a module requires, for example with
+    /// [`WasmFeatures::difference`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either `a` or `b` doesn't validate with all
+    /// features enabled.
+    pub fn diff(a: &[u8], b: &[u8]) -> crate::Result<(WasmFeatures, WasmFeatures)> {
+        Ok((Self::minimal_for(a)?, Self::minimal_for(b)?))
+    }
+}
+
 #[cfg(feature = "features")]
 impl From<WasmFeaturesInflated> for WasmFeatures {
     #[inline]
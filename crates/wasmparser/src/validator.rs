@@ -64,7 +64,7 @@ use self::component::*;
 pub use self::core::ValidatorResources;
 use self::core::*;
 use self::types::{TypeAlloc, Types, TypesRef};
-pub use func::{FuncToValidate, FuncValidator, FuncValidatorAllocations};
+pub use func::{FuncToValidate, FuncValidator, FuncValidatorAllocations, validate_func_body};
 pub use operators::Frame;
 
 fn check_max(cur_len: usize, amt_added: u32, max: usize, desc: &str, offset: usize) -> Result<()> {
@@ -156,6 +156,10 @@ pub struct Validator {
     /// Enabled WebAssembly feature flags, dictating what's valid and what
     /// isn't.
     features: WasmFeatures,
+
+    /// The names of custom sections which are permitted, or `None` if all
+    /// custom sections are permitted.
+    custom_section_allowlist: Option<Set<String>>,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -387,6 +391,29 @@ impl Validator {
         &self.features
     }
 
+    /// Restricts which custom sections this validator accepts.
+    ///
+    /// Custom sections are ignored during validation by default. When
+    /// `allowlist` is `Some`, any custom section whose name isn't in the set
+    /// is instead reported as an error, for example to only permit `name`,
+    /// `producers`, and `target_features` sections. Passing `None` restores
+    /// the default of accepting all custom sections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use wasmparser::Validator;
+    ///
+    /// let mut validator = Validator::new();
+    /// validator.set_custom_section_allowlist(Some(["name".to_string()].into_iter().collect()));
+    ///
+    /// let wasm = wat::parse_str(r#"(module (@custom "secret" "shh"))"#).unwrap();
+    /// assert!(validator.validate_all(&wasm).is_err());
+    /// ```
+    pub fn set_custom_section_allowlist(&mut self, allowlist: Option<Set<String>>) {
+        self.custom_section_allowlist = allowlist;
+    }
+
     /// Reset this validator's state such that it is ready to validate a new
     /// Wasm module or component.
     ///
@@ -464,6 +491,9 @@ impl Validator {
             // have "invalid" types inside our current types list.
             features: _,
 
+            // The allowlist is configuration, just like the features.
+            custom_section_allowlist: _,
+
             state,
             module,
             #[cfg(feature = "component-model")]
@@ -529,6 +559,58 @@ impl Validator {
         Ok(last_types.unwrap())
     }
 
+    /// Validates an entire in-memory module or component, reporting as many
+    /// validation errors as possible instead of only the first.
+    ///
+    /// This is similar to [`Validator::validate_all`] except that errors are
+    /// accumulated rather than returned eagerly. Errors fall into two classes:
+    ///
+    /// * Errors within a function body are recoverable. Each function body is
+    ///   validated independently, so an error in one body is recorded and
+    ///   validation continues with the next body.
+    ///
+    /// * All other errors, such as malformed sections or invalid types,
+    ///   imports, exports, or initializers, are fatal. Subsequent sections
+    ///   may depend on the invalid item so parsing stops at the first such
+    ///   error. Function bodies that were already parsed are still
+    ///   validated.
+    ///
+    /// The returned errors are sorted by their offset in `bytes`.
+    pub fn validate_all_collecting(&mut self, bytes: &[u8]) -> Result<(), Vec<BinaryReaderError>> {
+        let mut errors = Vec::new();
+        let mut functions_to_validate = Vec::new();
+        let mut parser = Parser::new(0);
+        let _ = &mut parser;
+        #[cfg(feature = "features")]
+        parser.set_features(self.features);
+        for payload in parser.parse_all(bytes) {
+            match payload.and_then(|payload| self.payload(&payload)) {
+                Ok(ValidPayload::Func(a, b)) => functions_to_validate.push((a, b)),
+                Ok(_) => {}
+                Err(e) => {
+                    errors.push(e);
+                    break;
+                }
+            }
+        }
+
+        let mut allocs = FuncValidatorAllocations::default();
+        for (func, body) in functions_to_validate {
+            let mut validator = func.into_validator(allocs);
+            if let Err(e) = validator.validate(&body) {
+                errors.push(e);
+            }
+            allocs = validator.into_allocations();
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            errors.sort_by_key(|e| e.offset());
+            Err(errors)
+        }
+    }
+
     /// Gets the types known by the validator so far within the
     /// module/component `level` modules/components up from the
     /// module/component currently being parsed.
@@ -643,7 +725,7 @@ impl Validator {
 
             End(offset) => return Ok(ValidPayload::End(self.end(*offset)?)),
 
-            CustomSection { .. } => {} // no validation for custom sections
+            CustomSection(s) => self.custom_section(s)?,
             UnknownSection { id, range, .. } => self.unknown_section(*id, range)?,
         }
         Ok(ValidPayload::Ok)
@@ -1314,6 +1396,22 @@ impl Validator {
         )
     }
 
+    /// Validates [`Payload::CustomSection`](crate::Payload).
+    ///
+    /// The contents of custom sections aren't validated, but their names must
+    /// be in the allowlist set with [`Validator::set_custom_section_allowlist`],
+    /// if any.
+    pub fn custom_section(&mut self, section: &crate::CustomSectionReader<'_>) -> Result<()> {
+        match &self.custom_section_allowlist {
+            Some(allowlist) if !allowlist.contains(section.name()) => Err(format_err!(
+                section.range().start,
+                "custom section `{}` is not allowed",
+                section.name()
+            )),
+            _ => Ok(()),
+        }
+    }
+
     /// Validates [`Payload::UnknownSection`](crate::Payload).
     ///
     /// Currently always returns an error.
@@ -1620,4 +1718,100 @@ mod tests {
     fn reset_fresh_validator() {
         Validator::new().reset();
     }
+
+    #[test]
+    fn validate_all_collecting_reports_every_function_error() -> Result<()> {
+        let bytes = wat::parse_str(
+            r#"
+            (module
+                (func (result i32) i64.const 0)
+                (func (result i32) i32.const 0)
+                (func (param f32) local.get 1 drop)
+            )
+        "#,
+        )?;
+
+        let errors = Validator::new()
+            .validate_all_collecting(&bytes)
+            .unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message().contains("type mismatch"));
+        assert!(errors[1].message().contains("unknown local"));
+        assert!(errors[0].offset() < errors[1].offset());
+
+        let bytes = wat::parse_str("(module (func (result i32) i32.const 0))")?;
+        assert!(Validator::new().validate_all_collecting(&bytes).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn custom_section_allowlist() -> Result<()> {
+        let allowed = wat::parse_str(r#"(module $m (@custom "target_features" ""))"#)?;
+        let disallowed = wat::parse_str(r#"(module (@custom "secret" "shh"))"#)?;
+
+        let mut validator = Validator::new();
+        validator.set_custom_section_allowlist(Some(
+            ["name", "producers", "target_features"]
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        ));
+        validator.validate_all(&allowed)?;
+        validator.reset();
+        let err = validator.validate_all(&disallowed).err().unwrap();
+        assert!(
+            err.message()
+                .contains("custom section `secret` is not allowed")
+        );
+
+        // Without an allowlist every custom section is accepted.
+        let mut validator = Validator::new();
+        validator.validate_all(&disallowed)?;
+        validator.set_custom_section_allowlist(None);
+        validator.reset();
+        validator.validate_all(&disallowed)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn feature_diff() -> Result<()> {
+        let simd = wat::parse_str(
+            r#"
+            (module
+                (func (param i64 i64) (result i64)
+                    local.get 0
+                    i64x2.splat
+                    local.get 1
+                    i64x2.splat
+                    i64x2.add
+                    i64x2.extract_lane 0)
+            )
+        "#,
+        )?;
+        let scalar = wat::parse_str(
+            r#"
+            (module
+                (func (param i64 i64) (result i64)
+                    local.get 0
+                    local.get 1
+                    i64.add)
+            )
+        "#,
+        )?;
+
+        let (before, after) = WasmFeatures::diff(&simd, &scalar)?;
+        assert!(before.contains(WasmFeatures::SIMD));
+        assert!(!after.contains(WasmFeatures::SIMD));
+        assert_eq!(before.difference(after), WasmFeatures::SIMD);
+        assert!(after.difference(before).is_empty());
+
+        // Both still validate with their minimal features.
+        Validator::new_with_features(before).validate_all(&simd)?;
+        Validator::new_with_features(after).validate_all(&scalar)?;
+
+        assert!(WasmFeatures::diff(&simd, b"\0asm").is_err());
+        Ok(())
+    }
 }
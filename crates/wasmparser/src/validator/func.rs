@@ -318,6 +318,76 @@ arity mismatch in validation
     }
 }
 
+/// Validates a standalone function body against `resources` without needing
+/// to assemble it into a module.
+///
+/// The function has the type at `type_index` in `resources` and declares
+/// `locals` in addition to its parameters. The `ops` are the operators of the
+/// body, including the final `end`, as they would be produced by an
+/// [`OperatorsReader`](crate::OperatorsReader). Any types, functions, tables,
+/// etc. referenced by `ops` are looked up in `resources`, and operators are
+/// validated with the given `features`.
+///
+/// Errors in the function's type or `locals` are reported at offset zero, and
+/// errors in `ops` at one plus the index of the offending operator, so that
+/// offsets are ordered as they would be in an encoded function.
+///
+/// # Examples
+///
+/// ```
+/// # use wasmparser::*;
+/// # fn check(resources: &impl WasmModuleResources) -> Result<()> {
+/// // The type at index 0 of `resources` is `(func (param i32) (result i32))`.
+/// let ops = [
+///     Operator::LocalGet { local_index: 0 },
+///     Operator::LocalGet { local_index: 1 },
+///     Operator::I32Add,
+///     Operator::End,
+/// ];
+/// validate_func_body(0, &[ValType::I32], &ops, resources, WasmFeatures::default())?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn validate_func_body(
+    type_index: u32,
+    locals: &[ValType],
+    ops: &[Operator<'_>],
+    resources: &impl WasmModuleResources,
+    features: WasmFeatures,
+) -> Result<()> {
+    let mut validator = FuncValidator {
+        validator: OperatorValidator::new_func(
+            type_index,
+            0,
+            &features,
+            &resources,
+            Default::default(),
+        )?,
+        resources,
+        index: 0,
+    };
+    for ty in locals {
+        validator.define_locals(0, 1, *ty)?;
+    }
+    for (i, op) in ops.iter().enumerate() {
+        let offset = i + 1;
+        if validator.control_stack_height() == 0 {
+            bail!(
+                offset,
+                "operators remaining after end of function body or expression"
+            );
+        }
+        validator.op(offset, op)?;
+    }
+    if validator.control_stack_height() != 0 {
+        bail!(
+            ops.len() + 1,
+            "control frames remain at end of function body or expression"
+        );
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -605,4 +675,67 @@ mod tests {
             ]],
         );
     }
+
+    #[test]
+    fn validate_func_body_in_isolation() {
+        // Borrow the resources of a real module for the standalone body.
+        let wasm =
+            wat::parse_str("(module (type (func (param i32) (result i32))) (memory 1) (func))")
+                .unwrap();
+        let mut validator = Validator::new();
+        let mut resources = None;
+        for payload in Parser::new(0).parse_all(&wasm) {
+            match payload.unwrap() {
+                crate::Payload::CodeSectionEntry(body) => {
+                    resources = Some(validator.code_section_entry(&body).unwrap().resources);
+                }
+                p => {
+                    validator.payload(&p).unwrap();
+                }
+            }
+        }
+        let resources = resources.unwrap();
+
+        let locals = [ValType::I32];
+        let ops = [
+            Operator::LocalGet { local_index: 0 },
+            Operator::LocalGet { local_index: 1 },
+            Operator::I32Add,
+            Operator::I32Load {
+                memarg: crate::MemArg {
+                    align: 2,
+                    max_align: 2,
+                    offset: 0,
+                    memory: 0,
+                },
+            },
+            Operator::End,
+        ];
+        assert!(validate_func_body(0, &locals, &ops, &resources, WasmFeatures::default()).is_ok());
+
+        // `i32.add` with only one operand underflows the stack.
+        let ops = [
+            Operator::LocalGet { local_index: 0 },
+            Operator::I32Add,
+            Operator::End,
+        ];
+        let err =
+            validate_func_body(0, &locals, &ops, &resources, WasmFeatures::default()).unwrap_err();
+        assert_eq!(err.offset(), 2);
+        assert!(err.message().contains("type mismatch"), "{err}");
+
+        // A body missing its final `end`.
+        let ops = [Operator::LocalGet { local_index: 0 }];
+        let err =
+            validate_func_body(0, &locals, &ops, &resources, WasmFeatures::default()).unwrap_err();
+        assert!(err.message().contains("control frames remain"), "{err}");
+
+        // An empty body is valid for the `(func)` type at index 1, but the
+        // function's type must be in `resources`.
+        let ops = [Operator::End];
+        assert!(validate_func_body(1, &[], &ops, &resources, WasmFeatures::default()).is_ok());
+        let err =
+            validate_func_body(2, &[], &ops, &resources, WasmFeatures::default()).unwrap_err();
+        assert_eq!(err.offset(), 0);
+    }
 }